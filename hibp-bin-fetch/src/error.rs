@@ -27,6 +27,9 @@ pub enum Error {
     #[error("invalid configuration: {0}")]
     InvalidConfig(&'static str),
 
+    #[error("dataset error: {0}")]
+    Dataset(#[from] hibp_verifier::Error),
+
     #[error("Download failed after {retries} retries for prefix {prefix}")]
     MaxRetriesExceeded { prefix: CompactString, retries: u32 },
 }
@@ -15,7 +15,7 @@
 //! Each prefix file (e.g., `00000.bin`, `FFFFF.bin`) contains:
 //!
 //! - Fixed 6-byte records (bytes 2-7 of the SHA1 hash)
-//! - Sorted in ascending order
+//! - Sorted in ascending order, or in Eytzinger order with `fetch --layout eytzinger`
 //! - Direct indexing: record N is at byte offset N * 6
 //!
 //! This enables O(log n) binary search with no parsing overhead, which is exactly
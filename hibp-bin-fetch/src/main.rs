@@ -8,6 +8,9 @@ use std::time::Duration;
 use clap::{Parser, Subcommand};
 use hibp_bin_fetch::serve::{ServeArgs, run as serve_run};
 use hibp_bin_fetch::{Error, TOTAL_PREFIXES, get_completed_prefixes, worker};
+use hibp_verifier::layered;
+use hibp_verifier::prefilter::{PREFILTER_FILE, Prefilter};
+use hibp_verifier::search::RecordLayout;
 use indicatif::{ProgressBar, ProgressStyle};
 use tokio::fs;
 
@@ -35,6 +38,8 @@ enum Command {
     Fetch(FetchArgs),
     /// Run as a sync server, downloading nightly and serving changed files to clients
     Serve(ServeArgs),
+    /// Compile a wordlist into a blocklist or allowlist layer for hibp-verifier
+    CompileWordlist(CompileWordlistArgs),
 }
 
 #[derive(clap::Args, Debug)]
@@ -62,6 +67,27 @@ struct FetchArgs {
     /// Enable progress bar (default: true)
     #[arg(long, default_value_t = true)]
     progress: bool,
+
+    /// Build a pre-filter (prefilter.fuse) over the downloaded records once the download
+    /// completes
+    #[arg(long)]
+    prefilter: bool,
+
+    /// Order of the records within each prefix file: `sorted`, or `eytzinger` for
+    /// branch-free lookups on datasets that are never synced
+    #[arg(long, default_value_t = RecordLayout::Sorted)]
+    layout: RecordLayout,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompileWordlistArgs {
+    /// Wordlist with one password per line
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Output directory for the compiled prefix files. Existing .bin files in it are replaced
+    #[arg(short, long)]
+    output: PathBuf,
 }
 
 #[tokio::main]
@@ -71,6 +97,7 @@ async fn main() -> Result<(), Error> {
     match cli.command {
         Command::Fetch(args) => fetch(args).await,
         Command::Serve(args) => serve_run(args, None).await,
+        Command::CompileWordlist(args) => compile_wordlist(args).await,
     }
 }
 
@@ -94,6 +121,14 @@ async fn fetch(args: FetchArgs) -> Result<(), Error> {
 
     fs::create_dir_all(&args.output).await?;
 
+    // Files from an earlier run must be in the same order as the ones about to be written.
+    if args.resume && RecordLayout::load(&args.output)? != args.layout {
+        return Err(Error::InvalidConfig(
+            "--layout differs from the dataset being resumed",
+        ));
+    }
+    args.layout.write(&args.output)?;
+
     let completed = if args.resume {
         get_completed_prefixes(&args.output).await?
     } else {
@@ -107,9 +142,19 @@ async fn fetch(args: FetchArgs) -> Result<(), Error> {
 
     if total_to_download == 0 {
         println!("Nothing to download - all prefixes already exist.");
+        if args.prefilter {
+            build_prefilter(args.output).await?;
+        }
         return Ok(());
     }
 
+    // A filter from an earlier run would miss the records about to be downloaded.
+    match fs::remove_file(args.output.join(PREFILTER_FILE)).await {
+        Ok(()) => println!("Removed stale pre-filter"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
     println!(
         "Downloading {} prefixes to {:?} using {} concurrent workers",
         total_to_download, args.output, args.concurrent_workers
@@ -161,9 +206,10 @@ async fn fetch(args: FetchArgs) -> Result<(), Error> {
     for chunk in chunks {
         let client = client.clone();
         let output_dir = args.output.clone();
+        let layout = args.layout;
         let progress = Arc::clone(&progress_counter);
         handles.push(tokio::spawn(async move {
-            worker(client, output_dir, chunk, progress).await
+            worker(client, output_dir, chunk, layout, progress).await
         }));
     }
 
@@ -195,6 +241,34 @@ async fn fetch(args: FetchArgs) -> Result<(), Error> {
     }
 
     println!("Download complete!");
+    if args.prefilter {
+        build_prefilter(args.output).await?;
+    }
+    Ok(())
+}
+
+async fn build_prefilter(output: PathBuf) -> Result<(), Error> {
+    println!("Building pre-filter...");
+    let size = tokio::task::spawn_blocking(move || -> Result<usize, Error> {
+        let filter = Prefilter::build(&output, None)?;
+        filter.write(&output.join(PREFILTER_FILE))?;
+        Ok(filter.size_bytes())
+    })
+    .await
+    .map_err(|e| Error::Io(io::Error::other(format!("Task panicked: {}", e))))??;
+    println!("Pre-filter written ({} MiB)", size >> 20);
+    Ok(())
+}
+
+async fn compile_wordlist(args: CompileWordlistArgs) -> Result<(), Error> {
+    let output = args.output.clone();
+    let count = tokio::task::spawn_blocking(move || -> Result<usize, Error> {
+        let words = std::io::BufReader::new(std::fs::File::open(&args.input)?);
+        Ok(layered::compile_wordlist(words, &args.output)?)
+    })
+    .await
+    .map_err(|e| Error::Io(io::Error::other(format!("Task panicked: {}", e))))??;
+    println!("Compiled {} passwords into {:?}", count, output);
     Ok(())
 }
 
@@ -211,9 +285,34 @@ mod tests {
             force: false,
             limit: TOTAL_PREFIXES - 1,
             progress: false,
+            prefilter: false,
+            layout: RecordLayout::Sorted,
+        };
+
+        let err = fetch(args).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+
+    // Resuming a sorted dataset with --layout eytzinger → rejected before downloading.
+    #[tokio::test]
+    async fn fetch_rejects_resume_with_other_layout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let args = FetchArgs {
+            output: tmp.path().to_path_buf(),
+            concurrent_workers: 1,
+            resume: true,
+            force: false,
+            limit: 0,
+            progress: false,
+            prefilter: false,
+            layout: RecordLayout::Eytzinger,
         };
 
         let err = fetch(args).await.unwrap_err();
         assert!(matches!(err, Error::InvalidConfig(_)));
+        assert_eq!(
+            RecordLayout::load(tmp.path()).unwrap(),
+            RecordLayout::Sorted
+        );
     }
 }
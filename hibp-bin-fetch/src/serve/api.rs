@@ -43,6 +43,13 @@ pub struct SegmentQuery {
     segment: u8,
     of: u8,
     since: Option<String>,
+    /// Last prefix the client has already written for this segment. Only prefixes after it
+    /// are streamed, so an interrupted segment can resume mid-way.
+    after: Option<CompactString>,
+    /// First and last prefix (5 hex chars, inclusive) of the range being synced. Default to
+    /// the whole dataset, so a sharded client only receives its own prefixes.
+    start: Option<CompactString>,
+    end: Option<CompactString>,
 }
 
 #[web::get("/v1/status")]
@@ -79,6 +86,20 @@ pub async fn get_segment(
         return Err(ApiError::InvalidSegmentParams);
     }
 
+    let after = match query.after.as_deref() {
+        None => None,
+        Some(s) => Some(parse_prefix(s).ok_or_else(|| {
+            tracing::warn!(after = %s, "invalid after prefix");
+            ApiError::InvalidAfterPrefix
+        })?),
+    };
+
+    let (range_start, range_end) = prefix_range(query.start.as_deref(), query.end.as_deref())
+        .ok_or_else(|| {
+            tracing::warn!(start = ?query.start, end = ?query.end, "invalid prefix range");
+            ApiError::InvalidPrefixRange
+        })?;
+
     let stream = if let Some(ref since_str) = query.since {
         let since_ts = since_str.parse::<DateTime<Utc>>().map_err(|_| {
             tracing::warn!(since = %since_str, "invalid since timestamp");
@@ -100,13 +121,17 @@ pub async fn get_segment(
                 .prefixes
                 .iter()
                 .filter_map(|s| u32::from_str_radix(s, 16).ok())
+                .filter(|&p| (range_start..range_end).contains(&(p as usize)))
                 .collect()
         };
         all_changed.sort_unstable();
         let (start, end) = segment_bounds(all_changed.len(), segment, of);
-        encode_prefix_list(state.dirs.clone(), all_changed[start..end].to_vec())
+        let remaining = skip_through(&all_changed[start..end], after);
+        encode_prefix_list(state.dirs.clone(), remaining.to_vec())
     } else {
-        let (start, end) = segment_bounds(TOTAL_PREFIXES as usize, segment, of);
+        let (start, end) = segment_bounds(range_end - range_start, segment, of);
+        let (start, end) = (range_start + start, range_start + end);
+        let start = after.map_or(start, |a| start.max(a as usize + 1).min(end));
         encode_segment(state.dirs.clone(), start as u32, end as u32)
     };
 
@@ -136,6 +161,33 @@ fn segment_bounds(total: usize, segment: usize, of: usize) -> (usize, usize) {
     (start, end)
 }
 
+/// Returns the tail of a sorted prefix list that comes strictly after `after`.
+fn skip_through(prefixes: &[u32], after: Option<u32>) -> &[u32] {
+    match after {
+        Some(a) => &prefixes[prefixes.partition_point(|&p| p <= a)..],
+        None => prefixes,
+    }
+}
+
+/// Resolves the optional inclusive `start`/`end` prefixes to a half-open range, clamped to the
+/// prefixes this server holds. `None` if either is malformed or `start` is after `end`.
+fn prefix_range(start: Option<&str>, end: Option<&str>) -> Option<(usize, usize)> {
+    let start = start.map_or(Some(0), parse_prefix)? as usize;
+    let end = end.map_or(Some(TOTAL_PREFIXES - 1), parse_prefix)? as usize;
+    if start > end {
+        return None;
+    }
+    let limit = TOTAL_PREFIXES as usize;
+    Some((start.min(limit), (end + 1).min(limit)))
+}
+
+fn parse_prefix(s: &str) -> Option<u32> {
+    if s.len() != 5 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(s, 16).ok()
+}
+
 pub(crate) fn encode_segment(
     dirs: Arc<Dirs>,
     start: u32,
@@ -226,6 +278,38 @@ mod tests {
         assert_eq!(segment_bounds(10, 2, 3), (8, 10));
     }
 
+    #[test]
+    fn skip_through_resumes_after_prefix() {
+        let prefixes = [0x00001, 0x00005, 0x0000A, 0x0000F];
+        assert_eq!(skip_through(&prefixes, None), &prefixes);
+        assert_eq!(skip_through(&prefixes, Some(0x00005)), &[0x0000A, 0x0000F]);
+        assert_eq!(skip_through(&prefixes, Some(0x00006)), &[0x0000A, 0x0000F]);
+        assert_eq!(skip_through(&prefixes, Some(0x00000)), &prefixes);
+        assert!(skip_through(&prefixes, Some(0x0000F)).is_empty());
+    }
+
+    #[test]
+    fn parse_prefix_requires_five_hex_chars() {
+        assert_eq!(parse_prefix("0000A"), Some(0x0000A));
+        assert_eq!(parse_prefix("abcde"), Some(0xABCDE));
+        assert_eq!(parse_prefix("ABCD"), None);
+        assert_eq!(parse_prefix("ABCDEF"), None);
+        assert_eq!(parse_prefix("ABCDG"), None);
+        assert_eq!(parse_prefix("+ABCD"), None);
+    }
+
+    #[test]
+    fn prefix_range_defaults_and_clamps() {
+        let all = TOTAL_PREFIXES as usize;
+        assert_eq!(prefix_range(None, None), Some((0, all)));
+        assert_eq!(prefix_range(Some("00002"), Some("00005")), Some((2, 6)));
+        assert_eq!(prefix_range(Some("00003"), None), Some((3, all)));
+        assert_eq!(prefix_range(None, Some("FFFFF")), Some((0, all)));
+        assert_eq!(prefix_range(Some("00004"), Some("00004")), Some((4, 5)));
+        assert_eq!(prefix_range(Some("00005"), Some("00002")), None);
+        assert_eq!(prefix_range(Some("5"), None), None);
+    }
+
     #[tokio::test]
     async fn encode_decode_roundtrip() {
         let tmp = tempfile::tempdir().unwrap();
@@ -11,6 +11,12 @@ pub enum ApiError {
     #[error("since parameter is not a valid RFC 3339 timestamp")]
     InvalidSinceTimestamp,
 
+    #[error("after parameter is not a 5-character hex prefix")]
+    InvalidAfterPrefix,
+
+    #[error("start and end must be 5-character hex prefixes with start <= end")]
+    InvalidPrefixRange,
+
     #[error("server data is not one cycle ahead of the requested since timestamp")]
     NotOneCycleBehind,
 
@@ -26,7 +32,10 @@ struct ApiErrorBody {
 impl WebResponseError<DefaultError> for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
-            Self::InvalidSegmentParams | Self::InvalidSinceTimestamp => StatusCode::BAD_REQUEST,
+            Self::InvalidSegmentParams
+            | Self::InvalidSinceTimestamp
+            | Self::InvalidAfterPrefix
+            | Self::InvalidPrefixRange => StatusCode::BAD_REQUEST,
             Self::NotOneCycleBehind => StatusCode::CONFLICT,
             Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -39,6 +48,8 @@ impl WebResponseError<DefaultError> for ApiError {
         let code = match self {
             Self::InvalidSegmentParams => "invalid_segment_params",
             Self::InvalidSinceTimestamp => "invalid_since_timestamp",
+            Self::InvalidAfterPrefix => "invalid_after_prefix",
+            Self::InvalidPrefixRange => "invalid_prefix_range",
             Self::NotOneCycleBehind => "not_one_cycle_behind",
             Self::Internal(_) => "internal_error",
         };
@@ -5,6 +5,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 use compact_str::CompactString;
+use hibp_verifier::search::{RecordLayout, to_eytzinger};
 use tokio::fs;
 
 use crate::conversion::{line_to_sha1t48, prefix_to_hex};
@@ -93,17 +94,23 @@ pub async fn fetch_prefix_bytes(
     }))
 }
 
-/// Download a single prefix and write it to a binary file in the output directory.
+/// Download a single prefix and write it to a binary file in the output directory, with its
+/// records in `layout` order.
 #[tracing::instrument(skip(client, output_dir, records_buf))]
 pub async fn download_and_write_prefix(
     client: &reqwest::Client,
     output_dir: &Path,
     prefix: u32,
+    layout: RecordLayout,
     records_buf: &mut Vec<[u8; 6]>,
 ) -> Result<(), Error> {
     let prefix_hex = prefix_to_hex(prefix);
     let prefix_str = std::str::from_utf8(&prefix_hex).unwrap();
-    let bytes = fetch_prefix_bytes(client, prefix, prefix_str, records_buf).await?;
+    let mut bytes = fetch_prefix_bytes(client, prefix, prefix_str, records_buf).await?;
+    // The API serves suffixes in ascending order, so records_buf is already sorted.
+    if layout == RecordLayout::Eytzinger {
+        bytes = to_eytzinger(records_buf).concat();
+    }
     let file_path = bin_path(output_dir, prefix_str);
     fs::write(&file_path, &bytes).await?;
     Ok(())
@@ -154,11 +161,12 @@ pub async fn worker(
     client: reqwest::Client,
     output_dir: PathBuf,
     prefixes: Vec<u32>,
+    layout: RecordLayout,
     progress: Arc<AtomicU64>,
 ) -> Result<(), Error> {
     let mut records_buf: Vec<[u8; 6]> = Vec::with_capacity(2000);
     for prefix in prefixes {
-        download_and_write_prefix(&client, &output_dir, prefix, &mut records_buf).await?;
+        download_and_write_prefix(&client, &output_dir, prefix, layout, &mut records_buf).await?;
         progress.fetch_add(1, Ordering::Relaxed);
     }
     Ok(())
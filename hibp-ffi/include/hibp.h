@@ -0,0 +1,112 @@
+#ifndef HIBP_H
+#define HIBP_H
+
+/* Generated by cbindgen from hibp-ffi/src/lib.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of every `hibp_*` call.
+typedef enum HibpStatus {
+  // The call succeeded and its out-parameters are set.
+  HIBP_STATUS_OK = 0,
+  // A required pointer argument was NULL.
+  HIBP_STATUS_NULL_POINTER = 1,
+  // The dataset directory does not exist.
+  HIBP_STATUS_DATASET_MISSING = 2,
+  // The dataset path is not a directory.
+  HIBP_STATUS_NOT_A_DIRECTORY = 3,
+  // The prefix file for a password is missing; the dataset is incomplete.
+  HIBP_STATUS_PREFIX_FILE_MISSING = 4,
+  // A prefix file or the pre-filter is corrupt.
+  HIBP_STATUS_CORRUPT_DATASET = 5,
+  // The password's prefix is outside the shard the dataset holds.
+  HIBP_STATUS_PREFIX_NOT_IN_RANGE = 6,
+  // Any other I/O error, typically transient.
+  HIBP_STATUS_IO = 7,
+  // The library panicked. This is a bug; please report it.
+  HIBP_STATUS_PANIC = 8,
+} HibpStatus;
+
+// An open dataset. Create with [`hibp_open`], free with [`hibp_close`].
+typedef struct HibpChecker HibpChecker;
+
+// A password for [`hibp_check_batch`]: `len` bytes at `ptr`, not NUL-terminated.
+typedef struct HibpPassword {
+  const uint8_t *ptr;
+  size_t len;
+} HibpPassword;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens the dataset at `dataset_path`, a NUL-terminated path, and stores a new handle in
+// `*out`. The dataset layout is validated, so a wrong path fails here rather than on the
+// first lookup.
+//
+// # Safety
+//
+// `dataset_path` must be a valid NUL-terminated string and `out` must be valid for writes.
+enum HibpStatus hibp_open(const char *dataset_path, struct HibpChecker **out);
+
+// Frees a handle from [`hibp_open`]. NULL is ignored.
+//
+// # Safety
+//
+// `checker` must be NULL or a handle from [`hibp_open`] that has not been closed, and no
+// other thread may be using it.
+void hibp_close(struct HibpChecker *checker);
+
+// Checks the `len` bytes at `password`, which need not be NUL-terminated, and stores whether
+// they are breached in `*breached`. The bytes are hashed as given, so pass UTF-8 to match
+// HIBP.
+//
+// # Safety
+//
+// `checker` must be a live handle from [`hibp_open`], `password` must point to `len`
+// readable bytes (it may be NULL if `len` is 0) and `breached` must be valid for writes.
+enum HibpStatus hibp_check_password(const struct HibpChecker *checker,
+                                    const uint8_t *password,
+                                    size_t len,
+                                    bool *breached);
+
+// Checks a password by its 20-byte binary SHA-1 digest and stores whether it is breached in
+// `*breached`. Only the first 8 bytes are compared, as that is all the dataset stores.
+//
+// # Safety
+//
+// `checker` must be a live handle from [`hibp_open`], `sha1` must point to 20 readable
+// bytes and `breached` must be valid for writes.
+enum HibpStatus hibp_check_sha1(const struct HibpChecker *checker,
+                                const uint8_t (*sha1)[20],
+                                bool *breached);
+
+// Checks `count` passwords and stores each result at the same index of `breached`.
+//
+// Stops at the first failing lookup and returns its status; results before it are set,
+// the rest are left untouched.
+//
+// # Safety
+//
+// `checker` must be a live handle from [`hibp_open`], and `passwords` and `breached` must
+// point to `count` elements (either may be NULL if `count` is 0). Each password must
+// satisfy the requirements of [`hibp_check_password`].
+enum HibpStatus hibp_check_batch(const struct HibpChecker *checker,
+                                 const struct HibpPassword *passwords,
+                                 size_t count,
+                                 bool *breached);
+
+// A static, NUL-terminated description of `status`. Never NULL and never freed. Takes an `int`
+// rather than the enum so that any value from C is defined behaviour; values that are not a
+// `HibpStatus` get "unknown status".
+const char *hibp_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HIBP_H */
@@ -0,0 +1,348 @@
+//! A C ABI for [hibp-verifier](https://crates.io/crates/hibp-verifier), so C, C++, Go and
+//! other languages get the same lookups as Rust callers.
+//!
+//! The crate builds `libhibp.so` and `libhibp.a`; `include/hibp.h` declares the functions
+//! below. Every function returns an [`HibpStatus`] and writes results through out-pointers.
+//! A checker handle may be shared between threads.
+//!
+//! ```c
+//! #include "hibp.h"
+//!
+//! HibpChecker *checker;
+//! if (hibp_open("/path/to/hibp-data", &checker) != HIBP_STATUS_OK) {
+//!     /* dataset missing or incomplete */
+//! }
+//!
+//! bool breached;
+//! HibpStatus status = hibp_check_password(checker, (const uint8_t *)pw, strlen(pw), &breached);
+//! if (status != HIBP_STATUS_OK) {
+//!     fprintf(stderr, "lookup failed: %s\n", hibp_status_message(status));
+//! }
+//!
+//! hibp_close(checker);
+//! ```
+
+use std::ffi::{CStr, OsStr, c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use hibp_verifier::{Error, OwnedBreachChecker};
+use sha1::{Digest, Sha1};
+
+/// Result of every `hibp_*` call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HibpStatus {
+    /// The call succeeded and its out-parameters are set.
+    Ok = 0,
+    /// A required pointer argument was NULL.
+    NullPointer = 1,
+    /// The dataset directory does not exist.
+    DatasetMissing = 2,
+    /// The dataset path is not a directory.
+    NotADirectory = 3,
+    /// The prefix file for a password is missing; the dataset is incomplete.
+    PrefixFileMissing = 4,
+    /// A prefix file or the pre-filter is corrupt.
+    CorruptDataset = 5,
+    /// The password's prefix is outside the shard the dataset holds.
+    PrefixNotInRange = 6,
+    /// Any other I/O error, typically transient.
+    Io = 7,
+    /// The library panicked. This is a bug; please report it.
+    Panic = 8,
+}
+
+impl HibpStatus {
+    const ALL: [Self; 9] = [
+        Self::Ok,
+        Self::NullPointer,
+        Self::DatasetMissing,
+        Self::NotADirectory,
+        Self::PrefixFileMissing,
+        Self::CorruptDataset,
+        Self::PrefixNotInRange,
+        Self::Io,
+        Self::Panic,
+    ];
+
+    fn message(self) -> &'static CStr {
+        match self {
+            Self::Ok => c"ok",
+            Self::NullPointer => c"a required pointer argument was NULL",
+            Self::DatasetMissing => c"dataset directory not found",
+            Self::NotADirectory => c"dataset path is not a directory",
+            Self::PrefixFileMissing => c"prefix file not found; the dataset is incomplete",
+            Self::CorruptDataset => c"dataset file is corrupt",
+            Self::PrefixNotInRange => c"password prefix is outside the loaded shard",
+            Self::Io => c"I/O error",
+            Self::Panic => c"internal error in hibp-ffi",
+        }
+    }
+}
+
+impl From<Error> for HibpStatus {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::DatasetMissing(_) => Self::DatasetMissing,
+            Error::NotADirectory(_) => Self::NotADirectory,
+            Error::PrefixFileMissing { .. } => Self::PrefixFileMissing,
+            Error::CorruptRecordLength { .. }
+            | Error::PrefixFileTooLarge { .. }
+            | Error::CorruptPrefilter(_)
+            | Error::UnknownRecordLayout(_)
+            | Error::RecordLayoutMismatch(_) => Self::CorruptDataset,
+            Error::PrefixNotInRange { .. } => Self::PrefixNotInRange,
+            _ => Self::Io,
+        }
+    }
+}
+
+/// An open dataset. Create with [`hibp_open`], free with [`hibp_close`].
+pub struct HibpChecker(OwnedBreachChecker);
+
+/// A password for [`hibp_check_batch`]: `len` bytes at `ptr`, not NUL-terminated.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HibpPassword {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+/// Runs `f`, turning a panic into [`HibpStatus::Panic`] rather than unwinding into C.
+fn guard(f: impl FnOnce() -> Result<(), HibpStatus>) -> HibpStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => HibpStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => HibpStatus::Panic,
+    }
+}
+
+/// # Safety
+///
+/// Unless `len` is 0, `ptr` must point to `len` readable bytes.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], HibpStatus> {
+    match len {
+        0 => Ok(&[]),
+        _ if ptr.is_null() => Err(HibpStatus::NullPointer),
+        // SAFETY: upheld by the caller.
+        _ => Ok(unsafe { slice::from_raw_parts(ptr, len) }),
+    }
+}
+
+fn check_bytes(checker: &OwnedBreachChecker, password: &[u8]) -> Result<bool, HibpStatus> {
+    let hash: [u8; 20] = Sha1::digest(password).into();
+    Ok(checker.contains_hash(&hash)?)
+}
+
+/// Opens the dataset at `dataset_path`, a NUL-terminated path, and stores a new handle in
+/// `*out`. The dataset layout is validated, so a wrong path fails here rather than on the
+/// first lookup.
+///
+/// # Safety
+///
+/// `dataset_path` must be a valid NUL-terminated string and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hibp_open(
+    dataset_path: *const c_char,
+    out: *mut *mut HibpChecker,
+) -> HibpStatus {
+    guard(|| {
+        if dataset_path.is_null() || out.is_null() {
+            return Err(HibpStatus::NullPointer);
+        }
+        // SAFETY: upheld by the caller.
+        let path = OsStr::from_bytes(unsafe { CStr::from_ptr(dataset_path) }.to_bytes());
+        let checker = OwnedBreachChecker::open(path)?;
+        // SAFETY: upheld by the caller.
+        unsafe { out.write(Box::into_raw(Box::new(HibpChecker(checker)))) };
+        Ok(())
+    })
+}
+
+/// Frees a handle from [`hibp_open`]. NULL is ignored.
+///
+/// # Safety
+///
+/// `checker` must be NULL or a handle from [`hibp_open`] that has not been closed, and no
+/// other thread may be using it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hibp_close(checker: *mut HibpChecker) {
+    if !checker.is_null() {
+        // SAFETY: upheld by the caller.
+        drop(unsafe { Box::from_raw(checker) });
+    }
+}
+
+/// Checks the `len` bytes at `password`, which need not be NUL-terminated, and stores whether
+/// they are breached in `*breached`. The bytes are hashed as given, so pass UTF-8 to match
+/// HIBP.
+///
+/// # Safety
+///
+/// `checker` must be a live handle from [`hibp_open`], `password` must point to `len`
+/// readable bytes (it may be NULL if `len` is 0) and `breached` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hibp_check_password(
+    checker: *const HibpChecker,
+    password: *const u8,
+    len: usize,
+    breached: *mut bool,
+) -> HibpStatus {
+    guard(|| {
+        // SAFETY: upheld by the caller.
+        let (Some(checker), false) = (unsafe { checker.as_ref() }, breached.is_null()) else {
+            return Err(HibpStatus::NullPointer);
+        };
+        // SAFETY: upheld by the caller.
+        let password = unsafe { bytes(password, len) }?;
+        let result = check_bytes(&checker.0, password)?;
+        // SAFETY: upheld by the caller.
+        unsafe { breached.write(result) };
+        Ok(())
+    })
+}
+
+/// Checks a password by its 20-byte binary SHA-1 digest and stores whether it is breached in
+/// `*breached`. Only the first 8 bytes are compared, as that is all the dataset stores.
+///
+/// # Safety
+///
+/// `checker` must be a live handle from [`hibp_open`], `sha1` must point to 20 readable
+/// bytes and `breached` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hibp_check_sha1(
+    checker: *const HibpChecker,
+    sha1: *const [u8; 20],
+    breached: *mut bool,
+) -> HibpStatus {
+    guard(|| {
+        // SAFETY: upheld by the caller.
+        let (Some(checker), Some(hash), false) = (
+            unsafe { checker.as_ref() },
+            unsafe { sha1.as_ref() },
+            breached.is_null(),
+        ) else {
+            return Err(HibpStatus::NullPointer);
+        };
+        let result = checker.0.contains_hash(hash)?;
+        // SAFETY: upheld by the caller.
+        unsafe { breached.write(result) };
+        Ok(())
+    })
+}
+
+/// Checks `count` passwords and stores each result at the same index of `breached`.
+///
+/// Stops at the first failing lookup and returns its status; results before it are set,
+/// the rest are left untouched.
+///
+/// # Safety
+///
+/// `checker` must be a live handle from [`hibp_open`], and `passwords` and `breached` must
+/// point to `count` elements (either may be NULL if `count` is 0). Each password must
+/// satisfy the requirements of [`hibp_check_password`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hibp_check_batch(
+    checker: *const HibpChecker,
+    passwords: *const HibpPassword,
+    count: usize,
+    breached: *mut bool,
+) -> HibpStatus {
+    guard(|| {
+        // SAFETY: upheld by the caller.
+        let Some(checker) = (unsafe { checker.as_ref() }) else {
+            return Err(HibpStatus::NullPointer);
+        };
+        if count == 0 {
+            return Ok(());
+        }
+        if passwords.is_null() || breached.is_null() {
+            return Err(HibpStatus::NullPointer);
+        }
+        // SAFETY: upheld by the caller.
+        let passwords = unsafe { slice::from_raw_parts(passwords, count) };
+        for (i, password) in passwords.iter().enumerate() {
+            // SAFETY: upheld by the caller.
+            let password = unsafe { bytes(password.ptr, password.len) }?;
+            let result = check_bytes(&checker.0, password)?;
+            // SAFETY: upheld by the caller; `i < count`.
+            unsafe { breached.add(i).write(result) };
+        }
+        Ok(())
+    })
+}
+
+/// A static, NUL-terminated description of `status`. Never NULL and never freed. Takes an `int`
+/// rather than the enum so that any value from C is defined behaviour; values that are not a
+/// `HibpStatus` get "unknown status".
+#[unsafe(no_mangle)]
+pub extern "C" fn hibp_status_message(status: c_int) -> *const c_char {
+    let message = HibpStatus::ALL
+        .into_iter()
+        .find(|s| *s as c_int == status)
+        .map_or(c"unknown status", HibpStatus::message);
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    // Empty input is valid with a NULL pointer; any other length needs a pointer.
+    #[test]
+    fn bytes_accepts_null_only_when_empty() {
+        assert_eq!(unsafe { bytes(ptr::null(), 0) }, Ok(&[][..]));
+        assert_eq!(
+            unsafe { bytes(ptr::null(), 3) },
+            Err(HibpStatus::NullPointer)
+        );
+    }
+
+    #[test]
+    fn null_arguments_are_rejected() {
+        let mut out = ptr::null_mut();
+        assert_eq!(
+            unsafe { hibp_open(ptr::null(), &mut out) },
+            HibpStatus::NullPointer
+        );
+        assert!(out.is_null());
+
+        let mut breached = false;
+        let status = unsafe { hibp_check_password(ptr::null(), ptr::null(), 0, &mut breached) };
+        assert_eq!(status, HibpStatus::NullPointer);
+        let status = unsafe { hibp_check_batch(ptr::null(), ptr::null(), 0, ptr::null_mut()) };
+        assert_eq!(status, HibpStatus::NullPointer);
+        unsafe { hibp_close(ptr::null_mut()) };
+    }
+
+    #[test]
+    fn open_maps_dataset_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let missing = std::ffi::CString::new(
+            tmp.path().join("missing").into_os_string().into_encoded_bytes(),
+        )
+        .unwrap();
+        let mut out = ptr::null_mut();
+        assert_eq!(
+            unsafe { hibp_open(missing.as_ptr(), &mut out) },
+            HibpStatus::DatasetMissing
+        );
+        assert!(out.is_null());
+    }
+
+    #[test]
+    fn every_status_has_a_message() {
+        for status in HibpStatus::ALL {
+            let message = unsafe { CStr::from_ptr(hibp_status_message(status as c_int)) };
+            assert_eq!(message, status.message());
+        }
+        for status in [-1, 9, c_int::MAX] {
+            let message = unsafe { CStr::from_ptr(hibp_status_message(status)) };
+            assert_eq!(message, c"unknown status");
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use hibp_verifier::test_util::write_dataset;
+
+/// Directory holding `libhibp.so`: cargo leaves the library built for tests in `deps`, next
+/// to this test binary.
+fn lib_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+// tests/harness.c compiled against include/hibp.h and linked to the cdylib → every check in
+// it passes.
+#[test]
+fn c_harness_passes() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp = tempfile::tempdir().unwrap();
+    let dataset = tmp.path().join("dataset");
+    write_dataset(&dataset, &["password123"], &["correct horse"]);
+
+    let lib_dir = lib_dir();
+    assert!(
+        lib_dir.join("libhibp.so").exists(),
+        "libhibp.so not found in {}",
+        lib_dir.display()
+    );
+    let harness = tmp.path().join("harness");
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(&cc)
+        .args(["-std=c11", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/harness.c"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lhibp", "-o"])
+        .arg(&harness)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {cc}: {e}"));
+    assert!(status.success(), "compiling harness.c failed");
+
+    let output = Command::new(&harness)
+        .arg(&dataset)
+        .arg(tmp.path().join("missing"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,79 @@
+/* Exercises the C ABI the way a C caller would. Run by tests/c_harness.rs with a dataset
+ * holding "password123", with an empty prefix file for "correct horse". */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "hibp.h"
+
+static int failures = 0;
+
+#define EXPECT(cond)                                                        \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: expected %s\n", __FILE__, __LINE__, #cond); \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+static HibpStatus check(const HibpChecker *checker, const char *password, bool *breached) {
+    return hibp_check_password(checker, (const uint8_t *)password, strlen(password), breached);
+}
+
+int main(int argc, char **argv) {
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s <dataset-dir> <missing-dir>\n", argv[0]);
+        return 2;
+    }
+
+    HibpChecker *checker = NULL;
+    EXPECT(hibp_open(argv[2], &checker) == HIBP_STATUS_DATASET_MISSING);
+    EXPECT(checker == NULL);
+    EXPECT(hibp_open(NULL, &checker) == HIBP_STATUS_NULL_POINTER);
+
+    HibpStatus status = hibp_open(argv[1], &checker);
+    if (status != HIBP_STATUS_OK) {
+        fprintf(stderr, "hibp_open: %s\n", hibp_status_message(status));
+        return 1;
+    }
+
+    bool breached = false;
+    EXPECT(check(checker, "password123", &breached) == HIBP_STATUS_OK);
+    EXPECT(breached);
+    EXPECT(check(checker, "correct horse", &breached) == HIBP_STATUS_OK);
+    EXPECT(!breached);
+    /* No file for this prefix: the dataset is incomplete. */
+    EXPECT(check(checker, "letmein", &breached) == HIBP_STATUS_PREFIX_FILE_MISSING);
+    EXPECT(check(checker, "password123", NULL) == HIBP_STATUS_NULL_POINTER);
+
+    /* SHA-1 of "password123". */
+    const uint8_t hit[20] = {0xCB, 0xFD, 0xAC, 0x60, 0x08, 0xF9, 0xCA, 0xB4, 0x08, 0x37,
+                             0x84, 0xCB, 0xD1, 0x87, 0x4F, 0x76, 0x61, 0x8D, 0x2A, 0x97};
+    const uint8_t miss[20] = {0xCB, 0xFD, 0xA0};
+    EXPECT(hibp_check_sha1(checker, &hit, &breached) == HIBP_STATUS_OK);
+    EXPECT(breached);
+    EXPECT(hibp_check_sha1(checker, &miss, &breached) == HIBP_STATUS_OK);
+    EXPECT(!breached);
+
+    const char *words[] = {"correct horse", "password123"};
+    HibpPassword batch[2];
+    for (int i = 0; i < 2; i++) {
+        batch[i].ptr = (const uint8_t *)words[i];
+        batch[i].len = strlen(words[i]);
+    }
+    bool results[2] = {true, false};
+    EXPECT(hibp_check_batch(checker, batch, 2, results) == HIBP_STATUS_OK);
+    EXPECT(!results[0] && results[1]);
+    EXPECT(hibp_check_batch(checker, NULL, 0, NULL) == HIBP_STATUS_OK);
+
+    EXPECT(strcmp(hibp_status_message(HIBP_STATUS_OK), "ok") == 0);
+
+    hibp_close(checker);
+    hibp_close(NULL);
+
+    if (failures > 0) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+// The committed header → identical to what cbindgen generates from the current source.
+#[test]
+fn header_matches_source() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+
+    let header = crate_dir.join("include/hibp.h");
+    if std::env::var_os("HIBP_FFI_BLESS").is_some() {
+        std::fs::write(&header, &generated).unwrap();
+    }
+    let committed = std::fs::read(&header).unwrap();
+    assert!(
+        committed == generated,
+        "include/hibp.h is out of date; regenerate it with HIBP_FFI_BLESS=1 cargo test -p hibp-ffi --test header"
+    );
+}
@@ -8,7 +8,10 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
-use hibp_verifier::{BreachChecker, PREFIX_LEN, RECORD_SIZE, dataset_path_from_env};
+use hibp_verifier::search::{RecordLayout, eytzinger_search, interpolation_search, to_eytzinger};
+use hibp_verifier::{
+    BreachChecker, OwnedBreachChecker, PREFIX_LEN, RECORD_SIZE, dataset_path_from_env,
+};
 use rdtsc_timer::{Profiler, time};
 use sha1::{Digest, Sha1};
 
@@ -19,7 +22,7 @@ fn profile_password_check<const N: usize>(
     password: &str,
     dataset_path: &Path,
 ) -> bool {
-    let checker = BreachChecker::new(dataset_path);
+    let checker = BreachChecker::open(dataset_path).expect("Invalid dataset");
 
     // Step 1: SHA1 hash
     let hash: [u8; 20] = time!(profiler, "sha1_hash", {
@@ -50,14 +53,92 @@ fn profile_password_check<const N: usize>(
         unsafe { hash[2..8].try_into().unwrap_unchecked() }
     });
 
-    // Step 6: Binary search
+    // Steps 6-8: each search over the same records, arranged for it outside the timings
+    let sorted = RecordLayout::load(dataset_path)
+        .expect("Failed to read record layout")
+        .to_sorted(buf[..n].as_chunks::<RECORD_SIZE>().0);
+    let tree = to_eytzinger(&sorted);
+
     let found: bool = time!(profiler, "binary_search", {
-        buf[..n].as_chunks::<RECORD_SIZE>().0.binary_search(&search_key).is_ok()
+        sorted.binary_search(&search_key).is_ok()
+    });
+    let interpolated: bool = time!(profiler, "interpolation_search", {
+        interpolation_search(&sorted, &search_key)
+    });
+    let eytzinger: bool = time!(profiler, "eytzinger_search", {
+        eytzinger_search(&tree, &search_key)
     });
+    assert_eq!((interpolated, eytzinger), (found, found));
 
     found
 }
 
+/// Average cycles per search of `search` over `keys`
+fn cycles_per_search(
+    keys: &[[u8; RECORD_SIZE]],
+    mut search: impl FnMut(&[u8; RECORD_SIZE]) -> bool,
+) -> u64 {
+    let start = rdtsc_timer::cpu_timer();
+    for key in keys {
+        std::hint::black_box(search(std::hint::black_box(key)));
+    }
+    let end = rdtsc_timer::cpu_timer();
+    (end - start) / keys.len() as u64
+}
+
+/// Compare the three searches over every record of the first `files` prefix files (present
+/// keys) and the values just above them (almost always absent keys)
+fn profile_searches(dataset_path: &Path, files: u32) {
+    let layout = RecordLayout::load(dataset_path).expect("Failed to read record layout");
+    let owned = OwnedBreachChecker::open(dataset_path).expect("Failed to open dataset dir");
+
+    let (mut binary, mut interpolation, mut eytzinger) = ([0u64; 2], [0u64; 2], [0u64; 2]);
+    let (mut searched, mut measured) = (0, 0u64);
+    for prefix in 0..files {
+        // prefix_records comes back sorted whatever the layout on disk.
+        let bytes = owned.prefix_records(prefix).expect("Failed to read prefix file");
+        let sorted = bytes.as_chunks::<RECORD_SIZE>().0;
+        if sorted.is_empty() {
+            continue;
+        }
+        let tree = to_eytzinger(sorted);
+        let absent: Vec<[u8; RECORD_SIZE]> = sorted
+            .iter()
+            .map(|r| {
+                let mut key = *r;
+                key[RECORD_SIZE - 1] = key[RECORD_SIZE - 1].wrapping_add(1);
+                key
+            })
+            .collect();
+
+        for (i, keys) in [sorted, absent.as_slice()].into_iter().enumerate() {
+            binary[i] += cycles_per_search(keys, |k| sorted.binary_search(k).is_ok());
+            interpolation[i] += cycles_per_search(keys, |k| interpolation_search(sorted, k));
+            eytzinger[i] += cycles_per_search(keys, |k| eytzinger_search(&tree, k));
+        }
+        searched += sorted.len();
+        measured += 1;
+    }
+
+    println!(
+        "\n=== Search over {} records in {} prefix files (dataset layout: {}) ===\n",
+        searched, files, layout
+    );
+    let measured = measured.max(1);
+    for (name, cycles) in [
+        ("binary_search", binary),
+        ("interpolation_search", interpolation),
+        ("eytzinger_search", eytzinger),
+    ] {
+        println!(
+            "{:<21} present: {} cycles/search, absent: {} cycles/search",
+            name,
+            cycles[0] / measured,
+            cycles[1] / measured
+        );
+    }
+}
+
 fn main() {
     println!("=== Breached Password Searcher Profiling ===\n");
 
@@ -74,7 +155,7 @@ fn main() {
     // Profile a known breached password (positive case)
     // "password123" -> SHA1: CBFDAC6008F9CAB4083784CBD1874F76618D2A97
     {
-        let mut profiler: Profiler<6> =
+        let mut profiler: Profiler<8> =
             Profiler::new("Breached password (password123) - POSITIVE PATH");
         let found = profile_password_check(&mut profiler, "password123", dataset_path);
         assert!(found, "password123 should be found");
@@ -84,13 +165,15 @@ fn main() {
     // Profile a non-breached password (negative case)
     // "hAwT?}cuC:r#kW5" -> not in database
     {
-        let mut profiler: Profiler<6> =
+        let mut profiler: Profiler<8> =
             Profiler::new("Non-breached password (hAwT?}cuC:r#kW5) - NEGATIVE PATH");
         let found = profile_password_check(&mut profiler, "hAwT?}cuC:r#kW5", dataset_path);
         assert!(!found, "random password should not be found");
         profiler.finalize();
     }
 
+    profile_searches(dataset_path, 256);
+
     // Aggregated timing using the actual library code paths
     let iterations = 1000;
     println!(
@@ -98,7 +181,7 @@ fn main() {
         iterations
     );
 
-    let checker = BreachChecker::new(dataset_path);
+    let checker = BreachChecker::open(dataset_path).expect("Invalid dataset");
 
     let start = rdtsc_timer::cpu_timer();
     for _ in 0..iterations {
@@ -121,4 +204,47 @@ fn main() {
         "Non-breached password (hAwT?}}cuC:r#kW5): {} cycles/iter average",
         cycles_per_iter
     );
+
+    // File open only: full path vs openat relative to the held dataset directory fd
+    println!(
+        "\n=== File open over {} iterations: path vs openat ===\n",
+        iterations
+    );
+
+    let owned = OwnedBreachChecker::open(dataset_path).expect("Failed to open dataset dir");
+    let mut hasher = Sha1::new();
+    hasher.update(b"password123");
+    let hash: [u8; 20] = hasher.finalize().into();
+    let prefix_hex = BreachChecker::prefix_hex(&hash);
+
+    let start = rdtsc_timer::cpu_timer();
+    for _ in 0..iterations {
+        drop(checker.open_file(prefix_hex).expect("Failed to open file"));
+    }
+    let end = rdtsc_timer::cpu_timer();
+    let path_cycles = (end - start) / iterations as u64;
+    println!("open (full path): {} cycles/iter average", path_cycles);
+
+    let start = rdtsc_timer::cpu_timer();
+    for _ in 0..iterations {
+        drop(owned.open_file(prefix_hex).expect("Failed to open file"));
+    }
+    let end = rdtsc_timer::cpu_timer();
+    let openat_cycles = (end - start) / iterations as u64;
+    println!("openat (dir fd):  {} cycles/iter average", openat_cycles);
+    println!(
+        "openat saves {} cycles/iter",
+        path_cycles as i64 - openat_cycles as i64
+    );
+
+    let start = rdtsc_timer::cpu_timer();
+    for _ in 0..iterations {
+        let _ = owned.is_breached("password123");
+    }
+    let end = rdtsc_timer::cpu_timer();
+    let cycles_per_iter = (end - start) / iterations as u64;
+    println!(
+        "Breached password (password123) via OwnedBreachChecker: {} cycles/iter average",
+        cycles_per_iter
+    );
 }
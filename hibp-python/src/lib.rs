@@ -0,0 +1,200 @@
+//! Python bindings for [hibp-verifier](https://crates.io/crates/hibp-verifier).
+//!
+//! Built with [maturin](https://www.maturin.rs) into the `pyhibp` module:
+//!
+//! ```python
+//! import numpy as np
+//! import pyhibp
+//!
+//! checker = pyhibp.BreachChecker("/path/to/hibp-data")
+//! checker.is_breached("password123")                    # True
+//! checker.is_breached_sha1("CBFDAC6008F9CAB4083784CBD1874F76618D2A97")
+//! checker.check_batch(["password123", "correct horse"])  # [True, False]
+//!
+//! digests = np.frombuffer(raw_digests, dtype=np.uint8).reshape(-1, 20)
+//! breached = checker.check_sha1_batch(digests)          # numpy bool array
+//! ```
+//!
+//! The batch methods release the GIL while they read the dataset, so audits can run on
+//! several Python threads at once.
+
+use std::path::{Path, PathBuf};
+
+use hibp_verifier::{Error, OwnedBreachChecker, parse_sha1_hex};
+use numpy::{IntoPyArray, PyArray1, PyReadonlyArray2, PyUntypedArrayMethods};
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+pyo3::create_exception!(pyhibp, HibpError, PyException, "A dataset lookup failed.");
+pyo3::create_exception!(
+    pyhibp,
+    DatasetError,
+    HibpError,
+    "The dataset directory is missing or is not a complete dataset."
+);
+
+fn lookup_error(e: Error) -> PyErr {
+    if e.is_dataset_error() {
+        DatasetError::new_err(e.to_string())
+    } else {
+        HibpError::new_err(e.to_string())
+    }
+}
+
+/// Checks passwords against a local HIBP dataset.
+#[pyclass(frozen, module = "pyhibp")]
+struct BreachChecker {
+    inner: OwnedBreachChecker,
+}
+
+#[pymethods]
+impl BreachChecker {
+    /// Opens the dataset at `dataset_path`. Raises `DatasetError` if the directory is missing
+    /// or incomplete.
+    #[new]
+    fn new(dataset_path: PathBuf) -> PyResult<Self> {
+        let inner = OwnedBreachChecker::open(dataset_path)
+            .map_err(|e| DatasetError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    fn dataset_path(&self) -> &Path {
+        self.inner.dataset_path()
+    }
+
+    /// Whether `password` appears in the dataset.
+    fn is_breached(&self, password: &str) -> PyResult<bool> {
+        self.inner.is_breached(password).map_err(lookup_error)
+    }
+
+    /// Whether the password with SHA-1 `digest` appears in the dataset. `digest` is either
+    /// 20 raw bytes or 40 hex characters.
+    fn is_breached_sha1(&self, digest: &Bound<'_, PyAny>) -> PyResult<bool> {
+        let hash = match digest.cast::<PyBytes>() {
+            Ok(bytes) => <[u8; 20]>::try_from(bytes.as_bytes())
+                .map_err(|_| PyValueError::new_err("digest must be 20 bytes"))?,
+            Err(_) => parse_sha1_hex(&digest.extract::<String>()?)
+                .ok_or_else(|| PyValueError::new_err("digest must be 40 hex characters"))?,
+        };
+        self.inner.contains_hash(&hash).map_err(lookup_error)
+    }
+
+    /// Checks each password, releasing the GIL during the lookups. Returns a list of results
+    /// in the same order.
+    fn check_batch(&self, py: Python<'_>, passwords: Vec<String>) -> PyResult<Vec<bool>> {
+        py.detach(|| check_passwords(&self.inner, &passwords)).map_err(lookup_error)
+    }
+
+    /// Checks a C-contiguous `uint8` array of shape `(n, 20)` holding one SHA-1 digest per
+    /// row, releasing the GIL during the lookups. Returns a `bool` array of length `n`.
+    fn check_sha1_batch<'py>(
+        &self,
+        py: Python<'py>,
+        digests: PyReadonlyArray2<'py, u8>,
+    ) -> PyResult<Bound<'py, PyArray1<bool>>> {
+        if digests.shape()[1] != 20 {
+            return Err(PyValueError::new_err("digests must have shape (n, 20)"));
+        }
+        let bytes = digests
+            .as_slice()
+            .map_err(|_| PyValueError::new_err("digests must be C-contiguous"))?;
+        let breached = py
+            .detach(|| check_digests(&self.inner, bytes.as_chunks::<20>().0))
+            .map_err(lookup_error)?;
+        Ok(breached.into_pyarray(py))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BreachChecker({:?})", self.inner.dataset_path())
+    }
+}
+
+fn check_passwords(checker: &OwnedBreachChecker, passwords: &[String]) -> Result<Vec<bool>, Error> {
+    passwords.iter().map(|password| checker.is_breached(password)).collect()
+}
+
+fn check_digests(checker: &OwnedBreachChecker, digests: &[[u8; 20]]) -> Result<Vec<bool>, Error> {
+    digests.iter().map(|hash| checker.contains_hash(hash)).collect()
+}
+
+#[pymodule]
+fn pyhibp(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<BreachChecker>()?;
+    m.add("HibpError", m.py().get_type::<HibpError>())?;
+    m.add("DatasetError", m.py().get_type::<DatasetError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hibp_verifier::test_util::write_dataset;
+
+    use super::*;
+
+    // SHA-1 of "password123".
+    const HIT: [u8; 20] = [
+        0xCB, 0xFD, 0xAC, 0x60, 0x08, 0xF9, 0xCA, 0xB4, 0x08, 0x37, 0x84, 0xCB, 0xD1, 0x87, 0x4F,
+        0x76, 0x61, 0x8D, 0x2A, 0x97,
+    ];
+
+    fn checker(dir: &Path) -> BreachChecker {
+        write_dataset(dir, &["password123"], &[]);
+        BreachChecker::new(dir.to_path_buf()).unwrap()
+    }
+
+    // Raw or hex digest → same answer as the plaintext; a malformed digest → ValueError.
+    #[test]
+    fn sha1_lookups_accept_bytes_and_hex() {
+        let tmp = tempfile::tempdir().unwrap();
+        let checker = checker(tmp.path());
+
+        Python::attach(|py| {
+            assert!(checker.is_breached("password123").unwrap());
+            let raw = PyBytes::new(py, &HIT);
+            assert!(checker.is_breached_sha1(&raw).unwrap());
+            let hex = "cbfdac6008f9cab4083784cbd1874f76618d2a97".into_pyobject(py).unwrap();
+            assert!(checker.is_breached_sha1(hex.as_any()).unwrap());
+            let miss = "CBFDA00000000000000000000000000000000000".into_pyobject(py).unwrap();
+            assert!(!checker.is_breached_sha1(miss.as_any()).unwrap());
+
+            for bad in [
+                PyBytes::new(py, &HIT[..19]).into_any(),
+                "CBFDA".into_pyobject(py).unwrap().into_any(),
+            ] {
+                let err = checker.is_breached_sha1(&bad).unwrap_err();
+                assert!(err.is_instance_of::<PyValueError>(py));
+            }
+        });
+    }
+
+    // A batch → results in order; a missing prefix file → HibpError, not DatasetError.
+    #[test]
+    fn batch_and_lookup_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let checker = checker(tmp.path());
+
+        Python::attach(|py| {
+            let passwords = vec!["password123".to_owned(), "password123".to_owned()];
+            assert_eq!(checker.check_batch(py, passwords).unwrap(), [true, true]);
+
+            let err = checker.check_batch(py, vec!["letmein".to_owned()]).unwrap_err();
+            assert!(err.is_instance_of::<HibpError>(py));
+            assert!(!err.is_instance_of::<DatasetError>(py));
+        });
+
+        let miss = parse_sha1_hex("CBFDA00000000000000000000000000000000000").unwrap();
+        assert_eq!(
+            check_digests(&checker.inner, &[miss, HIT]).unwrap(),
+            [false, true]
+        );
+    }
+
+    #[test]
+    fn missing_dataset_raises_dataset_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let err = BreachChecker::new(tmp.path().join("missing")).err().unwrap();
+        Python::attach(|py| assert!(err.is_instance_of::<DatasetError>(py)));
+    }
+}
@@ -0,0 +1,702 @@
+use std::fmt::Write as _;
+use std::sync::OnceLock;
+use std::sync::atomic::Ordering;
+
+use bytes::Bytes;
+use hibp_verifier::reload::{DEFAULT_POLL_INTERVAL, ReloadingChecker};
+use hibp_verifier::{
+    OwnedBreachChecker, PREFIX_COUNT, PREFIX_LEN, RECORD_SIZE, parse_sha1_hex,
+    validate_range_layout,
+};
+use http::request::Parts;
+use http::{Method, Request, Response, StatusCode, header};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::Body;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::Config;
+use crate::error::{ApiError, Error};
+use crate::metrics::{DatasetGauges, Metrics};
+
+/// Padded truncated range responses hold between this many lines and [`PADDED_MAX`].
+const PADDED_MIN: usize = 800;
+const PADDED_MAX: usize = 1000;
+
+/// Bits of a record left after the 20-bit prefix: the 11 hex characters of a suffix.
+const SUFFIX_BITS: u32 = 44;
+
+/// Path of the truncated range listing. It is deliberately not HIBP's `/range/`, whose
+/// clients compare 35-character suffixes and would report every password as clean.
+const TRUNCATED_RANGE: &str = "/truncated-range/";
+
+/// Media type of a truncated range listing, so it is not mistaken for an HIBP response.
+const TRUNCATED_RANGE_TYPE: &str = "text/vnd.hibp-rs.truncated-range";
+
+/// State shared by every connection.
+pub struct AppState {
+    config: Config,
+    checker: OnceLock<ReloadingChecker>,
+    in_flight: Semaphore,
+    metrics: Metrics,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Result<Self, Error> {
+        if config.max_body_bytes == 0 {
+            return Err(Error::InvalidConfig(
+                "max_body_bytes must be greater than zero",
+            ));
+        }
+        if config.max_batch == 0 {
+            return Err(Error::InvalidConfig("max_batch must be greater than zero"));
+        }
+        if config.max_in_flight == 0 || config.max_in_flight > Semaphore::MAX_PERMITS {
+            return Err(Error::InvalidConfig("max_in_flight is out of range"));
+        }
+        Ok(Self {
+            in_flight: Semaphore::new(config.max_in_flight),
+            config,
+            checker: OnceLock::new(),
+            metrics: Metrics::default(),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Opens the dataset if it is not open yet and returns the version being served. Blocks on
+    /// filesystem I/O.
+    pub fn open_dataset(&self) -> Result<Option<String>, hibp_verifier::Error> {
+        if let Some(checker) = self.checker.get() {
+            return Ok(checker.version());
+        }
+        let mut builder =
+            OwnedBreachChecker::builder(&self.config.dataset_path).prefilter(self.config.prefilter);
+        if let Some(range) = &self.config.prefix_range {
+            builder = builder.prefix_range(range.clone());
+        }
+        let checker = ReloadingChecker::from_builder(builder, DEFAULT_POLL_INTERVAL, None)?;
+        let version = checker.version();
+        // A concurrent open may have won; either checker serves the same dataset.
+        let _ = self.checker.set(checker);
+        Ok(version)
+    }
+
+    fn checker(&self) -> Result<ReloadingChecker, ApiError> {
+        self.checker.get().cloned().ok_or(ApiError::NotReady)
+    }
+
+    /// Whether the dataset is loaded with its layout intact, and its version. The layout check
+    /// opens files, so it runs on the blocking pool.
+    async fn readiness(&self) -> (bool, Option<String>) {
+        let Some(checker) = self.checker.get().cloned() else {
+            return (false, None);
+        };
+        tokio::task::spawn_blocking(move || {
+            let dataset = checker.current();
+            let range = dataset.checker().prefix_range();
+            let ready = validate_range_layout(dataset.dir(), range).is_ok();
+            (ready, dataset.last_updated().map(str::to_owned))
+        })
+        .await
+        .expect("readiness task panicked")
+    }
+}
+
+/// A query in a `/check` body. Plaintext passwords pass through here, so this deliberately
+/// has no `Debug`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawQuery {
+    password: Option<String>,
+    sha1: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CheckBody {
+    One(RawQuery),
+    Batch(Vec<RawQuery>),
+}
+
+enum Query {
+    Password(String),
+    Sha1([u8; 20]),
+}
+
+impl TryFrom<RawQuery> for Query {
+    type Error = ApiError;
+
+    fn try_from(raw: RawQuery) -> Result<Self, ApiError> {
+        match (raw.password, raw.sha1) {
+            (Some(password), None) => Ok(Self::Password(password)),
+            (None, Some(sha1)) => {
+                parse_sha1_hex(&sha1).map(Self::Sha1).ok_or(ApiError::InvalidSha1)
+            }
+            _ => Err(ApiError::InvalidQuery),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CheckResult {
+    breached: bool,
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    version: Option<String>,
+}
+
+pub(crate) fn json<T: Serialize>(status: StatusCode, body: &T) -> Response<Full<Bytes>> {
+    let body = serde_json::to_vec(body).expect("response bodies serialize infallibly");
+    respond(status, "application/json", Bytes::from(body))
+}
+
+fn respond(status: StatusCode, content_type: &'static str, body: Bytes) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Full::new(body))
+        .expect("static response parts are valid")
+}
+
+/// Routes one request. Failures are answered with a JSON error body and counted.
+pub async fn handle<B>(req: Request<B>, state: &AppState) -> Response<Full<Bytes>>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = req.into_parts();
+    let path = parts.uri.path();
+    let result = match (&parts.method, path) {
+        (&Method::POST, "/check") => check(body, state).await,
+        (&Method::GET, _) if path.starts_with(TRUNCATED_RANGE) => range(&parts, state).await,
+        // HIBP clients would misread 11-character suffixes as misses, so fail them loudly.
+        (_, _) if path.starts_with("/range/") => Err(ApiError::HibpRangeUnsupported),
+        (&Method::GET, "/readyz") => Ok(readyz(state).await),
+        (&Method::GET, "/healthz") => Ok(respond(StatusCode::OK, "text/plain", Bytes::new())),
+        (&Method::GET, "/metrics") => Ok(metrics(state).await),
+        (_, "/check" | "/readyz" | "/healthz" | "/metrics") => Err(ApiError::MethodNotAllowed),
+        _ if path.starts_with(TRUNCATED_RANGE) => Err(ApiError::MethodNotAllowed),
+        _ => Err(ApiError::NotFound),
+    };
+    result.unwrap_or_else(|e| {
+        state.metrics.record_error(&e);
+        e.into_response()
+    })
+}
+
+async fn check<B>(body: B, state: &AppState) -> Result<Response<Full<Bytes>>, ApiError>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    state.metrics.check_requests_total.fetch_add(1, Ordering::Relaxed);
+    let _permit = state.in_flight.try_acquire().map_err(|_| ApiError::Overloaded)?;
+    let checker = state.checker()?;
+
+    let body = read_body(body, state.config.max_body_bytes).await?;
+    let (raw, batch) = match serde_json::from_slice(&body)? {
+        CheckBody::One(raw) => (vec![raw], false),
+        CheckBody::Batch(raw) => (raw, true),
+    };
+    if raw.len() > state.config.max_batch {
+        return Err(ApiError::BatchTooLarge { len: raw.len(), max: state.config.max_batch });
+    }
+    let queries = raw.into_iter().map(Query::try_from).collect::<Result<Vec<_>, _>>()?;
+
+    let breached = tokio::task::spawn_blocking(move || {
+        // One snapshot for the whole batch, so every answer comes from the same version.
+        let dataset = checker.current();
+        queries
+            .iter()
+            .map(|query| match query {
+                Query::Password(password) => dataset.checker().is_breached(password),
+                Query::Sha1(hash) => dataset.checker().contains_hash(hash),
+            })
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .expect("lookup task panicked")?;
+
+    let hits = breached.iter().filter(|&&b| b).count();
+    state.metrics.lookups_total.fetch_add(breached.len() as u64, Ordering::Relaxed);
+    state.metrics.breached_total.fetch_add(hits as u64, Ordering::Relaxed);
+
+    let results: Vec<_> = breached.into_iter().map(|breached| CheckResult { breached }).collect();
+    Ok(if batch {
+        json(StatusCode::OK, &results)
+    } else {
+        json(StatusCode::OK, &results[0])
+    })
+}
+
+async fn read_body<B>(body: B, limit: usize) -> Result<Bytes, ApiError>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    match Limited::new(body, limit).collect().await {
+        Ok(collected) => Ok(collected.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => Err(ApiError::BodyTooLarge(limit)),
+        Err(_) => Err(ApiError::BodyRead),
+    }
+}
+
+async fn range(parts: &Parts, state: &AppState) -> Result<Response<Full<Bytes>>, ApiError> {
+    state.metrics.range_requests_total.fetch_add(1, Ordering::Relaxed);
+    let prefix =
+        parse_prefix(&parts.uri.path()[TRUNCATED_RANGE.len()..]).ok_or(ApiError::InvalidPrefix)?;
+    let padding = parts
+        .headers
+        .get("add-padding")
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"));
+    let _permit = state.in_flight.try_acquire().map_err(|_| ApiError::Overloaded)?;
+    let checker = state.checker()?;
+
+    let records =
+        tokio::task::spawn_blocking(move || checker.current().checker().prefix_records(prefix))
+            .await
+            .expect("lookup task panicked")?;
+
+    Ok(respond(
+        StatusCode::OK,
+        TRUNCATED_RANGE_TYPE,
+        Bytes::from(range_body(&records, padding)),
+    ))
+}
+
+/// Formats a prefix file as `SUFFIX:COUNT` lines joined by CRLF, like HIBP's range API but
+/// with 11-character suffixes, which full-suffix HIBP clients never match. Served only under
+/// [`TRUNCATED_RANGE`] for that reason.
+fn range_body(records: &[u8], padding: bool) -> String {
+    let mask = (1u64 << SUFFIX_BITS) - 1;
+    let mut lines: Vec<(u64, u8)> = records
+        .chunks_exact(RECORD_SIZE)
+        .map(|record| {
+            let mut bytes = [0u8; 8];
+            bytes[2..].copy_from_slice(record);
+            (u64::from_be_bytes(bytes) & mask, 1)
+        })
+        .collect();
+    if padding {
+        let target = fastrand::usize(PADDED_MIN..=PADDED_MAX);
+        while lines.len() < target {
+            lines.push((fastrand::u64(..=mask), 0));
+        }
+        lines.sort_unstable();
+    }
+
+    let mut out = String::with_capacity(lines.len() * 16);
+    for (i, (suffix, count)) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push_str("\r\n");
+        }
+        let _ = write!(out, "{suffix:011X}:{count}");
+    }
+    out
+}
+
+async fn readyz(state: &AppState) -> Response<Full<Bytes>> {
+    let (ready, version) = state.readiness().await;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    json(status, &Readiness { ready, version })
+}
+
+async fn metrics(state: &AppState) -> Response<Full<Bytes>> {
+    let (ready, version) = state.readiness().await;
+    let gauges = DatasetGauges {
+        ready,
+        last_updated: version
+            .and_then(|v| chrono::DateTime::parse_from_rfc3339(&v).ok())
+            .map(|t| t.timestamp()),
+        in_flight: state.config.max_in_flight - state.in_flight.available_permits(),
+    };
+    respond(
+        StatusCode::OK,
+        "text/plain; version=0.0.4",
+        Bytes::from(state.metrics.render(&gauges)),
+    )
+}
+
+fn parse_prefix(hex: &str) -> Option<u32> {
+    if hex.len() != PREFIX_LEN || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let prefix = u32::from_str_radix(hex, 16).ok()?;
+    (prefix < PREFIX_COUNT).then_some(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use hibp_verifier::test_util;
+
+    use super::*;
+
+    // "password123" hashes to CBFDAC6008F9CAB4083784CBD1874F76618D2A97.
+    const MISS_SHA1: &str = "CBFDA00000000000000000000000000000000000";
+
+    fn write_dataset(dir: &Path) {
+        test_util::write_dataset(dir, &["password123"], &[]);
+        std::fs::write(
+            dir.join("sync-state.json"),
+            r#"{"last_updated":"2026-01-02T03:04:05Z"}"#,
+        )
+        .unwrap();
+    }
+
+    fn state(dir: &Path, max_in_flight: usize) -> AppState {
+        AppState::new(Config {
+            dataset_path: dir.to_path_buf(),
+            max_body_bytes: 256,
+            max_batch: 2,
+            max_in_flight,
+            open_retry_interval: Duration::from_secs(1),
+            prefix_range: None,
+            prefilter: false,
+        })
+        .unwrap()
+    }
+
+    fn loaded(dir: &Path) -> AppState {
+        write_dataset(dir);
+        let state = state(dir, 4);
+        state.open_dataset().unwrap();
+        state
+    }
+
+    async fn send(
+        state: &AppState,
+        method: Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> (StatusCode, String) {
+        let mut req = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let req = req.body(Full::new(Bytes::from(body.to_owned()))).unwrap();
+        let resp = handle(req, state).await;
+        let status = resp.status();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn post_check(state: &AppState, body: &str) -> (StatusCode, String) {
+        send(state, Method::POST, "/check", &[], body).await
+    }
+
+    // Password and SHA-1 queries → single results; an array → results in order.
+    #[tokio::test]
+    async fn check_single_and_batch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = loaded(tmp.path());
+
+        let hit = (StatusCode::OK, r#"{"breached":true}"#.to_owned());
+        let miss = (StatusCode::OK, r#"{"breached":false}"#.to_owned());
+        assert_eq!(
+            post_check(&state, r#"{"password":"password123"}"#).await,
+            hit
+        );
+        assert_eq!(
+            post_check(
+                &state,
+                r#"{"sha1":"cbfdac6008f9cab4083784cbd1874f76618d2a97"}"#
+            )
+            .await,
+            hit
+        );
+        assert_eq!(
+            post_check(&state, &format!(r#"{{"sha1":"{MISS_SHA1}"}}"#)).await,
+            miss
+        );
+
+        let batch = format!(r#"[{{"sha1":"{MISS_SHA1}"}},{{"password":"password123"}}]"#);
+        assert_eq!(
+            post_check(&state, &batch).await,
+            (
+                StatusCode::OK,
+                r#"[{"breached":false},{"breached":true}]"#.to_owned()
+            )
+        );
+    }
+
+    // Malformed queries → 400 with a specific code; oversized body or batch → 413.
+    #[tokio::test]
+    async fn check_rejects_bad_requests() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = loaded(tmp.path());
+
+        for (body, status, code) in [
+            (
+                r#"{"sha1":"CBFDA"}"#,
+                StatusCode::BAD_REQUEST,
+                "invalid_sha1",
+            ),
+            (
+                r#"{"sha1":"+BFDA00000000000000000000000000000000000"}"#,
+                StatusCode::BAD_REQUEST,
+                "invalid_sha1",
+            ),
+            (
+                r#"{"password":"a","sha1":"b"}"#,
+                StatusCode::BAD_REQUEST,
+                "invalid_query",
+            ),
+            (r#"{}"#, StatusCode::BAD_REQUEST, "invalid_query"),
+            (
+                r#"{"pasword":"a"}"#,
+                StatusCode::BAD_REQUEST,
+                "invalid_body",
+            ),
+            (
+                r#"[{"password":"a"},{"password":"b"},{"password":"c"}]"#,
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "batch_too_large",
+            ),
+        ] {
+            assert_eq!(
+                post_check(&state, body).await,
+                (status, format!(r#"{{"error":"{code}"}}"#)),
+                "{body}"
+            );
+        }
+
+        let long = format!(r#"{{"password":"{}"}}"#, "a".repeat(300));
+        assert_eq!(
+            post_check(&state, &long).await.0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(state.metrics.bad_requests_total.load(Ordering::Relaxed), 5);
+        assert_eq!(
+            state.metrics.rejected_too_large_total.load(Ordering::Relaxed),
+            2
+        );
+    }
+
+    // Every permit taken → 503 overloaded instead of waiting.
+    #[tokio::test]
+    async fn check_sheds_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path());
+        let state = state(tmp.path(), 1);
+        state.open_dataset().unwrap();
+
+        let _held = state.in_flight.try_acquire().unwrap();
+        assert_eq!(
+            post_check(&state, r#"{"password":"password123"}"#).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"error":"overloaded"}"#.to_owned()
+            )
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/truncated-range/CBFDA", &[], "").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    // Records → 11-char suffixes with count 1 under a non-HIBP media type; padding → 800-1000
+    // sorted lines, count-0 fillers; HIBP's /range path → 501 so stock clients fail loudly.
+    #[tokio::test]
+    async fn range_lists_truncated_suffixes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = loaded(tmp.path());
+
+        assert_eq!(
+            send(&state, Method::GET, "/range/CBFDA", &[], "").await,
+            (
+                StatusCode::NOT_IMPLEMENTED,
+                r#"{"error":"hibp_range_unsupported"}"#.to_owned()
+            )
+        );
+        let req = Request::get("/truncated-range/CBFDA").body(Full::new(Bytes::new())).unwrap();
+        let resp = handle(req, &state).await;
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], TRUNCATED_RANGE_TYPE);
+
+        assert_eq!(
+            send(&state, Method::GET, "/truncated-range/CBFDA", &[], "").await,
+            (StatusCode::OK, "C6008F9CAB4:1".to_owned())
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/truncated-range/00000", &[], "").await,
+            (StatusCode::OK, String::new())
+        );
+
+        let (status, body) = send(
+            &state,
+            Method::GET,
+            "/truncated-range/cbfda",
+            &[("Add-Padding", "true")],
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let lines: Vec<&str> = body.split("\r\n").collect();
+        assert!((PADDED_MIN..=PADDED_MAX).contains(&lines.len()));
+        assert!(lines.is_sorted());
+        assert!(lines.contains(&"C6008F9CAB4:1"));
+        assert_eq!(lines.iter().filter(|l| l.ends_with(":1")).count(), 1);
+
+        for bad in [
+            "/truncated-range/CBFD",
+            "/truncated-range/CBFDAA",
+            "/truncated-range/+BFDA",
+            "/truncated-range/GGGGG",
+        ] {
+            assert_eq!(
+                send(&state, Method::GET, bad, &[], "").await,
+                (
+                    StatusCode::BAD_REQUEST,
+                    r#"{"error":"invalid_prefix"}"#.to_owned()
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    // No dataset → not ready and lookups 503; once opened → ready with its version; a
+    // sentinel file removed underneath → not ready again.
+    #[tokio::test]
+    async fn readiness_follows_dataset() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state(tmp.path(), 4);
+        assert!(state.open_dataset().is_err());
+
+        assert_eq!(
+            send(&state, Method::GET, "/readyz", &[], "").await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"ready":false,"version":null}"#.to_owned()
+            )
+        );
+        assert_eq!(
+            post_check(&state, r#"{"password":"password123"}"#).await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"error":"not_ready"}"#.to_owned()
+            )
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/healthz", &[], "").await.0,
+            StatusCode::OK
+        );
+
+        write_dataset(tmp.path());
+        assert_eq!(
+            state.open_dataset().unwrap().as_deref(),
+            Some("2026-01-02T03:04:05Z")
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/readyz", &[], "").await,
+            (
+                StatusCode::OK,
+                r#"{"ready":true,"version":"2026-01-02T03:04:05Z"}"#.to_owned()
+            )
+        );
+
+        std::fs::remove_file(tmp.path().join("FFFFF.bin")).unwrap();
+        assert_eq!(
+            send(&state, Method::GET, "/readyz", &[], "").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    // Shard 12/16 (C0000-CFFFF) without 00000.bin → loads and is ready; "password123" (CBFDA)
+    // is answered, "password" (5BAA6) and /truncated-range/5BAA6 get 421.
+    #[tokio::test]
+    async fn shard_serves_only_its_range() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path());
+        std::fs::remove_file(tmp.path().join("00000.bin")).unwrap();
+        std::fs::write(tmp.path().join("C0000.bin"), b"").unwrap();
+        std::fs::write(tmp.path().join("CFFFF.bin"), b"").unwrap();
+        let state = AppState::new(Config {
+            prefix_range: Some(0xC0000..=0xCFFFF),
+            ..state(tmp.path(), 4).config
+        })
+        .unwrap();
+        state.open_dataset().unwrap();
+
+        assert_eq!(
+            send(&state, Method::GET, "/readyz", &[], "").await.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            post_check(&state, r#"{"password":"password123"}"#).await,
+            (StatusCode::OK, r#"{"breached":true}"#.to_owned())
+        );
+        let misdirected = (
+            StatusCode::MISDIRECTED_REQUEST,
+            r#"{"error":"prefix_not_in_range"}"#.to_owned(),
+        );
+        assert_eq!(
+            post_check(&state, r#"{"password":"password"}"#).await,
+            misdirected
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/truncated-range/5BAA6", &[], "").await,
+            misdirected
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_and_methods() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = loaded(tmp.path());
+
+        assert_eq!(
+            send(&state, Method::GET, "/check", &[], "").await.0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            send(&state, Method::POST, "/truncated-range/CBFDA", &[], "").await.0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            send(&state, Method::GET, "/nope", &[], "").await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_count_lookups() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = loaded(tmp.path());
+
+        let batch = format!(r#"[{{"sha1":"{MISS_SHA1}"}},{{"password":"password123"}}]"#);
+        post_check(&state, &batch).await;
+        post_check(&state, r#"{"password":"password123"}"#).await;
+        post_check(&state, r#"{}"#).await;
+
+        let (status, body) = send(&state, Method::GET, "/metrics", &[], "").await;
+        assert_eq!(status, StatusCode::OK);
+        for line in [
+            "hibp_server_dataset_ready 1",
+            "hibp_server_dataset_last_updated_timestamp_seconds 1767323045",
+            "hibp_server_in_flight_requests 0",
+            "hibp_server_check_requests_total 3",
+            "hibp_server_lookups_total 3",
+            "hibp_server_breached_total 2",
+            "hibp_server_bad_requests_total 1",
+        ] {
+            assert!(body.lines().any(|l| l == line), "missing {line}:\n{body}");
+        }
+    }
+}
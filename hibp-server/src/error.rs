@@ -0,0 +1,111 @@
+use bytes::Bytes;
+use http::StatusCode;
+use http_body_util::Full;
+use serde::Serialize;
+
+/// Errors that fail a startup or the server loop.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(&'static str),
+}
+
+/// Errors answered to a client, each with a status code and a stable error code.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("no such endpoint")]
+    NotFound,
+
+    #[error("method not allowed for this endpoint")]
+    MethodNotAllowed,
+
+    #[error("HIBP's /range API is not served; use /truncated-range or /check")]
+    HibpRangeUnsupported,
+
+    #[error("request body could not be read")]
+    BodyRead,
+
+    #[error("request body exceeds {0} bytes")]
+    BodyTooLarge(usize),
+
+    #[error("request body is not a query or a list of queries: {0}")]
+    InvalidBody(#[from] serde_json::Error),
+
+    #[error("each query needs exactly one of password or sha1")]
+    InvalidQuery,
+
+    #[error("sha1 must be 40 hex characters")]
+    InvalidSha1,
+
+    #[error("prefix must be 5 hex characters")]
+    InvalidPrefix,
+
+    #[error("batch of {len} queries exceeds the limit of {max}")]
+    BatchTooLarge { len: usize, max: usize },
+
+    #[error("too many requests in flight")]
+    Overloaded,
+
+    #[error("dataset is not loaded")]
+    NotReady,
+
+    #[error("lookup failed: {0}")]
+    Lookup(#[from] hibp_verifier::Error),
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    error: &'static str,
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::HibpRangeUnsupported => StatusCode::NOT_IMPLEMENTED,
+            Self::BodyRead
+            | Self::InvalidBody(_)
+            | Self::InvalidQuery
+            | Self::InvalidSha1
+            | Self::InvalidPrefix => StatusCode::BAD_REQUEST,
+            Self::BodyTooLarge(_) | Self::BatchTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Overloaded | Self::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Lookup(hibp_verifier::Error::PrefixNotInRange { .. }) => {
+                StatusCode::MISDIRECTED_REQUEST
+            }
+            Self::Lookup(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::HibpRangeUnsupported => "hibp_range_unsupported",
+            Self::BodyRead => "body_read_failed",
+            Self::BodyTooLarge(_) => "body_too_large",
+            Self::InvalidBody(_) => "invalid_body",
+            Self::InvalidQuery => "invalid_query",
+            Self::InvalidSha1 => "invalid_sha1",
+            Self::InvalidPrefix => "invalid_prefix",
+            Self::BatchTooLarge { .. } => "batch_too_large",
+            Self::Overloaded => "overloaded",
+            Self::NotReady => "not_ready",
+            Self::Lookup(hibp_verifier::Error::PrefixNotInRange { .. }) => "prefix_not_in_range",
+            Self::Lookup(_) => "lookup_failed",
+        }
+    }
+
+    pub fn into_response(self) -> http::Response<Full<Bytes>> {
+        if let Self::Lookup(e) = &self
+            && self.status_code().is_server_error()
+        {
+            tracing::error!(error = %e, "lookup failed");
+        }
+        crate::api::json(self.status_code(), &ApiErrorBody { error: self.code() })
+    }
+}
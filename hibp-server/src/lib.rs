@@ -0,0 +1,110 @@
+//! An HTTP service answering password breach checks from a local HIBP dataset, for services
+//! that cannot link [hibp-verifier](https://crates.io/crates/hibp-verifier) directly.
+//!
+//! # Endpoints
+//!
+//! - `POST /check` takes `{"password": "..."}` or `{"sha1": "<40 hex>"}` and answers `{"breached":
+//!   bool}`. A JSON array of queries is checked as a batch and answered with an array of results in
+//!   the same order.
+//! - `GET /truncated-range/{prefix}` answers one `SUFFIX:COUNT` line per record in the prefix, as
+//!   `text/vnd.hibp-rs.truncated-range`. It is *not* HIBP's k-anonymity API: the dataset stores the
+//!   first 64 bits of each hash, so suffixes are the 11 hex characters after the prefix rather than
+//!   HIBP's 35, and counts are `1`. Send `Add-Padding: true` to pad the response with count-0
+//!   entries. HIBP's own `/range/{prefix}` path answers 501 so that stock clients, which would
+//!   never match a truncated suffix, fail instead of reporting every password as clean.
+//! - `GET /readyz` is 200 once the dataset is loaded and its layout is intact, 503 otherwise, with
+//!   the served dataset's `last_updated` version in the body.
+//! - `GET /healthz` is 200 while the process is serving.
+//! - `GET /metrics` serves Prometheus counters and gauges.
+//!
+//! # Limits
+//!
+//! Request bodies over [`Config::max_body_bytes`] get 413, as do batches of more than
+//! [`Config::max_batch`] queries. At most [`Config::max_in_flight`] `/check` and
+//! `/truncated-range` requests are served at once; the rest get 503 rather than queueing.
+//!
+//! # Dataset Updates
+//!
+//! The dataset is opened with `hibp_verifier::reload::ReloadingChecker`, so pointing
+//! `--dataset-path` at the `current` link of `hibp-sync-client --keep-versions` picks up each
+//! sync as it commits. If the dataset is missing at startup, the server keeps retrying and
+//! reports not ready until it appears.
+//!
+//! A dataset synced with `hibp-sync-client --shard` or `--prefilter` needs the matching
+//! [`Config::prefix_range`] or [`Config::prefilter`]; every new version is opened with them.
+
+use std::convert::Infallible;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+pub mod api;
+pub mod error;
+pub mod metrics;
+
+pub use api::AppState;
+pub use error::Error;
+
+/// Server options.
+pub struct Config {
+    /// Dataset directory, e.g. `<data-dir>/current` of `hibp-sync-client --keep-versions`.
+    pub dataset_path: PathBuf,
+    /// Largest accepted request body.
+    pub max_body_bytes: usize,
+    /// Most queries accepted in one `/check` batch.
+    pub max_batch: usize,
+    /// Most `/check` and `/truncated-range` requests served concurrently.
+    pub max_in_flight: usize,
+    /// Delay between attempts to open the dataset while it is missing or incomplete.
+    pub open_retry_interval: Duration,
+    /// Serve only this prefix range, for a dataset synced with `hibp-sync-client --shard`.
+    /// Queries for other prefixes get 421. `None` serves the full dataset.
+    pub prefix_range: Option<RangeInclusive<u32>>,
+    /// Load the `prefilter.fuse` kept by `hibp-sync-client --prefilter` with each version.
+    pub prefilter: bool,
+}
+
+/// Opens the dataset, retrying every [`Config::open_retry_interval`] until it succeeds.
+pub async fn open_dataset(state: Arc<AppState>) {
+    loop {
+        let attempt = Arc::clone(&state);
+        match tokio::task::spawn_blocking(move || attempt.open_dataset())
+            .await
+            .expect("dataset open task panicked")
+        {
+            Ok(version) => {
+                tracing::info!(?version, "dataset loaded");
+                return;
+            }
+            Err(e) => {
+                let retry = state.config().open_retry_interval;
+                tracing::warn!(error = %e, retry_in_secs = retry.as_secs(), "dataset not available");
+                tokio::time::sleep(retry).await;
+            }
+        }
+    }
+}
+
+/// Serves HTTP/1 connections on `listener` until an accept fails.
+pub async fn run(state: Arc<AppState>, listener: TcpListener) -> Result<(), Error> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |req| {
+                let state = Arc::clone(&state);
+                async move { Ok::<_, Infallible>(api::handle(req, &state).await) }
+            });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(error = %e, "connection error");
+            }
+        });
+    }
+}
@@ -0,0 +1,120 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use hibp_server::{AppState, Config, open_dataset, run};
+use hibp_verifier::shard::Shard;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(l: LogLevel) -> Self {
+        match l {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "hibp-server")]
+#[command(about = "Answer password breach checks over HTTP from a local HIBP dataset")]
+struct Args {
+    /// Dataset directory; use <data-dir>/current with hibp-sync-client --keep-versions
+    #[arg(long)]
+    dataset_path: PathBuf,
+
+    /// Socket address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Largest accepted request body in bytes
+    #[arg(long, default_value = "65536")]
+    max_body_bytes: usize,
+
+    /// Most queries accepted in one /check batch
+    #[arg(long, default_value = "1000")]
+    max_batch: usize,
+
+    /// Most /check and /truncated-range requests served at once; further requests get 503
+    #[arg(long, default_value = "1024")]
+    max_in_flight: usize,
+
+    /// Seconds between attempts to open the dataset while it is missing
+    #[arg(long, default_value = "5")]
+    open_retry_secs: u64,
+
+    /// Serve only one shard of the dataset, given as INDEX/COUNT like hibp-sync-client --shard
+    #[arg(long)]
+    shard: Option<Shard>,
+
+    /// Load the pre-filter (prefilter.fuse) kept by hibp-sync-client --prefilter
+    #[arg(long)]
+    prefilter: bool,
+
+    /// Log level
+    #[arg(long, default_value = "info")]
+    log_level: LogLevel,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::from(args.log_level))
+        .init();
+
+    let config = Config {
+        dataset_path: args.dataset_path,
+        max_body_bytes: args.max_body_bytes,
+        max_batch: args.max_batch,
+        max_in_flight: args.max_in_flight,
+        open_retry_interval: Duration::from_secs(args.open_retry_secs),
+        prefix_range: args.shard.map(|shard| shard.range()),
+        prefilter: args.prefilter,
+    };
+    let state = match AppState::new(config) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            tracing::error!(error = %e, "invalid server configuration");
+            process::exit(1);
+        }
+    };
+
+    let listener = match tokio::net::TcpListener::bind(args.listen).await {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!(error = %e, addr = %args.listen, "failed to bind");
+            process::exit(1);
+        }
+    };
+    tracing::info!(addr = %args.listen, "listening");
+
+    tokio::spawn(open_dataset(Arc::clone(&state)));
+
+    tokio::select! {
+        result = run(state, listener) => {
+            if let Err(e) = result {
+                tracing::error!(error = %e, "server failed");
+                process::exit(1);
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            tracing::info!("shutting down");
+        }
+    }
+}
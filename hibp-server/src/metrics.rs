@@ -0,0 +1,139 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::ApiError;
+
+/// Request and lookup counters, served on `/metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub check_requests_total: AtomicU64,
+    pub range_requests_total: AtomicU64,
+    pub lookups_total: AtomicU64,
+    pub breached_total: AtomicU64,
+    pub lookup_errors_total: AtomicU64,
+    pub bad_requests_total: AtomicU64,
+    pub rejected_too_large_total: AtomicU64,
+    pub rejected_overloaded_total: AtomicU64,
+    pub rejected_not_ready_total: AtomicU64,
+}
+
+/// Dataset state sampled when `/metrics` is scraped.
+pub struct DatasetGauges {
+    pub ready: bool,
+    /// `last_updated` of the dataset being served, as Unix seconds.
+    pub last_updated: Option<i64>,
+    pub in_flight: usize,
+}
+
+impl Metrics {
+    /// Counts a request that was answered with `error`.
+    pub fn record_error(&self, error: &ApiError) {
+        let counter = match error {
+            ApiError::NotFound | ApiError::MethodNotAllowed | ApiError::HibpRangeUnsupported => {
+                return;
+            }
+            ApiError::BodyRead
+            | ApiError::InvalidBody(_)
+            | ApiError::InvalidQuery
+            | ApiError::InvalidSha1
+            | ApiError::InvalidPrefix => &self.bad_requests_total,
+            ApiError::BodyTooLarge(_) | ApiError::BatchTooLarge { .. } => {
+                &self.rejected_too_large_total
+            }
+            ApiError::Overloaded => &self.rejected_overloaded_total,
+            ApiError::NotReady => &self.rejected_not_ready_total,
+            ApiError::Lookup(_) => &self.lookup_errors_total,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self, dataset: &DatasetGauges) -> String {
+        fn gauge(out: &mut String, name: &str, help: &str, value: Option<i64>) {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            if let Some(v) = value {
+                let _ = writeln!(out, "{name} {v}");
+            }
+        }
+        fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let mut out = String::new();
+        gauge(
+            &mut out,
+            "hibp_server_dataset_ready",
+            "Whether the dataset is loaded and its layout is intact.",
+            Some(dataset.ready as i64),
+        );
+        gauge(
+            &mut out,
+            "hibp_server_dataset_last_updated_timestamp_seconds",
+            "Server last_updated of the dataset being served.",
+            dataset.last_updated,
+        );
+        gauge(
+            &mut out,
+            "hibp_server_in_flight_requests",
+            "Lookup requests currently being served.",
+            Some(dataset.in_flight as i64),
+        );
+        counter(
+            &mut out,
+            "hibp_server_check_requests_total",
+            "Requests to /check.",
+            &self.check_requests_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_range_requests_total",
+            "Requests to /truncated-range.",
+            &self.range_requests_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_lookups_total",
+            "Passwords and hashes checked, counting each query in a batch.",
+            &self.lookups_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_breached_total",
+            "Lookups that found the password breached.",
+            &self.breached_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_lookup_errors_total",
+            "Requests failed by a dataset error.",
+            &self.lookup_errors_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_bad_requests_total",
+            "Requests rejected as malformed.",
+            &self.bad_requests_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_rejected_too_large_total",
+            "Requests rejected for body or batch size.",
+            &self.rejected_too_large_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_rejected_overloaded_total",
+            "Requests rejected because too many were in flight.",
+            &self.rejected_overloaded_total,
+        );
+        counter(
+            &mut out,
+            "hibp_server_rejected_not_ready_total",
+            "Requests rejected before the dataset was loaded.",
+            &self.rejected_not_ready_total,
+        );
+        out
+    }
+}
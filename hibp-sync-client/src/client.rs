@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use bytes::{BufMut as _, Bytes, BytesMut};
 use chrono::{DateTime, Utc};
 use compact_str::CompactString;
@@ -14,10 +16,14 @@ static STATUS_PATH: &[u8] = b"/v1/status";
 static CHANGED_PATH: &[u8] = b"/v1/changed";
 static SEGMENT_PREFIX: &[u8] = b"/v1/segment?segment=";
 static OF_PARAM: &[u8] = b"&of=";
+static AFTER_PARAM: &[u8] = b"&after=";
 static SINCE_PARAM: &[u8] = b"&since=";
+static START_PARAM: &[u8] = b"&start=";
+static END_PARAM: &[u8] = b"&end=";
 
-// Max path+query: "/v1/segment?segment=254&of=255&since=2026-01-01T00:00:00Z" = 57 bytes
-const SEGMENT_BUF_CAP: usize = 64;
+// Max path+query: "/v1/segment?segment=254&of=255&after=FFFFF&start=00000&end=FFFFF
+// &since=2026-01-01T00:00:00Z" = 95 bytes
+const SEGMENT_BUF_CAP: usize = 96;
 
 pub struct Client {
     scheme: http::uri::Scheme,
@@ -81,12 +87,17 @@ impl Client {
         Ok(serde_json::from_slice(&bytes)?)
     }
 
+    /// Streams a segment of prefix files. When `range` is set, only prefixes inside it are
+    /// split into segments. When `after` is set, the server skips every prefix up to and
+    /// including it so a partially written segment can pick up where it stopped.
     #[tracing::instrument(skip(self))]
     pub async fn segment_stream(
         &self,
         segment: u8,
         of: u8,
         since: Option<&str>,
+        range: Option<&RangeInclusive<u32>>,
+        after: Option<&[u8; 5]>,
     ) -> Result<impl AsyncRead + Unpin + Send + 'static, Error> {
         let since_bytes = since.map_or(&[][..], str::as_bytes);
         let mut buf = BytesMut::with_capacity(SEGMENT_BUF_CAP + since_bytes.len());
@@ -94,6 +105,16 @@ impl Client {
         write_decimal(&mut buf, segment);
         buf.extend_from_slice(OF_PARAM);
         write_decimal(&mut buf, of);
+        if let Some(after) = after {
+            buf.extend_from_slice(AFTER_PARAM);
+            buf.extend_from_slice(after);
+        }
+        if let Some(range) = range {
+            buf.extend_from_slice(START_PARAM);
+            write_prefix(&mut buf, *range.start());
+            buf.extend_from_slice(END_PARAM);
+            write_prefix(&mut buf, *range.end());
+        }
         if !since_bytes.is_empty() {
             buf.extend_from_slice(SINCE_PARAM);
             buf.extend_from_slice(since_bytes);
@@ -116,6 +137,13 @@ impl Client {
     }
 }
 
+/// Writes `prefix` as 5 uppercase hex digits.
+fn write_prefix(buf: &mut BytesMut, prefix: u32) {
+    for shift in (0..20).step_by(4).rev() {
+        buf.put_u8(b"0123456789ABCDEF"[(prefix >> shift & 0xF) as usize]);
+    }
+}
+
 fn write_decimal(buf: &mut BytesMut, mut n: u8) {
     let start = buf.len();
     let mut count = 0;
@@ -169,6 +197,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn segment_uri_with_after() {
+        let client = Client::new(&"http://127.0.0.1:8765".parse().unwrap()).unwrap();
+        let mut buf = BytesMut::with_capacity(SEGMENT_BUF_CAP);
+        buf.extend_from_slice(SEGMENT_PREFIX);
+        write_decimal(&mut buf, 254);
+        buf.extend_from_slice(OF_PARAM);
+        write_decimal(&mut buf, 255);
+        buf.extend_from_slice(AFTER_PARAM);
+        buf.extend_from_slice(b"FFFFF");
+        buf.extend_from_slice(SINCE_PARAM);
+        buf.extend_from_slice(b"2026-01-01T00:00:00Z");
+        assert!(buf.len() <= SEGMENT_BUF_CAP);
+        let uri = client.uri(buf.freeze());
+        assert_eq!(
+            uri.to_string(),
+            "http://127.0.0.1:8765/v1/segment?segment=254&of=255&after=FFFFF&since=2026-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn segment_uri_with_range() {
+        let client = Client::new(&"http://127.0.0.1:8765".parse().unwrap()).unwrap();
+        let mut buf = BytesMut::with_capacity(SEGMENT_BUF_CAP);
+        buf.extend_from_slice(SEGMENT_PREFIX);
+        write_decimal(&mut buf, 254);
+        buf.extend_from_slice(OF_PARAM);
+        write_decimal(&mut buf, 255);
+        buf.extend_from_slice(AFTER_PARAM);
+        buf.extend_from_slice(b"FFFFF");
+        buf.extend_from_slice(START_PARAM);
+        write_prefix(&mut buf, 0x60000);
+        buf.extend_from_slice(END_PARAM);
+        write_prefix(&mut buf, 0x7FFFF);
+        buf.extend_from_slice(SINCE_PARAM);
+        buf.extend_from_slice(b"2026-01-01T00:00:00Z");
+        assert!(buf.len() <= SEGMENT_BUF_CAP);
+        let uri = client.uri(buf.freeze());
+        assert_eq!(
+            uri.to_string(),
+            "http://127.0.0.1:8765/v1/segment?segment=254&of=255&after=FFFFF&start=60000&end=7FFFF&since=2026-01-01T00:00:00Z"
+        );
+    }
+
     #[test]
     fn new_rejects_missing_scheme() {
         assert!(Client::new(&http::Uri::from_static("/no-scheme")).is_err());
@@ -0,0 +1,370 @@
+use std::convert::Infallible;
+use std::fmt::Write as _;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, TimeDelta, Utc};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::sync::{Config, Outcome, local_last_updated, sync};
+
+/// Scheduling options for [`Daemon`].
+pub struct DaemonConfig {
+    /// Time between successful polls of the server's `/v1/status`.
+    pub poll_interval: Duration,
+    /// Upper bound of the random delay added to every sleep, so a fleet of clients does not
+    /// poll the server in lockstep.
+    pub jitter: Duration,
+    /// Upper bound of the delay after consecutive failures. The delay starts at
+    /// `poll_interval` and doubles with each failure until it reaches this value.
+    pub max_backoff: Duration,
+}
+
+/// Counters and timestamps describing the daemon's progress, served on `/healthz` and
+/// `/metrics`.
+#[derive(Default, Clone, Serialize)]
+pub struct Stats {
+    /// When a poll last succeeded, whether or not it had anything to sync.
+    pub last_poll: Option<DateTime<Utc>>,
+    /// When a sync last committed a new dataset.
+    pub last_sync: Option<DateTime<Utc>>,
+    /// The server `last_updated` of the locally committed dataset.
+    pub local_last_updated: Option<DateTime<Utc>>,
+    /// The server `last_updated` seen on the most recent poll.
+    pub server_last_updated: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+    pub syncs_total: u64,
+    pub failures_total: u64,
+}
+
+impl Stats {
+    /// How far the local dataset trails the server, as of the most recent poll.
+    pub fn lag(&self) -> Option<TimeDelta> {
+        match (self.server_last_updated, self.local_last_updated) {
+            (Some(server), Some(local)) => Some((server - local).max(TimeDelta::zero())),
+            (Some(_), None) => None,
+            (None, _) => Some(TimeDelta::zero()),
+        }
+    }
+
+    /// Healthy once a poll has succeeded and no poll has failed since.
+    pub fn is_healthy(&self) -> bool {
+        self.last_poll.is_some() && self.consecutive_failures == 0
+    }
+}
+
+/// Keeps a local dataset in sync by polling the server and running [`sync`] whenever the
+/// server has published a newer dataset.
+pub struct Daemon {
+    config: Config,
+    schedule: DaemonConfig,
+    client: Client,
+    stats: Arc<RwLock<Stats>>,
+}
+
+impl Daemon {
+    pub fn new(config: Config, schedule: DaemonConfig) -> Result<Self, Error> {
+        if schedule.poll_interval.is_zero() {
+            return Err(Error::InvalidConfig("poll interval must be > 0"));
+        }
+        if schedule.max_backoff < schedule.poll_interval {
+            return Err(Error::InvalidConfig("max backoff must be >= poll interval"));
+        }
+        let client = Client::new(&config.server_url)?;
+        Ok(Self { config, schedule, client, stats: Arc::new(RwLock::new(Stats::default())) })
+    }
+
+    /// Shared handle to the daemon's stats, e.g. for [`serve_health`].
+    pub fn stats(&self) -> Arc<RwLock<Stats>> {
+        Arc::clone(&self.stats)
+    }
+
+    /// Polls the server once and syncs if it has advanced past the local dataset.
+    ///
+    /// Returns the sync outcome, or `None` if the local dataset was already current.
+    #[tracing::instrument(skip_all)]
+    pub async fn poll_once(&self) -> Result<Option<Outcome>, Error> {
+        let result = self.check_and_sync().await;
+        let mut stats = self.stats.write().unwrap();
+        match &result {
+            Ok(_) => {
+                stats.last_poll = Some(Utc::now());
+                stats.consecutive_failures = 0;
+            }
+            Err(_) => {
+                stats.consecutive_failures += 1;
+                stats.failures_total += 1;
+            }
+        }
+        result
+    }
+
+    async fn check_and_sync(&self) -> Result<Option<Outcome>, Error> {
+        let server = self.client.status().await?.last_updated;
+        let local = local_last_updated(&self.config).await?;
+        {
+            let mut stats = self.stats.write().unwrap();
+            stats.server_last_updated = server;
+            stats.local_last_updated = local;
+        }
+
+        if server.is_none() || server <= local {
+            return Ok(None);
+        }
+
+        tracing::info!(server = ?server, local = ?local, "server advanced; syncing");
+        let outcome = sync(&self.config).await?;
+        let local = local_last_updated(&self.config).await?;
+        let mut stats = self.stats.write().unwrap();
+        stats.local_last_updated = local;
+        stats.syncs_total += 1;
+        if outcome != Outcome::UpToDate {
+            stats.last_sync = Some(Utc::now());
+        }
+        Ok(Some(outcome))
+    }
+
+    /// Polls forever, backing off exponentially while polls or syncs fail.
+    pub async fn run(&self) {
+        loop {
+            match self.poll_once().await {
+                Ok(None) => tracing::debug!("already up to date"),
+                Ok(Some(Outcome::UpToDate)) => tracing::info!("already up to date"),
+                Ok(Some(Outcome::DeltaSync { changed_count })) => {
+                    tracing::info!(changed = changed_count, "delta sync complete");
+                }
+                Ok(Some(Outcome::FullSync { file_count })) => {
+                    tracing::info!(files = file_count, "full sync complete");
+                }
+                Err(e) => tracing::error!(error = %e, "sync failed"),
+            }
+
+            let failures = self.stats.read().unwrap().consecutive_failures;
+            let delay = next_delay(&self.schedule, failures);
+            tracing::debug!(next_poll_in_secs = delay.as_secs(), "poll scheduled");
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn next_delay(schedule: &DaemonConfig, failures: u32) -> Duration {
+    let base = schedule
+        .poll_interval
+        .checked_mul(1u32.checked_shl(failures).unwrap_or(u32::MAX))
+        .map_or(schedule.max_backoff, |d| d.min(schedule.max_backoff));
+    let jitter_ms = schedule.jitter.as_millis().min(u64::MAX as u128) as u64;
+    base + Duration::from_millis(fastrand::u64(0..=jitter_ms))
+}
+
+/// Serves `/healthz` and `/metrics` for a running [`Daemon`] until the listener fails.
+///
+/// `/healthz` returns 200 with a JSON summary while the daemon is healthy and 503 otherwise.
+/// `/metrics` returns the same values in the Prometheus text format.
+pub async fn serve_health(listener: TcpListener, stats: Arc<RwLock<Stats>>) -> Result<(), Error> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let stats = Arc::clone(&stats);
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |req| {
+                let resp = respond(&req, &stats.read().unwrap());
+                async move { Ok::<_, Infallible>(resp) }
+            });
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(error = %e, "health connection error");
+            }
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct Health<'a> {
+    healthy: bool,
+    lag_seconds: Option<i64>,
+    #[serde(flatten)]
+    stats: &'a Stats,
+}
+
+fn respond(req: &hyper::Request<Incoming>, stats: &Stats) -> hyper::Response<Full<Bytes>> {
+    let (status, content_type, body) = match (req.method(), req.uri().path()) {
+        (&http::Method::GET, "/healthz") => {
+            let healthy = stats.is_healthy();
+            let body = Health { healthy, lag_seconds: stats.lag().map(|d| d.num_seconds()), stats };
+            let status = if healthy {
+                http::StatusCode::OK
+            } else {
+                http::StatusCode::SERVICE_UNAVAILABLE
+            };
+            let json = serde_json::to_vec(&body).expect("stats serialize infallibly");
+            (status, "application/json", Bytes::from(json))
+        }
+        (&http::Method::GET, "/metrics") => (
+            http::StatusCode::OK,
+            "text/plain; version=0.0.4",
+            Bytes::from(render_metrics(stats)),
+        ),
+        _ => (http::StatusCode::NOT_FOUND, "text/plain", Bytes::new()),
+    };
+
+    hyper::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, content_type)
+        .body(Full::new(body))
+        .expect("static response parts are valid")
+}
+
+fn render_metrics(stats: &Stats) -> String {
+    fn gauge(out: &mut String, name: &str, help: &str, value: Option<i64>) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        if let Some(v) = value {
+            let _ = writeln!(out, "{name} {v}");
+        }
+    }
+    fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} counter");
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    let mut out = String::new();
+    gauge(
+        &mut out,
+        "hibp_sync_last_poll_timestamp_seconds",
+        "Time of the last successful poll.",
+        stats.last_poll.map(|t| t.timestamp()),
+    );
+    gauge(
+        &mut out,
+        "hibp_sync_last_sync_timestamp_seconds",
+        "Time of the last committed sync.",
+        stats.last_sync.map(|t| t.timestamp()),
+    );
+    gauge(
+        &mut out,
+        "hibp_sync_local_last_updated_timestamp_seconds",
+        "Server last_updated of the local dataset.",
+        stats.local_last_updated.map(|t| t.timestamp()),
+    );
+    gauge(
+        &mut out,
+        "hibp_sync_server_last_updated_timestamp_seconds",
+        "Server last_updated seen on the last poll.",
+        stats.server_last_updated.map(|t| t.timestamp()),
+    );
+    gauge(
+        &mut out,
+        "hibp_sync_lag_seconds",
+        "How far the local dataset trails the server.",
+        stats.lag().map(|d| d.num_seconds()),
+    );
+    gauge(
+        &mut out,
+        "hibp_sync_consecutive_failures",
+        "Polls failed since the last success.",
+        Some(stats.consecutive_failures as i64),
+    );
+    counter(
+        &mut out,
+        "hibp_sync_syncs_total",
+        "Syncs run.",
+        stats.syncs_total,
+    );
+    counter(
+        &mut out,
+        "hibp_sync_failures_total",
+        "Failed polls or syncs.",
+        stats.failures_total,
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(jitter: Duration) -> DaemonConfig {
+        DaemonConfig {
+            poll_interval: Duration::from_secs(60),
+            jitter,
+            max_backoff: Duration::from_secs(600),
+        }
+    }
+
+    #[test]
+    fn next_delay_backs_off_and_caps() {
+        let s = schedule(Duration::ZERO);
+        assert_eq!(next_delay(&s, 0), Duration::from_secs(60));
+        assert_eq!(next_delay(&s, 1), Duration::from_secs(120));
+        assert_eq!(next_delay(&s, 3), Duration::from_secs(480));
+        assert_eq!(next_delay(&s, 4), Duration::from_secs(600));
+        assert_eq!(next_delay(&s, 40), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn next_delay_adds_bounded_jitter() {
+        let s = schedule(Duration::from_secs(5));
+        for _ in 0..100 {
+            let d = next_delay(&s, 0);
+            assert!((Duration::from_secs(60)..=Duration::from_secs(65)).contains(&d));
+        }
+    }
+
+    #[test]
+    fn lag_and_health() {
+        let t0 = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().to_utc();
+        let mut stats = Stats::default();
+        assert!(!stats.is_healthy());
+        assert_eq!(stats.lag(), Some(TimeDelta::zero()));
+
+        stats.server_last_updated = Some(t0 + TimeDelta::hours(2));
+        assert_eq!(stats.lag(), None);
+
+        stats.local_last_updated = Some(t0);
+        stats.last_poll = Some(t0);
+        assert!(stats.is_healthy());
+        assert_eq!(stats.lag(), Some(TimeDelta::hours(2)));
+
+        stats.consecutive_failures = 1;
+        assert!(!stats.is_healthy());
+    }
+
+    #[test]
+    fn metrics_omit_unknown_values() {
+        let stats = Stats { syncs_total: 3, ..Stats::default() };
+        let text = render_metrics(&stats);
+        assert!(text.contains("hibp_sync_syncs_total 3\n"));
+        assert!(text.contains("hibp_sync_lag_seconds 0\n"));
+        assert!(!text.contains("\nhibp_sync_last_poll_timestamp_seconds "));
+        assert!(!text.contains("\nhibp_sync_last_sync_timestamp_seconds "));
+    }
+
+    #[test]
+    fn new_rejects_backoff_below_interval() {
+        let config = Config {
+            server_url: "http://127.0.0.1:8765".parse().unwrap(),
+            data_dir: "/tmp".into(),
+            segments: 1,
+            parallel: 1,
+            keep_versions: None,
+            prefilter: false,
+            prefix_range: None,
+            on_commit: None,
+        };
+        let schedule =
+            DaemonConfig { max_backoff: Duration::from_secs(1), ..schedule(Duration::ZERO) };
+        assert!(matches!(
+            Daemon::new(config, schedule),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -26,4 +26,10 @@ pub enum Error {
 
     #[error("invalid configuration: {0}")]
     InvalidConfig(&'static str),
+
+    #[error("dataset error: {0}")]
+    Dataset(#[from] hibp_verifier::Error),
+
+    #[error("post-sync command exited with {0}")]
+    HookExit(std::process::ExitStatus),
 }
@@ -0,0 +1,214 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use http_body_util::Full;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::error::Error;
+use crate::sync::{Commit, CommitCallback};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notifications sent after each successful commit.
+///
+/// Hooks run on their own task so a slow command or webhook never delays a sync. Failures
+/// are logged and otherwise ignored: the dataset has already been committed.
+#[derive(Default)]
+pub struct Hooks {
+    /// Shell command run via `sh -c`. The commit is described in the environment variables
+    /// `HIBP_SYNC_OUTCOME`, `HIBP_SYNC_FILE_COUNT`, `HIBP_SYNC_LAST_UPDATED` and
+    /// `HIBP_SYNC_DATASET_DIR`.
+    pub command: Option<String>,
+    /// `http://` URL that receives the commit as a JSON `POST`.
+    pub webhook: Option<http::Uri>,
+}
+
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    outcome: &'static str,
+    file_count: usize,
+    last_updated: DateTime<Utc>,
+    dataset_dir: &'a std::path::Path,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.command.is_none() && self.webhook.is_none()
+    }
+
+    /// Starts a task that runs the hooks for every commit reported through the returned
+    /// callback. The task exits once every clone of the callback has been dropped, so
+    /// awaiting the handle waits for outstanding notifications.
+    pub fn spawn(self) -> (CommitCallback, JoinHandle<()>) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Commit>();
+        let handle = tokio::spawn(async move {
+            while let Some(commit) = rx.recv().await {
+                self.run(&commit).await;
+            }
+        });
+        let callback: CommitCallback = Arc::new(move |commit: &Commit| {
+            let _ = tx.send(commit.clone());
+        });
+        (callback, handle)
+    }
+
+    /// Runs every configured hook for one commit, logging failures.
+    #[tracing::instrument(skip_all, fields(outcome = commit.outcome.as_str()))]
+    pub async fn run(&self, commit: &Commit) {
+        if let Some(command) = &self.command
+            && let Err(e) = run_command(command, commit).await
+        {
+            tracing::warn!(error = %e, "post-sync command failed");
+        }
+        if let Some(url) = &self.webhook
+            && let Err(e) = post_webhook(url, commit).await
+        {
+            tracing::warn!(error = %e, "post-sync webhook failed");
+        }
+    }
+}
+
+async fn run_command(command: &str, commit: &Commit) -> Result<(), Error> {
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("HIBP_SYNC_OUTCOME", commit.outcome.as_str())
+        .env(
+            "HIBP_SYNC_FILE_COUNT",
+            commit.outcome.file_count().to_string(),
+        )
+        .env(
+            "HIBP_SYNC_LAST_UPDATED",
+            commit.last_updated.to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+        .env("HIBP_SYNC_DATASET_DIR", &commit.dataset_dir)
+        .status()
+        .await?;
+    if !status.success() {
+        return Err(Error::HookExit(status));
+    }
+    tracing::info!("post-sync command finished");
+    Ok(())
+}
+
+async fn post_webhook(url: &http::Uri, commit: &Commit) -> Result<(), Error> {
+    let body = WebhookBody {
+        outcome: commit.outcome.as_str(),
+        file_count: commit.outcome.file_count(),
+        last_updated: commit.last_updated,
+        dataset_dir: &commit.dataset_dir,
+    };
+    let req = hyper::Request::post(url)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(&body)?)))?;
+
+    let client: hyper_util::client::legacy::Client<HttpConnector, Full<Bytes>> =
+        hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+            .build(HttpConnector::new());
+    let resp = tokio::time::timeout(WEBHOOK_TIMEOUT, client.request(req))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "webhook timed out"))??;
+    if !resp.status().is_success() {
+        return Err(Error::HttpStatus(resp.status()));
+    }
+    tracing::info!("post-sync webhook delivered");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::sync::Outcome;
+
+    fn commit() -> Commit {
+        Commit {
+            outcome: Outcome::DeltaSync { changed_count: 4 },
+            last_updated: DateTime::parse_from_rfc3339("2026-01-01T01:00:00Z").unwrap().to_utc(),
+            dataset_dir: "/data/hibp".into(),
+            changed_prefixes: Some(vec![0x00000, 0xCBFDA]),
+        }
+    }
+
+    #[tokio::test]
+    async fn command_receives_commit_env() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("env.txt");
+        let command = format!(
+            "echo \"$HIBP_SYNC_OUTCOME $HIBP_SYNC_FILE_COUNT $HIBP_SYNC_LAST_UPDATED \
+             $HIBP_SYNC_DATASET_DIR\" > {}",
+            out.display()
+        );
+
+        run_command(&command, &commit()).await.unwrap();
+
+        let got = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(got, "delta_sync 4 2026-01-01T01:00:00Z /data/hibp\n");
+    }
+
+    #[tokio::test]
+    async fn command_failure_is_reported() {
+        let err = run_command("exit 3", &commit()).await.unwrap_err();
+        assert!(matches!(err, Error::HookExit(s) if s.code() == Some(3)));
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_json() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: http::Uri =
+            format!("http://{}/hook", listener.local_addr().unwrap()).parse().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut req = Vec::new();
+            let mut buf = [0u8; 1024];
+            // Read until the JSON body has been received in full.
+            while !req.ends_with(b"}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                req.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(req).unwrap()
+        });
+
+        post_webhook(&url, &commit()).await.unwrap();
+
+        let req = server.await.unwrap();
+        assert!(req.starts_with("POST /hook HTTP/1.1\r\n"));
+        let body = &req[req.find("\r\n\r\n").unwrap() + 4..];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["outcome"], "delta_sync");
+        assert_eq!(json["file_count"], 4);
+        assert_eq!(json["last_updated"], "2026-01-01T01:00:00Z");
+        assert_eq!(json["dataset_dir"], "/data/hibp");
+    }
+
+    #[tokio::test]
+    async fn spawned_hooks_drain_after_callback_dropped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("count.txt");
+        let hooks = Hooks {
+            command: Some(format!("echo $HIBP_SYNC_FILE_COUNT >> {}", out.display())),
+            webhook: None,
+        };
+
+        let (callback, handle) = hooks.spawn();
+        callback(&commit());
+        callback(&commit());
+        drop(callback);
+        handle.await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "4\n4\n");
+    }
+}
@@ -14,8 +14,8 @@
 //!
 //! On first run (no local dataset yet), every prefix file is fetched from the server.
 //! The server divides the full dataset into `--segments` chunks and the client fetches
-//! them sequentially, decompressing each segment and writing the prefix files to a staging
-//! directory before atomically moving them to the data directory.
+//! up to `--parallel` of them at once, decompressing each segment and writing the prefix
+//! files to a staging directory before atomically moving them to the data directory.
 //!
 //! ## Delta Sync
 //!
@@ -33,9 +33,22 @@
 //!
 //! All downloads land in a `.staging/` subdirectory of the data directory before being
 //! committed. If the process is interrupted mid-download, the next run resumes from where
-//! it left off - completed segments are not re-fetched. If the server's data has changed
+//! it left off - completed segments are not re-fetched, and a partially written segment
+//! continues after the last prefix file it finished. If the server's data has changed
 //! between attempts, the stale staging directory is discarded and a fresh sync starts.
 //!
+//! # Versioned Layout
+//!
+//! By default committed files are renamed one by one into the data directory, so a reader
+//! can briefly see a mix of old and new files. With `--keep-versions K` each sync is
+//! instead assembled in its own `versions/<timestamp>/` directory, with unchanged files
+//! hard-linked from the previous version, and published by atomically repointing the
+//! `current` symlink. Point `hibp-verifier` at `<data-dir>/current`. The newest `K`
+//! versions are kept so an operator can roll back by repointing the symlink.
+//!
+//! Switching an existing flat data directory to the versioned layout triggers one full
+//! sync, since the new layout has no prior version to build on.
+//!
 //! # Usage
 //!
 //! First, ensure an `hibp-bin-fetch serve` instance is running and accessible.
@@ -52,6 +65,26 @@
 //! hibp-sync-client --server-url http://192.168.1.10:8765 --data-dir ./hibp-data --segments 32
 //! ```
 //!
+//! Over a high-latency link, download several segments concurrently:
+//!
+//! ```sh
+//! hibp-sync-client --server-url http://192.168.1.10:8765 --data-dir ./hibp-data --parallel 4
+//! ```
+//!
+//! ## Daemon
+//!
+//! Instead of running the binary from a timer, `--daemon` keeps it running. It polls the
+//! server's `/v1/status` every `--poll-interval-secs` (plus up to `--poll-jitter-secs` of
+//! random delay) and syncs whenever the server has published a newer dataset. Failed polls
+//! back off exponentially up to `--max-backoff-secs`. With `--listen`, the daemon serves
+//! `/healthz` and Prometheus `/metrics` reporting the last successful sync and the lag
+//! behind the server:
+//!
+//! ```sh
+//! hibp-sync-client --server-url http://192.168.1.10:8765 --data-dir ./hibp-data \
+//!     --daemon --listen 127.0.0.1:9100
+//! ```
+//!
 //! ## Library
 //!
 //! ```rust,ignore
@@ -62,6 +95,11 @@
 //!     server_url: "http://192.168.1.10:8765".parse().unwrap(),
 //!     data_dir: PathBuf::from("./hibp-data"),
 //!     segments: 16,
+//!     parallel: 4,
+//!     keep_versions: None,
+//!     prefilter: false,
+//!     prefix_range: None,
+//!     on_commit: None,
 //! };
 //!
 //! match sync(&config).await? {
@@ -72,6 +110,8 @@
 //! ```
 
 pub mod client;
+pub mod daemon;
 pub mod error;
+pub mod hooks;
 pub mod sync;
 pub mod wire;
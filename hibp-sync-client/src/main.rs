@@ -1,11 +1,16 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process;
+use std::time::Duration;
 
 use clap::Parser;
+use hibp_sync_client::daemon::{Daemon, DaemonConfig, serve_health};
+use hibp_sync_client::hooks::Hooks;
 use hibp_sync_client::sync::{Config, Outcome, sync};
+use hibp_verifier::shard::Shard;
 use http::Uri;
 
-fn parse_segments(s: &str) -> Result<u8, String> {
+fn parse_nonzero_u8(s: &str) -> Result<u8, String> {
     let n: u8 = s.parse().map_err(|_| "must be an integer in 1..=255".to_string())?;
     if n == 0 {
         return Err("must be in 1..=255".to_string());
@@ -47,9 +52,57 @@ struct Args {
     data_dir: PathBuf,
 
     /// Number of segments to split the sync into (controls resume granularity, max 255)
-    #[arg(long, default_value = "16", value_parser = parse_segments)]
+    #[arg(long, default_value = "16", value_parser = parse_nonzero_u8)]
     segments: u8,
 
+    /// Maximum number of segments to download concurrently (max 255)
+    #[arg(long, default_value = "1", value_parser = parse_nonzero_u8)]
+    parallel: u8,
+
+    /// Build each sync in its own version directory and publish it by atomically flipping a
+    /// `current` symlink, keeping this many versions for rollback (max 255)
+    #[arg(long, value_parser = parse_nonzero_u8)]
+    keep_versions: Option<u8>,
+
+    /// Maintain a pre-filter (prefilter.fuse) for hibp-verifier alongside the dataset,
+    /// rebuilding it before each sync is published
+    #[arg(long)]
+    prefilter: bool,
+
+    /// Sync only one shard of the dataset, given as INDEX/COUNT (e.g. 3/8 for the fourth of
+    /// eight equal prefix ranges)
+    #[arg(long)]
+    shard: Option<Shard>,
+
+    /// Keep running, polling the server and syncing whenever it publishes a new dataset
+    #[arg(long)]
+    daemon: bool,
+
+    /// Seconds between polls of the server in daemon mode
+    #[arg(long, default_value = "300", requires = "daemon")]
+    poll_interval_secs: u64,
+
+    /// Maximum random delay in seconds added to each poll in daemon mode
+    #[arg(long, default_value = "30", requires = "daemon")]
+    poll_jitter_secs: u64,
+
+    /// Maximum delay in seconds between polls while they keep failing in daemon mode
+    #[arg(long, default_value = "3600", requires = "daemon")]
+    max_backoff_secs: u64,
+
+    /// Socket address for the /healthz and /metrics endpoints in daemon mode
+    #[arg(long, requires = "daemon")]
+    listen: Option<SocketAddr>,
+
+    /// Shell command to run after each successful sync. Receives HIBP_SYNC_OUTCOME,
+    /// HIBP_SYNC_FILE_COUNT, HIBP_SYNC_LAST_UPDATED and HIBP_SYNC_DATASET_DIR in its environment
+    #[arg(long)]
+    post_sync_command: Option<String>,
+
+    /// http:// URL to POST a JSON description of each successful sync to
+    #[arg(long)]
+    webhook_url: Option<Uri>,
+
     /// Log level
     #[arg(long, default_value = "info")]
     log_level: LogLevel,
@@ -68,10 +121,44 @@ async fn main() {
         process::exit(1);
     }
 
-    let config =
-        Config { server_url: args.server_url, data_dir: args.data_dir, segments: args.segments };
+    let hooks = Hooks { command: args.post_sync_command, webhook: args.webhook_url };
+    let (on_commit, hooks_task) = if hooks.is_empty() {
+        (None, None)
+    } else {
+        let (callback, task) = hooks.spawn();
+        (Some(callback), Some(task))
+    };
+
+    let config = Config {
+        server_url: args.server_url,
+        data_dir: args.data_dir,
+        segments: args.segments,
+        parallel: args.parallel,
+        keep_versions: args.keep_versions,
+        prefilter: args.prefilter,
+        prefix_range: args.shard.map(|shard| shard.range()),
+        on_commit,
+    };
+
+    if args.daemon {
+        let schedule = DaemonConfig {
+            poll_interval: Duration::from_secs(args.poll_interval_secs),
+            jitter: Duration::from_secs(args.poll_jitter_secs),
+            max_backoff: Duration::from_secs(args.max_backoff_secs),
+        };
+        run_daemon(config, schedule, args.listen).await;
+        return;
+    }
+
+    let result = sync(&config).await;
+
+    // Dropping the config releases the commit callback so the hooks task can finish.
+    drop(config);
+    if let Some(task) = hooks_task {
+        let _ = task.await;
+    }
 
-    match sync(&config).await {
+    match result {
         Ok(Outcome::UpToDate) => {
             tracing::info!("already up to date");
         }
@@ -87,3 +174,32 @@ async fn main() {
         }
     }
 }
+
+async fn run_daemon(config: Config, schedule: DaemonConfig, listen: Option<SocketAddr>) {
+    let daemon = match Daemon::new(config, schedule) {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::error!(error = %e, "invalid daemon configuration");
+            process::exit(1);
+        }
+    };
+
+    if let Some(addr) = listen {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(l) => l,
+            Err(e) => {
+                tracing::error!(error = %e, %addr, "failed to bind health endpoint");
+                process::exit(1);
+            }
+        };
+        tracing::info!(%addr, "health endpoint listening");
+        let stats = daemon.stats();
+        tokio::spawn(async move {
+            if let Err(e) = serve_health(listener, stats).await {
+                tracing::error!(error = %e, "health endpoint failed");
+            }
+        });
+    }
+
+    daemon.run().await;
+}
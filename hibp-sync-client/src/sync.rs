@@ -1,11 +1,17 @@
 use std::io;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::{DateTime, SecondsFormat, Utc};
 use futures_util::StreamExt;
+use hibp_verifier::PREFIX_COUNT;
+use hibp_verifier::prefilter::{PREFILTER_FILE, Prefilter};
+use hibp_verifier::search::RecordLayout;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
 use crate::client::Client;
 use crate::error::Error;
@@ -15,17 +21,89 @@ pub struct Config {
     pub server_url: http::Uri,
     pub data_dir: PathBuf,
     pub segments: u8,
+    /// Maximum number of segments downloaded concurrently.
+    pub parallel: u8,
+    /// When set, each sync is committed to its own directory under `versions/` and published
+    /// by atomically repointing the `current` symlink. This many versions are kept for
+    /// rollback. When `None`, files are renamed directly into `data_dir`.
+    pub keep_versions: Option<u8>,
+    /// Rebuild the dataset's pre-filter (`prefilter.fuse`, see `hibp_verifier::prefilter`)
+    /// before each commit is published. Delta syncs only rebuild the filter shards they
+    /// touched; full syncs, and deltas without a matching previous filter, rebuild it all.
+    pub prefilter: bool,
+    /// Sync only the prefixes in this range, e.g. a `hibp_verifier::shard::Shard::range`, so
+    /// the dataset can be split across hosts. `None` syncs all of them. Changing the range
+    /// forces a full sync, which deletes the prefix files outside the new range.
+    pub prefix_range: Option<RangeInclusive<u32>>,
+    /// Called after every successful commit, once the new files are live and staging has
+    /// been cleared. Runs on the sync task, so it should return quickly.
+    pub on_commit: Option<CommitCallback>,
 }
 
+/// Callback invoked with the details of each successful commit.
+pub type CommitCallback = Arc<dyn Fn(&Commit) + Send + Sync>;
+
+/// Details of a successful commit.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub outcome: Outcome,
+    /// Server `last_updated` of the dataset that is now live.
+    pub last_updated: DateTime<Utc>,
+    /// Directory holding the live dataset, see [`Config::dataset_dir`].
+    pub dataset_dir: PathBuf,
+    /// Prefixes rewritten by a delta sync, in ascending order, for consumers that update
+    /// individual prefixes in place. `None` after a full sync.
+    pub changed_prefixes: Option<Vec<u32>>,
+}
+
+impl Config {
+    /// Directory holding the live dataset, i.e. the path a `BreachChecker` should be given.
+    pub fn dataset_dir(&self) -> PathBuf {
+        match self.keep_versions {
+            Some(_) => self.data_dir.join(CURRENT_LINK),
+            None => self.data_dir.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Outcome {
     UpToDate,
     DeltaSync { changed_count: usize },
     FullSync { file_count: usize },
 }
 
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::UpToDate => "up_to_date",
+            Outcome::DeltaSync { .. } => "delta_sync",
+            Outcome::FullSync { .. } => "full_sync",
+        }
+    }
+
+    /// Number of prefix files written by the sync.
+    pub fn file_count(&self) -> usize {
+        match *self {
+            Outcome::UpToDate => 0,
+            Outcome::DeltaSync { changed_count } => changed_count,
+            Outcome::FullSync { file_count } => file_count,
+        }
+    }
+}
+
+const STATE_FILE: &str = "sync-state.json";
+/// Prefixes of a delta sync, recorded in staging before any are moved so an interrupted
+/// commit still knows the full set when it resumes.
+const CHANGED_FILE: &str = ".changed-prefixes.json";
+const VERSIONS_DIR: &str = "versions";
+const CURRENT_LINK: &str = "current";
+
 #[derive(Serialize, Deserialize, Default)]
 struct LocalState {
     last_updated: Option<DateTime<Utc>>,
+    #[serde(default)]
+    prefix_range: Option<RangeInclusive<u32>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +111,13 @@ struct Plan {
     server_last_updated: DateTime<Utc>,
     since: Option<String>,
     segments: u8,
+    #[serde(default)]
+    prefix_range: Option<RangeInclusive<u32>>,
+    /// Directory under `versions/` that a versioned sync commits to, chosen with the plan so
+    /// a resumed commit reuses it. `None` for in-place syncs and for plans written before the
+    /// name was recorded, which fall back to the timestamp name.
+    #[serde(default)]
+    version: Option<String>,
 }
 
 #[tracing::instrument(skip_all)]
@@ -40,6 +125,25 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
     if config.segments == 0 {
         return Err(Error::InvalidConfig("segments must be >= 1"));
     }
+    if config.parallel == 0 {
+        return Err(Error::InvalidConfig("parallel must be >= 1"));
+    }
+    if config.keep_versions == Some(0) {
+        return Err(Error::InvalidConfig("keep_versions must be >= 1"));
+    }
+    if let Some(range) = &config.prefix_range
+        && (range.is_empty() || *range.end() >= PREFIX_COUNT)
+    {
+        return Err(Error::InvalidConfig(
+            "prefix_range must be a non-empty range of prefixes",
+        ));
+    }
+    // The server sends sorted files, which an Eytzinger dataset would search wrongly.
+    if RecordLayout::load(&config.dataset_dir())? != RecordLayout::Sorted {
+        return Err(Error::InvalidConfig(
+            "data_dir holds an Eytzinger dataset, which cannot be synced",
+        ));
+    }
 
     let staging = config.data_dir.join(".staging");
     let complete_marker = staging.join(".complete");
@@ -48,7 +152,7 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
     if staging.exists() {
         if complete_marker.exists() {
             tracing::info!("staging/.complete exists - finishing interrupted commit");
-            return finish_commit(&staging, &config.data_dir).await;
+            return finish_commit(&staging, config).await;
         } else if plan_path.exists() {
             tracing::info!("resuming interrupted download");
             let plan: Plan = serde_json::from_slice(&fs::read(&plan_path).await?)?;
@@ -61,10 +165,14 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
                     "server state changed since last attempt; discarding staging and starting fresh"
                 );
                 clear_staging(&staging).await?;
+            } else if plan.prefix_range != config.prefix_range {
+                tracing::warn!("prefix range changed since last attempt; discarding staging");
+                clear_staging(&staging).await?;
             } else {
-                fetch_missing_segments(&config.server_url, &staging, &plan).await?;
+                fetch_missing_segments(&config.server_url, &staging, &plan, config.parallel)
+                    .await?;
                 fs::write(&complete_marker, b"").await?;
-                return finish_commit(&staging, &config.data_dir).await;
+                return finish_commit(&staging, config).await;
             }
         } else {
             tracing::warn!("staging exists without .sync-plan.json; discarding");
@@ -72,19 +180,15 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
         }
     }
 
-    let state_path = config.data_dir.join("sync-state.json");
-    let local: LocalState = match fs::read(&state_path).await {
-        Ok(bytes) => serde_json::from_slice(&bytes)?,
-        Err(e) if e.kind() == io::ErrorKind::NotFound => LocalState::default(),
-        Err(e) => return Err(e.into()),
-    };
+    let local = read_local_state(config).await?;
     let client = Client::new(&config.server_url)?;
     let server_last_updated = match client.status().await?.last_updated {
         Some(t) => t,
         None => return Ok(Outcome::UpToDate),
     };
 
-    if Some(server_last_updated) <= local.last_updated {
+    if Some(server_last_updated) <= local.last_updated && local.prefix_range == config.prefix_range
+    {
         return Ok(Outcome::UpToDate);
     }
 
@@ -92,6 +196,13 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
     // without encoding when used as a query parameter.
     let since_opt: Option<String> = if local.last_updated.is_none() {
         None
+    } else if local.prefix_range != config.prefix_range {
+        tracing::warn!(
+            local = ?local.prefix_range,
+            configured = ?config.prefix_range,
+            "prefix range changed; falling back to full sync"
+        );
+        None
     } else {
         let changed = client.changed().await?;
         if changed.prev_last_updated == local.last_updated {
@@ -106,96 +217,457 @@ pub async fn sync(config: &Config) -> Result<Outcome, Error> {
 
     fs::create_dir_all(&staging).await?;
 
-    let plan = Plan { server_last_updated, since: since_opt, segments: config.segments };
+    let version = match config.keep_versions {
+        Some(_) => {
+            Some(new_version_name(&config.data_dir.join(VERSIONS_DIR), server_last_updated).await?)
+        }
+        None => None,
+    };
+    let plan = Plan {
+        server_last_updated,
+        since: since_opt,
+        segments: config.segments,
+        prefix_range: config.prefix_range.clone(),
+        version,
+    };
     fs::write(&plan_path, serde_json::to_vec_pretty(&plan)?).await?;
 
-    fetch_missing_segments(&config.server_url, &staging, &plan).await?;
+    fetch_missing_segments(&config.server_url, &staging, &plan, config.parallel).await?;
     fs::write(&complete_marker, b"").await?;
 
-    finish_commit(&staging, &config.data_dir).await
+    finish_commit(&staging, config).await
+}
+
+/// Returns the server `last_updated` timestamp of the locally committed dataset, or `None`
+/// if nothing has been synced yet for the configured prefix range.
+pub async fn local_last_updated(config: &Config) -> Result<Option<DateTime<Utc>>, Error> {
+    let local = read_local_state(config).await?;
+    Ok(local.last_updated.filter(|_| local.prefix_range == config.prefix_range))
 }
 
-#[tracing::instrument(skip(server_url, staging), fields(segments = plan.segments, since = plan.since.as_deref()))]
+async fn read_local_state(config: &Config) -> Result<LocalState, Error> {
+    match fs::read(config.dataset_dir().join(STATE_FILE)).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(LocalState::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fetches every segment that does not yet have a `.seg.N.done` marker, with at most
+/// `parallel` segments in flight at once.
+///
+/// Each in-flight segment decodes one entry at a time and holds at most one staging file
+/// open, so memory and file descriptor usage scale with `parallel` rather than `segments`.
+/// If any segment fails the remaining downloads are dropped; segments that finished keep
+/// their markers and are skipped on the next attempt.
+#[tracing::instrument(
+    skip(server_url, staging),
+    fields(segments = plan.segments, since = plan.since.as_deref(), prefix_range = ?plan.prefix_range)
+)]
 async fn fetch_missing_segments(
     server_url: &http::Uri,
     staging: &Path,
     plan: &Plan,
+    parallel: u8,
 ) -> Result<(), Error> {
-    let segments = plan.segments;
     let client = Client::new(server_url)?;
 
-    for seg in 0..segments {
-        if staging.join(format!(".seg.{}.done", seg)).exists() {
-            continue;
-        }
-        fetch_segment_with_retry(&client, seg, segments, plan.since.as_deref(), staging).await?;
+    let missing =
+        (0..plan.segments).filter(|seg| !staging.join(format!(".seg.{}.done", seg)).exists());
+    let mut downloads = futures_util::stream::iter(missing)
+        .map(|seg| fetch_segment_with_retry(&client, seg, plan, staging))
+        .buffer_unordered(parallel as usize);
+
+    while let Some(res) = downloads.next().await {
+        res?;
     }
 
     Ok(())
 }
 
-#[tracing::instrument(skip(client, staging))]
+/// Downloads one segment into staging, retrying on failure.
+///
+/// After each prefix file is written its name is recorded in `.seg.N.progress`. Both a retry
+/// and a later run resume from that prefix instead of refetching the whole segment. Failures
+/// writing to staging are not retried.
+#[tracing::instrument(skip(client, plan, staging))]
 async fn fetch_segment_with_retry(
     client: &Client,
     segment: u8,
-    of: u8,
-    since: Option<&str>,
+    plan: &Plan,
     staging: &Path,
 ) -> Result<(), Error> {
     const MAX_RETRIES: u32 = 5;
     let mut delay = Duration::from_millis(500);
     let mut last_result = Ok(());
 
+    let progress_path = staging.join(format!(".seg.{}.progress", segment));
+    let mut after = read_progress(&progress_path).await?;
+    if let Some(prefix) = &after {
+        tracing::info!(after = %String::from_utf8_lossy(prefix), "resuming partially written segment");
+    }
+
     for attempt in 0..MAX_RETRIES {
         if attempt > 0 {
             tokio::time::sleep(delay).await;
             delay *= 2;
         }
-        match client.segment_stream(segment, of, since).await {
-            Ok(decoder) => {
-                let mut stream = Box::pin(decode_segment_stream(decoder));
-                while let Some(entry_res) = stream.next().await {
-                    let entry = entry_res?;
-                    let prefix_str = std::str::from_utf8(&entry.prefix)
-                        .map_err(|e| Error::Decode(format!("invalid prefix bytes: {e}")))?;
-                    fs::write(staging.join(format!("{}.bin", prefix_str)), &entry.content).await?;
-                }
+        match fetch_segment(client, segment, plan, staging, &progress_path, &mut after).await {
+            Ok(()) => {
                 fs::write(staging.join(format!(".seg.{}.done", segment)), b"").await?;
                 return Ok(());
             }
-            Err(e) => last_result = Err(e),
+            Err(e @ Error::Io(_)) => return Err(e),
+            Err(e) => {
+                tracing::warn!(error = %e, attempt, "segment download failed");
+                last_result = Err(e);
+            }
         }
     }
 
     last_result
 }
 
+async fn fetch_segment(
+    client: &Client,
+    segment: u8,
+    plan: &Plan,
+    staging: &Path,
+    progress_path: &Path,
+    after: &mut Option<[u8; 5]>,
+) -> Result<(), Error> {
+    let decoder = client
+        .segment_stream(
+            segment,
+            plan.segments,
+            plan.since.as_deref(),
+            plan.prefix_range.as_ref(),
+            after.as_ref(),
+        )
+        .await?;
+    let mut progress = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(progress_path)
+        .await?;
+
+    let mut stream = Box::pin(decode_segment_stream(decoder));
+    while let Some(entry_res) = stream.next().await {
+        let entry = entry_res?;
+        let prefix_str = std::str::from_utf8(&entry.prefix)
+            .map_err(|e| Error::Decode(format!("invalid prefix bytes: {e}")))?;
+        fs::write(staging.join(format!("{}.bin", prefix_str)), &entry.content).await?;
+
+        // Recorded only after the prefix file is fully written, so a crash can at worst
+        // cause that one file to be fetched again.
+        progress.rewind().await?;
+        progress.write_all(&entry.prefix).await?;
+        *after = Some(entry.prefix);
+    }
+    progress.flush().await?;
+
+    Ok(())
+}
+
+/// Reads the last fully written prefix of a segment, ignoring a missing or torn progress file.
+async fn read_progress(path: &Path) -> Result<Option<[u8; 5]>, Error> {
+    match fs::read(path).await {
+        Ok(bytes) => Ok(<[u8; 5]>::try_from(bytes.as_slice())
+            .ok()
+            .filter(|p| p.iter().all(u8::is_ascii_hexdigit))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[tracing::instrument(skip_all)]
-async fn finish_commit(staging: &Path, data_dir: &Path) -> Result<Outcome, Error> {
+async fn finish_commit(staging: &Path, config: &Config) -> Result<Outcome, Error> {
     let plan: Plan = serde_json::from_slice(&fs::read(staging.join(".sync-plan.json")).await?)?;
 
+    let target_dir = match config.keep_versions {
+        Some(_) => {
+            let dir = config.data_dir.join(VERSIONS_DIR).join(version_name(&plan));
+            fs::create_dir_all(&dir).await?;
+            if plan.since.is_some() {
+                link_unchanged(&config.data_dir.join(CURRENT_LINK), &dir).await?;
+            }
+            dir
+        }
+        None => config.data_dir.clone(),
+    };
+
+    let changed_prefixes = match plan.since {
+        Some(_) => Some(changed_prefixes(staging).await?),
+        None => None,
+    };
+
+    // Keep the previous filter in memory for a delta rebuild. In-place commits delete the
+    // live file first, even with the filter disabled, so it is never paired with prefix
+    // files it doesn't cover.
+    let previous_filter = match config.prefilter {
+        true => load_previous_filter(config, plan.since.as_deref()).await?,
+        false => None,
+    };
+    if config.keep_versions.is_none() {
+        remove_if_exists(&target_dir.join(PREFILTER_FILE)).await?;
+    }
+
     let mut entries = fs::read_dir(staging).await?;
     let mut file_count = 0usize;
     while let Some(entry) = entries.next_entry().await? {
         let src = entry.path();
         if src.extension().is_some_and(|e| e == "bin") {
-            fs::rename(&src, data_dir.join(src.file_name().unwrap())).await?;
+            fs::rename(&src, target_dir.join(src.file_name().unwrap())).await?;
             file_count += 1;
         }
     }
+    if let Some(changed) = &changed_prefixes {
+        // Files moved by an interrupted attempt are no longer in staging.
+        file_count = changed.len();
+    }
+    // A versioned full sync writes to a new directory, but an in-place one may follow a sync
+    // of a different range.
+    if config.keep_versions.is_none()
+        && plan.since.is_none()
+        && let Some(range) = &plan.prefix_range
+    {
+        remove_out_of_range(&target_dir, range).await?;
+    }
+
+    if config.prefilter {
+        let version = plan.server_last_updated.to_rfc3339_opts(SecondsFormat::Secs, true);
+        build_filter(
+            &target_dir,
+            previous_filter,
+            changed_prefixes.clone(),
+            version,
+        )
+        .await?;
+    }
 
-    let state_path = data_dir.join("sync-state.json");
-    let new_state = LocalState { last_updated: Some(plan.server_last_updated) };
+    let state_path = target_dir.join(STATE_FILE);
+    let new_state = LocalState {
+        last_updated: Some(plan.server_last_updated),
+        prefix_range: plan.prefix_range.clone(),
+    };
     let tmp = state_path.with_extension("json.tmp");
     fs::write(&tmp, serde_json::to_vec_pretty(&new_state)?).await?;
     fs::rename(&tmp, &state_path).await?;
 
+    if let Some(keep) = config.keep_versions {
+        let name = version_name(&plan);
+        publish_version(&config.data_dir, &name).await?;
+        prune_versions(&config.data_dir.join(VERSIONS_DIR), &name, keep).await?;
+    }
+
     clear_staging(staging).await?;
 
-    Ok(match plan.since {
+    let outcome = match plan.since {
         Some(_) => Outcome::DeltaSync { changed_count: file_count },
         None => Outcome::FullSync { file_count },
+    };
+    if let Some(on_commit) = &config.on_commit {
+        on_commit(&Commit {
+            outcome,
+            last_updated: plan.server_last_updated,
+            dataset_dir: config.dataset_dir(),
+            changed_prefixes,
+        });
+    }
+
+    Ok(outcome)
+}
+
+/// Returns the sorted prefixes of a delta sync, reading them from [`CHANGED_FILE`] if an
+/// earlier attempt recorded them and otherwise listing and recording the staged files.
+async fn changed_prefixes(staging: &Path) -> Result<Vec<u32>, Error> {
+    let path = staging.join(CHANGED_FILE);
+    match fs::read(&path).await {
+        Ok(bytes) => return Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut prefixes = Vec::new();
+    let mut entries = fs::read_dir(staging).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(prefix) = bin_prefix(&entry.path()) {
+            prefixes.push(prefix);
+        }
+    }
+    prefixes.sort_unstable();
+
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(&prefixes)?).await?;
+    fs::rename(&tmp, &path).await?;
+    Ok(prefixes)
+}
+
+/// Deletes the prefix files in `dir` outside `range`, left over from a sync of another range.
+async fn remove_out_of_range(dir: &Path, range: &RangeInclusive<u32>) -> Result<(), Error> {
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if bin_prefix(&path).is_some_and(|prefix| !range.contains(&prefix)) {
+            fs::remove_file(&path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Prefix of a `<prefix>.bin` file name, e.g. `0x0000A` for `0000A.bin`.
+fn bin_prefix(path: &Path) -> Option<u32> {
+    if path.extension().is_none_or(|e| e != "bin") {
+        return None;
+    }
+    u32::from_str_radix(path.file_stem()?.to_str()?, 16).ok()
+}
+
+/// Loads the live dataset's filter if it was built for `since`, the version a delta sync
+/// starts from. Anything else (no delta, missing, stale or unreadable) means a full rebuild.
+async fn load_previous_filter(
+    config: &Config,
+    since: Option<&str>,
+) -> Result<Option<Prefilter>, Error> {
+    let Some(since) = since else { return Ok(None) };
+    let live = config.dataset_dir();
+    let filter = tokio::task::spawn_blocking(move || Prefilter::load(&live))
+        .await
+        .map_err(|e| io::Error::other(format!("task panicked: {e}")))?;
+    match filter {
+        Ok(filter) if filter.dataset_version() == Some(since) => Ok(Some(filter)),
+        Ok(filter) => {
+            tracing::info!(
+                filter_version = ?filter.dataset_version(),
+                "prefilter does not match the live dataset; rebuilding in full"
+            );
+            Ok(None)
+        }
+        Err(hibp_verifier::Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => {
+            tracing::warn!(error = %e, "could not load previous prefilter; rebuilding in full");
+            Ok(None)
+        }
+    }
+}
+
+/// Writes `dir`'s filter, rebuilding only the changed shards when a previous filter is given.
+async fn build_filter(
+    dir: &Path,
+    previous: Option<Prefilter>,
+    changed_prefixes: Option<Vec<u32>>,
+    version: String,
+) -> Result<(), Error> {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<(), Error> {
+        let filter = match (previous, changed_prefixes) {
+            (Some(previous), Some(changed)) => {
+                tracing::info!(changed = changed.len(), "rebuilding prefilter shards");
+                previous.rebuild(&dir, &changed, Some(&version))?
+            }
+            _ => {
+                tracing::info!("building prefilter");
+                Prefilter::build(&dir, Some(&version))?
+            }
+        };
+        filter.write(&dir.join(PREFILTER_FILE))?;
+        tracing::info!(size_bytes = filter.size_bytes(), "prefilter written");
+        Ok(())
     })
+    .await
+    .map_err(|e| io::Error::other(format!("task panicked: {e}")))?
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Version directory the plan commits to.
+fn version_name(plan: &Plan) -> String {
+    match &plan.version {
+        Some(name) => name.clone(),
+        None => timestamp_name(plan.server_last_updated),
+    }
+}
+
+/// Version directory name derived from the server timestamp, e.g. `20260101T010000Z`.
+fn timestamp_name(last_updated: DateTime<Utc>) -> String {
+    last_updated.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Picks a version directory name that does not exist yet. A sync of a server version that
+/// is already committed, e.g. after the prefix range changed, gets a numbered suffix
+/// (`20260101T010000Z-001`) rather than writing into a directory `current` may point at.
+/// Names sort in the same order as the versions they hold.
+async fn new_version_name(
+    versions_dir: &Path,
+    last_updated: DateTime<Utc>,
+) -> Result<String, Error> {
+    let base = timestamp_name(last_updated);
+    let mut name = base.clone();
+    let mut attempt = 0u32;
+    while fs::try_exists(versions_dir.join(&name)).await? {
+        attempt += 1;
+        name = format!("{base}-{attempt:03}");
+    }
+    Ok(name)
+}
+
+/// Hard-links every prefix file of the live version into a new version directory, so a delta
+/// sync only has to write the changed files. Files already present are left alone: they were
+/// either linked or moved in from staging by an interrupted commit.
+async fn link_unchanged(current: &Path, dir: &Path) -> Result<(), Error> {
+    let mut entries = fs::read_dir(current).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let src = entry.path();
+        if src.extension().is_some_and(|e| e == "bin") {
+            match fs::hard_link(&src, dir.join(entry.file_name())).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Atomically repoints the `current` symlink at `versions/{name}`.
+async fn publish_version(data_dir: &Path, name: &str) -> Result<(), Error> {
+    let tmp = data_dir.join(".current.tmp");
+    match fs::remove_file(&tmp).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    fs::symlink(Path::new(VERSIONS_DIR).join(name), &tmp).await?;
+    fs::rename(&tmp, data_dir.join(CURRENT_LINK)).await?;
+    tracing::info!(version = name, "published dataset version");
+    Ok(())
+}
+
+/// Removes the oldest version directories until at most `keep` remain. The live version is
+/// never removed.
+async fn prune_versions(versions_dir: &Path, live: &str, keep: u8) -> Result<(), Error> {
+    let mut names = Vec::new();
+    let mut entries = fs::read_dir(versions_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if let Ok(name) = entry.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    names.sort_unstable();
+
+    let excess = names.len().saturating_sub(keep as usize);
+    for name in names.iter().filter(|n| *n != live).take(excess) {
+        tracing::info!(version = %name, "removing old dataset version");
+        fs::remove_dir_all(versions_dir.join(name)).await?;
+    }
+    Ok(())
 }
 
 async fn clear_staging(staging: &Path) -> Result<(), Error> {
@@ -214,6 +686,11 @@ mod tests {
             server_url: "http://127.0.0.1:8765".parse().unwrap(),
             data_dir: tmp.path().to_path_buf(),
             segments: 0,
+            parallel: 1,
+            keep_versions: None,
+            prefilter: false,
+            prefix_range: None,
+            on_commit: None,
         };
 
         match sync(&cfg).await {
@@ -222,4 +699,189 @@ mod tests {
             Ok(_) => panic!("expected error for zero segments"),
         }
     }
+
+    #[tokio::test]
+    async fn sync_rejects_zero_keep_versions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cfg = Config {
+            server_url: "http://127.0.0.1:8765".parse().unwrap(),
+            data_dir: tmp.path().to_path_buf(),
+            segments: 16,
+            parallel: 1,
+            keep_versions: Some(0),
+            prefilter: false,
+            prefix_range: None,
+            on_commit: None,
+        };
+
+        match sync(&cfg).await {
+            Err(Error::InvalidConfig(_)) => {}
+            Err(e) => panic!("expected InvalidConfig, got {e}"),
+            Ok(_) => panic!("expected error for zero keep_versions"),
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_rejects_eytzinger_dataset() {
+        let tmp = tempfile::tempdir().unwrap();
+        RecordLayout::Eytzinger.write(tmp.path()).unwrap();
+        let cfg = Config {
+            server_url: "http://127.0.0.1:8765".parse().unwrap(),
+            data_dir: tmp.path().to_path_buf(),
+            segments: 16,
+            parallel: 1,
+            keep_versions: None,
+            prefilter: false,
+            prefix_range: None,
+            on_commit: None,
+        };
+
+        match sync(&cfg).await {
+            Err(Error::InvalidConfig(_)) => {}
+            Err(e) => panic!("expected InvalidConfig, got {e}"),
+            Ok(_) => panic!("expected error for an Eytzinger dataset"),
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::reversed_empty_ranges)]
+    async fn sync_rejects_invalid_prefix_range() {
+        let tmp = tempfile::tempdir().unwrap();
+        for range in [0x00009..=0x00004, 0x00000..=0x100000] {
+            let cfg = Config {
+                server_url: "http://127.0.0.1:8765".parse().unwrap(),
+                data_dir: tmp.path().to_path_buf(),
+                segments: 16,
+                parallel: 1,
+                keep_versions: None,
+                prefilter: false,
+                prefix_range: Some(range.clone()),
+                on_commit: None,
+            };
+
+            match sync(&cfg).await {
+                Err(Error::InvalidConfig(_)) => {}
+                Err(e) => panic!("expected InvalidConfig for {range:?}, got {e}"),
+                Ok(_) => panic!("expected error for prefix range {range:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn prune_versions_keeps_newest_and_live() {
+        let tmp = tempfile::tempdir().unwrap();
+        let names = ["20260101T000000Z", "20260102T000000Z", "20260103T000000Z"];
+        for name in names {
+            std::fs::create_dir_all(tmp.path().join(name)).unwrap();
+        }
+
+        // Live version is the oldest one, e.g. after a manual rollback.
+        prune_versions(tmp.path(), names[0], 2).await.unwrap();
+        assert!(tmp.path().join(names[0]).exists());
+        assert!(!tmp.path().join(names[1]).exists());
+        assert!(tmp.path().join(names[2]).exists());
+
+        prune_versions(tmp.path(), names[2], 1).await.unwrap();
+        assert!(!tmp.path().join(names[0]).exists());
+        assert!(tmp.path().join(names[2]).exists());
+    }
+
+    // versions/ holds T1 and T1-001 → a new version of T1 gets -002, a new timestamp none.
+    #[tokio::test]
+    async fn new_version_name_skips_existing_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let t1 = DateTime::parse_from_rfc3339("2026-01-01T01:00:00Z").unwrap().to_utc();
+        assert_eq!(
+            new_version_name(tmp.path(), t1).await.unwrap(),
+            "20260101T010000Z"
+        );
+
+        std::fs::create_dir(tmp.path().join("20260101T010000Z")).unwrap();
+        std::fs::create_dir(tmp.path().join("20260101T010000Z-001")).unwrap();
+        assert_eq!(
+            new_version_name(tmp.path(), t1).await.unwrap(),
+            "20260101T010000Z-002"
+        );
+
+        let t2 = t1 + chrono::TimeDelta::hours(1);
+        assert_eq!(
+            new_version_name(tmp.path(), t2).await.unwrap(),
+            "20260101T020000Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn read_progress_ignores_missing_and_torn_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(".seg.0.progress");
+
+        assert_eq!(read_progress(&path).await.unwrap(), None);
+
+        std::fs::write(&path, b"0000").unwrap();
+        assert_eq!(read_progress(&path).await.unwrap(), None);
+
+        std::fs::write(&path, b"0000Z").unwrap();
+        assert_eq!(read_progress(&path).await.unwrap(), None);
+
+        std::fs::write(&path, b"0000A").unwrap();
+        assert_eq!(read_progress(&path).await.unwrap(), Some(*b"0000A"));
+    }
+
+    // Commit interrupted after moving one of two staged files → the resumed commit still
+    // reports both prefixes.
+    #[tokio::test]
+    async fn changed_prefixes_survive_interrupted_commit() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("0000A.bin"), b"").unwrap();
+        std::fs::write(tmp.path().join("00003.bin"), b"").unwrap();
+        assert_eq!(changed_prefixes(tmp.path()).await.unwrap(), [0x3, 0xA]);
+
+        std::fs::remove_file(tmp.path().join("0000A.bin")).unwrap();
+        assert_eq!(changed_prefixes(tmp.path()).await.unwrap(), [0x3, 0xA]);
+    }
+
+    // Files from a wider earlier range → only those outside the new range are deleted; other
+    // files in the directory are kept.
+    #[tokio::test]
+    async fn remove_out_of_range_keeps_range_and_other_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in [
+            "00003.bin",
+            "00004.bin",
+            "00009.bin",
+            "0000A.bin",
+            "sync-state.json",
+        ] {
+            std::fs::write(tmp.path().join(name), b"").unwrap();
+        }
+
+        remove_out_of_range(tmp.path(), &(0x4..=0x9)).await.unwrap();
+        let mut left: Vec<_> = std::fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["00004.bin", "00009.bin", "sync-state.json"]);
+    }
+
+    #[tokio::test]
+    async fn sync_rejects_zero_parallel() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cfg = Config {
+            server_url: "http://127.0.0.1:8765".parse().unwrap(),
+            data_dir: tmp.path().to_path_buf(),
+            segments: 16,
+            parallel: 0,
+            keep_versions: None,
+            prefilter: false,
+            prefix_range: None,
+            on_commit: None,
+        };
+
+        match sync(&cfg).await {
+            Err(Error::InvalidConfig(_)) => {}
+            Err(e) => panic!("expected InvalidConfig, got {e}"),
+            Ok(_) => panic!("expected error for zero parallel"),
+        }
+    }
 }
@@ -1,8 +1,12 @@
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use bytes::Bytes;
 use chrono::{DateTime, TimeDelta, Utc};
-use hibp_sync_client::sync::{Config, Outcome, sync};
+use hibp_sync_client::daemon::{Daemon, DaemonConfig, serve_health};
+use hibp_sync_client::sync::{Commit, Config, Outcome, sync};
+use hibp_verifier::prefilter::Prefilter;
 use http::Uri;
 use http_body_util::Empty;
 use hyper_util::client::legacy::Client;
@@ -55,6 +59,20 @@ fn fake_bin(prefix: u32, version: u32) -> Vec<u8> {
     records.iter().flatten().copied().collect()
 }
 
+// Whether the filter in `dir` may contain every record of `content`, a prefix file body.
+fn filter_covers(dir: &Path, prefix: u32, content: &[u8]) -> bool {
+    let filter = Prefilter::load(dir).unwrap();
+    content.chunks(6).all(|record| {
+        // A hash whose first 8 bytes are the record's sha1t64.
+        let mut hash = [0u8; 20];
+        hash[0] = (prefix >> 12) as u8;
+        hash[1] = (prefix >> 4) as u8;
+        hash[2] = (prefix as u8) << 4 | (record[0] & 0x0F);
+        hash[3..8].copy_from_slice(&record[1..]);
+        filter.may_contain(&hash)
+    })
+}
+
 fn prepare_dirs(base: &Path) {
     std::fs::create_dir_all(base.join("data")).unwrap();
     std::fs::create_dir_all(base.join("digests")).unwrap();
@@ -170,7 +188,16 @@ async fn start_server(base: &Path) -> Uri {
 }
 
 fn sync_cfg(server_url: Uri, data_dir: &Path, segments: u8) -> Config {
-    Config { server_url, data_dir: data_dir.to_path_buf(), segments }
+    Config {
+        server_url,
+        data_dir: data_dir.to_path_buf(),
+        segments,
+        parallel: 1,
+        keep_versions: None,
+        prefilter: false,
+        prefix_range: None,
+        on_commit: None,
+    }
 }
 
 // Server has no state.json → status returns last_updated:null → UpToDate.
@@ -250,8 +277,20 @@ async fn delta_sync_partial_update() {
     write_client_state(cli.path(), ts(T1));
 
     let url = start_server(srv.path()).await;
-    let outcome = sync(&sync_cfg(url, cli.path(), 1)).await.unwrap();
+    let commits: Arc<Mutex<Vec<Commit>>> = Arc::default();
+    let seen = commits.clone();
+    let config = Config {
+        on_commit: Some(Arc::new(move |c: &Commit| {
+            seen.lock().unwrap().push(c.clone())
+        })),
+        ..sync_cfg(url, cli.path(), 1)
+    };
+    let outcome = sync(&config).await.unwrap();
     assert!(matches!(outcome, Outcome::DeltaSync { changed_count: 4 }));
+    assert_eq!(
+        commits.lock().unwrap()[0].changed_prefixes.as_deref(),
+        Some(changed)
+    );
 
     for &p in changed {
         let content = std::fs::read(cli.path().join(format!("{}.bin", hex_prefix(p)))).unwrap();
@@ -300,6 +339,124 @@ async fn client_crash_recovery_interrupted_commit() {
     assert_eq!(saved_ts, ts(T1));
 }
 
+// Client at T1 with a filter built for T1, server at T2 with 4 changed prefixes → the
+// filter is rebuilt for T2 and covers the new records.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn prefilter_rebuilt_on_delta_sync() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    let changed: &[u32] = &[0x00000, 0x00005, 0x0000A, 0x0000F];
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_bins(&srv.path().join("data"), changed, 2);
+    write_server_state(srv.path(), ts(T2));
+    write_changed(srv.path(), Some(ts(T1)), changed);
+
+    write_bins(cli.path(), PREFIXES, 1);
+    write_client_state(cli.path(), ts(T1));
+    let filter = Prefilter::build(cli.path(), Some(T1)).unwrap();
+    filter.write(&cli.path().join("prefilter.fuse")).unwrap();
+
+    let url = start_server(srv.path()).await;
+    let config = Config { prefilter: true, ..sync_cfg(url, cli.path(), 1) };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::DeltaSync { changed_count: 4 }));
+
+    assert_eq!(
+        Prefilter::load(cli.path()).unwrap().dataset_version(),
+        Some(T2)
+    );
+    for &p in PREFIXES {
+        let version = if changed.contains(&p) { 2 } else { 1 };
+        assert!(filter_covers(cli.path(), p, &fake_bin(p, version)));
+    }
+}
+
+// Delta commit interrupted after 2 of its 4 files were moved → the recorded prefix list
+// still reports all 4 and the filter covers every one of them.
+#[tokio::test]
+async fn prefilter_resumed_delta_commit() {
+    let cli = tempfile::tempdir().unwrap();
+    let staging = cli.path().join(".staging");
+    std::fs::create_dir_all(&staging).unwrap();
+
+    let changed: &[u32] = &[0x00000, 0x00005, 0x0000A, 0x0000F];
+    write_bins(cli.path(), PREFIXES, 1);
+    write_client_state(cli.path(), ts(T1));
+
+    write_sync_plan(&staging, ts(T2), Some(T1), 1);
+    std::fs::write(
+        staging.join(".changed-prefixes.json"),
+        serde_json::to_vec(changed).unwrap(),
+    )
+    .unwrap();
+    write_bins(cli.path(), &changed[..2], 2);
+    write_bins(&staging, &changed[2..], 2);
+    std::fs::write(staging.join(".complete"), b"").unwrap();
+
+    let commits: Arc<Mutex<Vec<Commit>>> = Arc::default();
+    let seen = commits.clone();
+    let config = Config {
+        prefilter: true,
+        on_commit: Some(Arc::new(move |c: &Commit| {
+            seen.lock().unwrap().push(c.clone())
+        })),
+        ..sync_cfg("http://127.0.0.1:1".parse().unwrap(), cli.path(), 1)
+    };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::DeltaSync { changed_count: 4 }));
+    assert_eq!(
+        commits.lock().unwrap()[0].changed_prefixes.as_deref(),
+        Some(changed)
+    );
+
+    assert_eq!(
+        Prefilter::load(cli.path()).unwrap().dataset_version(),
+        Some(T2)
+    );
+    for &p in changed {
+        assert!(filter_covers(cli.path(), p, &fake_bin(p, 2)));
+    }
+}
+
+// A commit invokes on_commit once with the outcome, new timestamp and dataset dir.
+// An up-to-date poll afterwards does not invoke it again.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn on_commit_called_after_commit() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+    write_changed(srv.path(), None, PREFIXES);
+
+    let url = start_server(srv.path()).await;
+    let commits: Arc<Mutex<Vec<Commit>>> = Arc::default();
+    let seen = commits.clone();
+    let config = Config {
+        on_commit: Some(Arc::new(move |c: &Commit| {
+            seen.lock().unwrap().push(c.clone())
+        })),
+        ..sync_cfg(url, cli.path(), 2)
+    };
+
+    assert!(matches!(
+        sync(&config).await.unwrap(),
+        Outcome::FullSync { file_count: 16 }
+    ));
+    assert!(matches!(sync(&config).await.unwrap(), Outcome::UpToDate));
+
+    let commits = commits.lock().unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0].outcome, Outcome::FullSync { file_count: 16 });
+    assert_eq!(commits[0].last_updated, ts(T1));
+    assert_eq!(commits[0].dataset_dir, cli.path());
+    assert_eq!(commits[0].changed_prefixes, None);
+}
+
 // Client staging has partial delta download (segment 0 done, segment 1 missing).
 // sync() re-fetches only segment 1 then commits all 16 files.
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -332,6 +489,119 @@ async fn client_crash_recovery_partial_download() {
     }
 }
 
+// Segment 0 was interrupted after writing prefix 00007 (recorded in .seg.0.progress).
+// sync() asks the server only for prefixes after 00007; earlier staged files are kept as-is.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn client_resume_within_segment() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+
+    // Staged files use version 2 content so we can tell they were not refetched.
+    let staging = cli.path().join(".staging");
+    std::fs::create_dir_all(&staging).unwrap();
+    write_sync_plan(&staging, ts(T1), None, 1);
+    write_bins(&staging, &PREFIXES[..8], 2);
+    std::fs::write(staging.join(".seg.0.progress"), b"00007").unwrap();
+
+    let outcome = sync(&sync_cfg(url, cli.path(), 1)).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+
+    for &p in &PREFIXES[..8] {
+        let content = std::fs::read(cli.path().join(format!("{}.bin", hex_prefix(p)))).unwrap();
+        assert_eq!(content, fake_bin(p, 2), "prefix {p:#07X} was refetched");
+    }
+    for &p in &PREFIXES[8..] {
+        let content = std::fs::read(cli.path().join(format!("{}.bin", hex_prefix(p)))).unwrap();
+        assert_eq!(content, fake_bin(p, 1), "prefix {p:#07X} was not fetched");
+    }
+}
+
+// GET /v1/segment with a malformed after prefix → 400.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn segment_400_when_after_invalid() {
+    let srv = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let status = http_get_status(&format!("{}v1/segment?segment=0&of=1&after=XYZ", url)).await;
+    assert_eq!(status, 400);
+}
+
+// GET /v1/segment with start after end → 400.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn segment_400_when_prefix_range_invalid() {
+    let srv = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let status = http_get_status(&format!(
+        "{}v1/segment?segment=0&of=1&start=00009&end=00004",
+        url
+    ))
+    .await;
+    assert_eq!(status, 400);
+}
+
+// Client syncs prefixes 4-9 only → full sync writes those 6 files, a delta touching 2, 5 and C
+// writes just 5, widening the range forces a full sync of everything, and narrowing it again
+// deletes the files outside it.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sharded_sync_stays_in_range() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let config = Config { prefix_range: Some(0x00004..=0x00009), ..sync_cfg(url, cli.path(), 4) };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 6 }));
+    for &p in PREFIXES {
+        let path = cli.path().join(format!("{}.bin", hex_prefix(p)));
+        assert_eq!(path.exists(), (4..=9).contains(&p), "prefix {p:#07X}");
+    }
+
+    let changed = [0x00002, 0x00005, 0x0000C];
+    write_bins(&srv.path().join("data"), &changed, 2);
+    write_server_state(srv.path(), ts(T2));
+    write_changed(srv.path(), Some(ts(T1)), &changed);
+    let config = Config { server_url: start_server(srv.path()).await, ..config };
+
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::DeltaSync { changed_count: 1 }));
+    let content = std::fs::read(cli.path().join("00005.bin")).unwrap();
+    assert_eq!(content, fake_bin(0x00005, 2));
+    assert!(!cli.path().join("00002.bin").exists());
+
+    // Same server version, new range → not up to date, since the local data covers another
+    // range.
+    let config = Config { prefix_range: None, ..config };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+
+    let config = Config { prefix_range: Some(0x00008..=0x0000B), ..config };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 4 }));
+    for &p in PREFIXES {
+        let path = cli.path().join(format!("{}.bin", hex_prefix(p)));
+        assert_eq!(path.exists(), (8..=0xB).contains(&p), "prefix {p:#07X}");
+    }
+}
+
 // Server staging has .bin files but no .complete -> recovery discards staging, data stays empty.
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn server_recovery_no_complete_marker() {
@@ -445,6 +715,27 @@ async fn full_sync_from_scratch() {
     assert_eq!(saved_ts, ts(T1));
 }
 
+// No sync-state.json, 4 segments fetched 4 at a time → FullSync{16} with every file intact.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn full_sync_parallel_segments() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let config = Config { parallel: 4, ..sync_cfg(url, cli.path(), 4) };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+
+    for &p in PREFIXES {
+        let content = std::fs::read(cli.path().join(format!("{}.bin", hex_prefix(p)))).unwrap();
+        assert_eq!(content, fake_bin(p, 1));
+    }
+}
+
 // Client is two cycles behind (T0, server moved T1→T2) → changed.prev(T1) != local(T0) → FullSync.
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn delta_fallback_to_full() {
@@ -562,3 +853,196 @@ async fn delta_sync_with_more_segments_than_changes() {
         );
     }
 }
+
+// Sets up a server at T2 where 4 of 16 prefixes changed since T1, for a client that already
+// synced T1 in versioned mode.
+async fn versioned_client_at_t1(srv: &Path, cli: &Path, keep: u8) -> Config {
+    prepare_dirs(srv);
+    write_bins(&srv.join("data"), PREFIXES, 1);
+    write_server_state(srv, ts(T1));
+
+    let url = start_server(srv).await;
+    let config = Config { keep_versions: Some(keep), ..sync_cfg(url, cli, 1) };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+
+    for &p in &[0x00000, 0x00005, 0x0000A, 0x0000F] {
+        std::fs::write(
+            srv.join("data").join(format!("{}.bin", hex_prefix(p))),
+            fake_bin(p, 2),
+        )
+        .unwrap();
+    }
+    write_server_state(srv, ts(T2));
+    write_changed(srv, Some(ts(T1)), &[0x00000, 0x00005, 0x0000A, 0x0000F]);
+
+    // The running server loaded its state at startup; start a fresh one for T2.
+    Config { server_url: start_server(srv).await, ..config }
+}
+
+// Versioned delta sync builds a new version next to the old one and flips `current` to it.
+// Unchanged files are shared via hard links; the previous version keeps its old contents.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn versioned_delta_sync_flips_current() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+    let config = versioned_client_at_t1(srv.path(), cli.path(), 2).await;
+
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::DeltaSync { changed_count: 4 }));
+
+    let current = std::fs::read_link(cli.path().join("current")).unwrap();
+    assert_eq!(current, Path::new("versions/20260101T020000Z"));
+
+    let changed: &[u32] = &[0x00000, 0x00005, 0x0000A, 0x0000F];
+    let old = cli.path().join("versions/20260101T010000Z");
+    for &p in PREFIXES {
+        let name = format!("{}.bin", hex_prefix(p));
+        let version = if changed.contains(&p) { 2 } else { 1 };
+        let content = std::fs::read(config.dataset_dir().join(&name)).unwrap();
+        assert_eq!(content, fake_bin(p, version), "prefix {p:#07X} in current");
+        let content = std::fs::read(old.join(&name)).unwrap();
+        assert_eq!(
+            content,
+            fake_bin(p, 1),
+            "prefix {p:#07X} in previous version"
+        );
+    }
+
+    let saved: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(config.dataset_dir().join("sync-state.json")).unwrap(),
+    )
+    .unwrap();
+    let saved_ts: DateTime<Utc> = serde_json::from_value(saved["last_updated"].clone()).unwrap();
+    assert_eq!(saved_ts, ts(T2));
+}
+
+// With keep_versions=1 the previous version is removed once the new one is live.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn versioned_sync_prunes_old_versions() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+    let config = versioned_client_at_t1(srv.path(), cli.path(), 1).await;
+
+    sync(&config).await.unwrap();
+
+    let versions: Vec<_> = std::fs::read_dir(cli.path().join("versions"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(versions, ["20260101T020000Z"]);
+    assert_eq!(
+        std::fs::read(config.dataset_dir().join("00001.bin")).unwrap(),
+        fake_bin(0x00001, 1)
+    );
+}
+
+// Versioned client at T1 changes its shard without a new server version → the full sync goes
+// to a new directory instead of the live one, which a reader keeps seeing unchanged until
+// `current` flips. The new version holds only the new range.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn versioned_shard_change_uses_new_directory() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let config = Config { keep_versions: Some(2), ..sync_cfg(url, cli.path(), 1) };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+    let live = cli.path().join("versions/20260101T010000Z");
+
+    let config = Config { prefix_range: Some(0x00008..=0x0000B), ..config };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 4 }));
+
+    let current = std::fs::read_link(cli.path().join("current")).unwrap();
+    assert_eq!(current, Path::new("versions/20260101T010000Z-001"));
+    for &p in PREFIXES {
+        let name = format!("{}.bin", hex_prefix(p));
+        assert!(
+            live.join(&name).exists(),
+            "prefix {p:#07X} in previous version"
+        );
+        assert_eq!(
+            config.dataset_dir().join(&name).exists(),
+            (8..=0xB).contains(&p),
+            "prefix {p:#07X} in current"
+        );
+    }
+
+    // Back to the full range → yet another directory, pruning the oldest.
+    let config = Config { prefix_range: None, ..config };
+    let outcome = sync(&config).await.unwrap();
+    assert!(matches!(outcome, Outcome::FullSync { file_count: 16 }));
+    let current = std::fs::read_link(cli.path().join("current")).unwrap();
+    assert_eq!(current, Path::new("versions/20260101T010000Z-002"));
+    assert!(!live.exists());
+}
+
+// Daemon poll syncs when the server is ahead, then reports zero lag and stays idle.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn daemon_poll_syncs_and_reports_health() {
+    let srv = tempfile::tempdir().unwrap();
+    let cli = tempfile::tempdir().unwrap();
+
+    prepare_dirs(srv.path());
+    write_bins(&srv.path().join("data"), PREFIXES, 1);
+    write_server_state(srv.path(), ts(T1));
+
+    let url = start_server(srv.path()).await;
+    let schedule = DaemonConfig {
+        poll_interval: Duration::from_secs(60),
+        jitter: Duration::ZERO,
+        max_backoff: Duration::from_secs(600),
+    };
+    let daemon = Daemon::new(sync_cfg(url, cli.path(), 1), schedule).unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let health_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(serve_health(listener, daemon.stats()));
+
+    // Nothing polled yet → unhealthy.
+    assert_eq!(http_get_status(&format!("{health_url}/healthz")).await, 503);
+
+    let outcome = daemon.poll_once().await.unwrap();
+    assert!(matches!(
+        outcome,
+        Some(Outcome::FullSync { file_count: 16 })
+    ));
+    let synced = daemon.stats().read().unwrap().last_sync;
+    assert!(synced.is_some());
+    assert!(daemon.poll_once().await.unwrap().is_none());
+
+    // The no-op poll counts as a poll but not as a sync.
+    let stats = daemon.stats().read().unwrap().clone();
+    assert_eq!(stats.last_sync, synced);
+    assert!(stats.last_poll >= synced);
+    assert_eq!(stats.local_last_updated, Some(ts(T1)));
+    assert_eq!(stats.lag(), Some(TimeDelta::zero()));
+    assert_eq!(stats.syncs_total, 1);
+
+    assert_eq!(http_get_status(&format!("{health_url}/healthz")).await, 200);
+    assert_eq!(http_get_status(&format!("{health_url}/metrics")).await, 200);
+}
+
+// A poll against an unreachable server counts as a failure and marks the daemon unhealthy.
+#[tokio::test]
+async fn daemon_poll_failure_is_counted() {
+    let cli = tempfile::tempdir().unwrap();
+    let schedule = DaemonConfig {
+        poll_interval: Duration::from_secs(60),
+        jitter: Duration::ZERO,
+        max_backoff: Duration::from_secs(600),
+    };
+    let config = sync_cfg("http://127.0.0.1:1".parse().unwrap(), cli.path(), 1);
+    let daemon = Daemon::new(config, schedule).unwrap();
+
+    assert!(daemon.poll_once().await.is_err());
+    let stats = daemon.stats().read().unwrap().clone();
+    assert_eq!(stats.consecutive_failures, 1);
+    assert!(!stats.is_healthy());
+}
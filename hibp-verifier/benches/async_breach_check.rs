@@ -112,6 +112,37 @@ fn bench_sync_concurrency(c: &mut Criterion) {
     group.finish();
 }
 
+// Benchmark: High concurrency comparison (10k concurrent lookups) - PooledChecker, driven by
+// futures' single-threaded executor to show it needs no runtime of its own
+fn bench_pool_concurrency(c: &mut Criterion) {
+    use common::generate_random_passwords;
+    use criterion::{BatchSize, black_box};
+    use futures::executor::block_on;
+    use futures::future::join_all;
+    use hibp_verifier::pool::PooledChecker;
+    use hibp_verifier::{OwnedBreachChecker, dataset_path_from_env};
+
+    let checker = OwnedBreachChecker::open(dataset_path_from_env()).unwrap();
+    let checker = PooledChecker::new(checker, 24).unwrap();
+    let passwords = generate_random_passwords(10000);
+
+    let mut group = c.benchmark_group("concurrent_10k");
+
+    group.bench_function("pooled_checker", |b| {
+        b.iter_batched(
+            || passwords.clone(),
+            |passwords| {
+                let futs: Vec<_> =
+                    passwords.iter().map(|password| checker.is_breached(password)).collect();
+                black_box(block_on(join_all(futs)))
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
 // Benchmark: High concurrency comparison (10k concurrent lookups) - tokio
 #[cfg(feature = "tokio")]
 fn bench_tokio_concurrency(c: &mut Criterion) {
@@ -238,10 +269,52 @@ fn bench_compio_concurrency(c: &mut Criterion) {
 #[cfg(not(feature = "compio"))]
 fn bench_compio_concurrency(_c: &mut Criterion) {}
 
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+fn bench_uring_concurrency(c: &mut Criterion) {
+    use common::generate_random_passwords;
+    use criterion::{BatchSize, black_box};
+    use hibp_verifier::uring::{UringChecker, UringConfig};
+    use hibp_verifier::{OwnedBreachChecker, dataset_path_from_env};
+
+    const THREADS: usize = 24;
+
+    let owned = OwnedBreachChecker::open(dataset_path_from_env()).unwrap();
+    let mut checkers: Vec<_> = (0..THREADS)
+        .map(|_| UringChecker::new(owned.clone(), UringConfig::default()).unwrap())
+        .collect();
+    let passwords = generate_random_passwords(10000);
+
+    let mut group = c.benchmark_group("concurrent_10k");
+
+    // One ring per thread, each checking its share of the 10k passwords as a single batch.
+    group.bench_function("uring_batch", |b| {
+        b.iter_batched(
+            || passwords.clone(),
+            |passwords| {
+                std::thread::scope(|s| {
+                    for (checker, batch) in
+                        checkers.iter_mut().zip(passwords.chunks(passwords.len().div_ceil(THREADS)))
+                    {
+                        s.spawn(move || black_box(checker.is_breached_batch(batch).unwrap()));
+                    }
+                });
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+fn bench_uring_concurrency(_c: &mut Criterion) {}
+
 criterion_group!(
     async_benches,
     bench_sync_concurrency,
+    bench_pool_concurrency,
     bench_tokio_concurrency,
-    bench_compio_concurrency
+    bench_compio_concurrency,
+    bench_uring_concurrency
 );
 criterion_main!(async_benches);
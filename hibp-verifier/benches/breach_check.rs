@@ -4,7 +4,7 @@ use std::time::{Duration, Instant};
 
 use common::generate_random_passwords;
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use hibp_verifier::{BreachChecker, dataset_path_from_env};
+use hibp_verifier::{BreachChecker, OwnedBreachChecker, dataset_path_from_env};
 
 // 20 commonly used passwords (guaranteed to be in breaches)
 const COMMON_PASSWORDS: &[&str] = &[
@@ -93,6 +93,19 @@ fn bench_mixed_passwords(c: &mut Criterion) {
     });
 }
 
+/// Same workload as mixed_passwords_40 through the owned checker's openat lookups.
+fn bench_mixed_passwords_owned(c: &mut Criterion) {
+    let checker = OwnedBreachChecker::open(dataset_path_from_env()).unwrap();
+
+    c.bench_function("mixed_passwords_40_owned", |b| {
+        b.iter(|| {
+            for password in COMMON_PASSWORDS.iter().chain(RANDOM_PASSWORDS.iter()) {
+                black_box(checker.is_breached(black_box(password)).unwrap());
+            }
+        })
+    });
+}
+
 /// Cold page benchmark
 /// Tests performance with 100k random passwords to maximize cold page misses
 fn bench_cold_pages(c: &mut Criterion) {
@@ -129,6 +142,7 @@ criterion_group!(
     bench_common_passwords,
     bench_random_passwords,
     bench_mixed_passwords,
+    bench_mixed_passwords_owned,
     bench_cold_pages,
 );
 criterion_main!(benches);
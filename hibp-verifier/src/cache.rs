@@ -0,0 +1,281 @@
+//! Bounded in-memory cache of hot prefix files.
+//!
+//! Login traffic is skewed toward common passwords, which live in a small set of prefixes. A
+//! [`PrefixCache`] keeps the records of recently read prefix files in memory up to a byte
+//! budget, evicting the least recently used file when full. A checker given a cache searches
+//! cached records instead of opening the file, and adds the file on a miss.
+//!
+//! A cache must only hold one dataset. Each checker is bound to the cache's generation when
+//! the cache is attached, and [`PrefixCache::invalidate`] empties the cache and starts a new
+//! generation. Checkers bound to an older one read from disk without touching the cache, so a
+//! lookup still running against the previous dataset cannot add its records to the new one.
+//! `reload::ReloadingChecker::with_cache` (`reload` feature) invalidates on every new
+//! dataset version.
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//!
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::cache::PrefixCache;
+//!
+//! let cache = Arc::new(PrefixCache::new(64 << 20));
+//! let checker = OwnedBreachChecker::open("/path/to/hibp-data")?.with_cache(cache.clone());
+//! checker.is_breached("password123")?;
+//! println!("{:?}", cache.stats());
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::RECORD_SIZE;
+
+/// Bytes charged per cached prefix file on top of its records, for the map entries around
+/// them. Keeps a cache of empty files bounded.
+pub const ENTRY_OVERHEAD: usize = 64;
+
+/// Records of one prefix file, shared between the cache and lookups in progress.
+pub(crate) type Records = Arc<[[u8; RECORD_SIZE]]>;
+
+/// A thread-safe LRU cache of prefix file records, bounded in bytes.
+///
+/// Share it between checkers and threads with an `Arc`.
+pub struct PrefixCache {
+    capacity: usize,
+    state: Mutex<State>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    generation: u64,
+    entries: HashMap<u32, Entry>,
+    /// Cached prefixes keyed by the tick of their last use, least recent first.
+    recency: BTreeMap<u64, u32>,
+    tick: u64,
+    bytes: usize,
+    evictions: u64,
+}
+
+struct Entry {
+    records: Records,
+    last_used: u64,
+}
+
+/// Counters and occupancy of a [`PrefixCache`]. Counters are cumulative across
+/// invalidations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups that read the prefix file, including those from checkers bound to an older
+    /// generation.
+    pub misses: u64,
+    /// Prefix files dropped to make room for others.
+    pub evictions: u64,
+    /// Prefix files currently cached.
+    pub entries: usize,
+    /// Bytes currently charged, records plus [`ENTRY_OVERHEAD`] per file.
+    pub bytes: usize,
+    /// Current generation, incremented by each [`PrefixCache::invalidate`].
+    pub generation: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups answered from the cache, or 0 before any lookup.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl PrefixCache {
+    /// Creates an empty cache holding at most `capacity` bytes. Prefix files are at most
+    /// 16 KiB, so a few MiB already covers the most common prefixes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The byte budget the cache was created with.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The generation checkers attached now are bound to.
+    pub fn generation(&self) -> u64 {
+        self.state().generation
+    }
+
+    /// Drops every cached file and starts a new generation, returning it. Call this when the
+    /// dataset changes, then attach the cache to checkers for the new dataset.
+    pub fn invalidate(&self) -> u64 {
+        let mut state = self.state();
+        let generation = state.generation + 1;
+        *state = State { generation, evictions: state.evictions, ..State::default() };
+        generation
+    }
+
+    /// Current counters and occupancy.
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: state.evictions,
+            entries: state.entries.len(),
+            bytes: state.bytes,
+            generation: state.generation,
+        }
+    }
+
+    /// The cached records for `prefix`, marking them as most recently used. Counts a hit or
+    /// a miss.
+    pub(crate) fn get(&self, generation: u64, prefix: u32) -> Option<Records> {
+        let mut state = self.state();
+        let records = match state.generation == generation {
+            true => state.touch(prefix),
+            false => None,
+        };
+        drop(state);
+        match records {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        records
+    }
+
+    /// Caches `records` for `prefix`, evicting least recently used files to make room. Does
+    /// nothing if `generation` is stale or the file alone exceeds the capacity.
+    pub(crate) fn insert(&self, generation: u64, prefix: u32, records: &[[u8; RECORD_SIZE]]) {
+        let cost = charge(records.len());
+        if cost > self.capacity {
+            return;
+        }
+        let mut state = self.state();
+        if state.generation != generation {
+            return;
+        }
+        // Another thread may have missed on the same prefix and inserted it first.
+        state.remove(prefix);
+        while state.bytes + cost > self.capacity {
+            let (_, oldest) = state.recency.pop_first().expect("bytes are only charged to entries");
+            let entry = state.entries.remove(&oldest).expect("recency mirrors entries");
+            state.bytes -= charge(entry.records.len());
+            state.evictions += 1;
+        }
+        let last_used = state.next_tick();
+        state.recency.insert(last_used, prefix);
+        state.entries.insert(prefix, Entry { records: records.into(), last_used });
+        state.bytes += cost;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Every update leaves the state consistent before anything that could panic.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn touch(&mut self, prefix: u32) -> Option<Records> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(&prefix)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(tick, prefix);
+        entry.last_used = tick;
+        Some(entry.records.clone())
+    }
+
+    fn remove(&mut self, prefix: u32) {
+        if let Some(entry) = self.entries.remove(&prefix) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= charge(entry.records.len());
+        }
+    }
+}
+
+impl fmt::Debug for PrefixCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixCache")
+            .field("capacity", &self.capacity)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Bytes charged for a prefix file of `records` records.
+fn charge(records: usize) -> usize {
+    records * RECORD_SIZE + ENTRY_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(n: usize) -> Vec<[u8; RECORD_SIZE]> {
+        (0..n).map(|i| [0, 0, 0, 0, 0, i as u8]).collect()
+    }
+
+    // Room for two 10-record files; touching A before inserting C → B is evicted.
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = PrefixCache::new(2 * charge(10));
+        let generation = cache.generation();
+        cache.insert(generation, 0xA, &records(10));
+        cache.insert(generation, 0xB, &records(10));
+        assert!(cache.get(generation, 0xA).is_some());
+
+        cache.insert(generation, 0xC, &records(10));
+        assert!(cache.get(generation, 0xB).is_none());
+        assert_eq!(cache.get(generation, 0xA).unwrap().len(), 10);
+        assert!(cache.get(generation, 0xC).is_some());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (3, 1, 1));
+        assert_eq!((stats.entries, stats.bytes), (2, 2 * charge(10)));
+        assert_eq!(stats.hit_rate(), 0.75);
+    }
+
+    // Re-inserting a cached prefix replaces it; a file larger than the whole cache is
+    // never cached.
+    #[test]
+    fn accounts_bytes_on_replace_and_oversize() {
+        let cache = PrefixCache::new(charge(10));
+        cache.insert(0, 0xA, &records(4));
+        cache.insert(0, 0xA, &records(10));
+        assert_eq!(cache.stats().bytes, charge(10));
+        assert_eq!(cache.stats().evictions, 0);
+
+        cache.insert(0, 0xB, &records(11));
+        assert!(cache.get(0, 0xB).is_none());
+        assert!(cache.get(0, 0xA).is_some());
+    }
+
+    // Invalidation empties the cache; the old generation can neither read nor insert.
+    #[test]
+    fn invalidation_fences_old_generation() {
+        let cache = PrefixCache::new(1 << 20);
+        cache.insert(0, 0xA, &records(1));
+        assert_eq!(cache.invalidate(), 1);
+        assert_eq!(cache.stats().entries, 0);
+
+        cache.insert(0, 0xA, &records(1));
+        assert!(cache.get(1, 0xA).is_none());
+        cache.insert(1, 0xA, &records(1));
+        assert!(cache.get(0, 0xA).is_none());
+        assert!(cache.get(1, 0xA).is_some());
+    }
+}
@@ -0,0 +1,95 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::search::RecordLayout;
+use crate::{PREFIX_LEN, RECORD_SIZE};
+
+/// Errors returned by dataset validation and lookups.
+///
+/// The variants separate problems with the dataset as a whole from a single missing or corrupt
+/// prefix file and from transient I/O errors, so callers can decide whether to fail open or
+/// closed for each.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The dataset directory does not exist.
+    #[error("dataset directory {} not found", .0.display())]
+    DatasetMissing(PathBuf),
+
+    /// The dataset path exists but is not a directory.
+    #[error("dataset path {} is not a directory", .0.display())]
+    NotADirectory(PathBuf),
+
+    /// The prefix file for a hash is missing from the dataset directory.
+    #[error("prefix file {prefix:05X}.bin not found")]
+    PrefixFileMissing { prefix: u32 },
+
+    /// The prefix file length is not a whole number of records.
+    #[error(
+        "prefix file {prefix:05X}.bin is corrupt: {len} bytes is not a multiple of {RECORD_SIZE}"
+    )]
+    CorruptRecordLength { prefix: u32, len: u64 },
+
+    /// The prefix file is larger than the lookup read buffer.
+    #[error("prefix file {prefix:05X}.bin is {len} bytes, larger than the {max}-byte read buffer")]
+    PrefixFileTooLarge { prefix: u32, len: u64, max: usize },
+
+    /// The prefix is outside the range held by this checker.
+    #[error("prefix {prefix:05X} is outside the loaded range")]
+    PrefixNotInRange { prefix: u32 },
+
+    /// The pre-filter file is malformed or from an unsupported format version.
+    #[error("prefilter is corrupt: {0}")]
+    CorruptPrefilter(&'static str),
+
+    /// The dataset's `record-layout` file names a layout this version does not know.
+    #[error("unknown record layout {0:?}")]
+    UnknownRecordLayout(String),
+
+    /// A prefix file's records are not in the layout the checker searches them as, e.g. an
+    /// Eytzinger dataset passed to `BreachChecker::new`, which assumes sorted records.
+    #[error("prefix file records are not in {0} order; open the dataset with BreachChecker::open")]
+    RecordLayoutMismatch(RecordLayout),
+
+    /// Any other I/O error, typically transient.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Whether the error means the dataset as a whole is unusable, rather than a single
+    /// prefix file or a transient read failure.
+    pub fn is_dataset_error(&self) -> bool {
+        matches!(
+            self,
+            Self::DatasetMissing(_) | Self::NotADirectory(_) | Self::RecordLayoutMismatch(_)
+        )
+    }
+
+    /// Maps a failure to open a prefix file. `dataset_path` is given for path-based opens so a
+    /// missing directory can be told apart from a missing file.
+    pub(crate) fn from_open(
+        e: io::Error,
+        prefix_hex: [u8; PREFIX_LEN],
+        dataset_path: Option<&Path>,
+    ) -> Self {
+        if e.kind() != io::ErrorKind::NotFound {
+            return Self::Io(e);
+        }
+        match dataset_path {
+            Some(path) if !path.is_dir() => Self::DatasetMissing(path.to_path_buf()),
+            _ => Self::PrefixFileMissing { prefix: prefix_value(prefix_hex) },
+        }
+    }
+}
+
+/// Numeric value of a 5-character uppercase hex prefix.
+pub(crate) fn prefix_value(prefix_hex: [u8; PREFIX_LEN]) -> u32 {
+    prefix_hex.iter().fold(0, |acc, &c| {
+        let digit = match c {
+            b'0'..=b'9' => c - b'0',
+            _ => c - b'A' + 10,
+        };
+        acc << 4 | digit as u32
+    })
+}
@@ -0,0 +1,297 @@
+//! Checking passwords against several datasets at once.
+//!
+//! Besides HIBP, a deployment often has to reject its own passwords: product names, the
+//! company name plus a year, leaked internal lists. [`compile_wordlist`] turns such a list into
+//! an overlay in the same prefix-file layout, and a [`LayeredChecker`] consults the overlays
+//! alongside the HIBP dataset, reporting which layer matched. Allowlist layers, compiled the
+//! same way, override matches from every other layer.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+use sha1::{Digest, Sha1};
+
+use crate::{Error, OwnedBreachChecker, READ_BUF_LEN, RECORD_SIZE, hash_prefix, search_key, sha1};
+
+/// What a layer's matches mean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    /// A full dataset such as HIBP, with a file for every prefix. Matches reject the password.
+    Dataset,
+    /// A wordlist compiled with [`compile_wordlist`]. Matches reject the password.
+    Blocklist,
+    /// A wordlist compiled with [`compile_wordlist`]. Matches accept the password even if a
+    /// `Dataset` or `Blocklist` layer holds it.
+    Allowlist,
+}
+
+/// Result of a [`LayeredChecker::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict<'a> {
+    /// No rejecting layer holds the password.
+    Clean,
+    /// The password is rejected by `layer`, the first rejecting layer that holds it, which is
+    /// of `kind`.
+    Blocked { layer: &'a str, kind: LayerKind },
+    /// `blocked_by` holds the password, but allowlist `layer` overrides it.
+    Allowed { layer: &'a str, blocked_by: &'a str },
+}
+
+impl Verdict<'_> {
+    /// Whether the password must be rejected.
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, Verdict::Blocked { .. })
+    }
+}
+
+struct Layer {
+    name: String,
+    kind: LayerKind,
+    checker: OwnedBreachChecker,
+}
+
+impl Layer {
+    fn contains_hash(&self, hash: &[u8; 20]) -> Result<bool, Error> {
+        match self.checker.contains_hash(hash) {
+            // Compiled wordlists only have files for prefixes they hold a password in.
+            Err(Error::PrefixFileMissing { .. }) if self.kind != LayerKind::Dataset => Ok(false),
+            result => result,
+        }
+    }
+}
+
+/// Checks passwords against an ordered stack of datasets, blocklists and allowlists.
+///
+/// Rejecting layers are consulted in the order they were added and the first match wins, so
+/// put the layer that should be reported first. Allowlists are only consulted once a rejecting
+/// layer has matched.
+///
+/// ```rust,ignore
+/// use hibp_verifier::OwnedBreachChecker;
+/// use hibp_verifier::layered::LayeredChecker;
+///
+/// let checker = LayeredChecker::new()
+///     .blocklist("company", "/etc/hibp/company-words")?
+///     .dataset("hibp", OwnedBreachChecker::open("/path/to/hibp-data")?)
+///     .allowlist("exceptions", "/etc/hibp/allowed")?;
+///
+/// if checker.check("Acme2026!")?.is_blocked() {
+///     // reject
+/// }
+/// ```
+#[derive(Default)]
+pub struct LayeredChecker {
+    layers: Vec<Layer>,
+}
+
+impl LayeredChecker {
+    /// A checker with no layers, which finds every password clean.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a full dataset, such as HIBP, as a rejecting layer.
+    pub fn dataset(self, name: impl Into<String>, checker: OwnedBreachChecker) -> Self {
+        self.push(name, LayerKind::Dataset, checker)
+    }
+
+    /// Adds the wordlist compiled into `path` as a rejecting layer.
+    pub fn blocklist(self, name: impl Into<String>, path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(self.push(name, LayerKind::Blocklist, open_wordlist(path.as_ref())?))
+    }
+
+    /// Adds the wordlist compiled into `path` as an allowlist.
+    pub fn allowlist(self, name: impl Into<String>, path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(self.push(name, LayerKind::Allowlist, open_wordlist(path.as_ref())?))
+    }
+
+    fn push(
+        mut self,
+        name: impl Into<String>,
+        kind: LayerKind,
+        checker: OwnedBreachChecker,
+    ) -> Self {
+        self.layers.push(Layer { name: name.into(), kind, checker });
+        self
+    }
+
+    /// The layers in the order they were added.
+    pub fn layers(&self) -> impl Iterator<Item = (&str, LayerKind)> {
+        self.layers.iter().map(|layer| (layer.name.as_str(), layer.kind))
+    }
+
+    /// Checks the password against every layer it needs to.
+    ///
+    /// Fails if any consulted layer fails, including [`Error::PrefixNotInRange`] from a
+    /// dataset limited to a shard.
+    pub fn check(&self, password: &str) -> Result<Verdict<'_>, Error> {
+        self.check_excusing(password, |_, _| Ok(false))
+    }
+
+    /// [`check`](Self::check), except that a rejecting layer holding the password is passed
+    /// over when `excuse` returns `true` for its name and kind, so later layers still decide.
+    /// `excuse` is only called for layers that hold the password.
+    pub fn check_excusing(
+        &self,
+        password: &str,
+        mut excuse: impl FnMut(&str, LayerKind) -> Result<bool, Error>,
+    ) -> Result<Verdict<'_>, Error> {
+        let hash = sha1(password);
+        let mut blocked_by = None;
+        for layer in self.layers.iter().filter(|layer| layer.kind != LayerKind::Allowlist) {
+            if layer.contains_hash(&hash)? && !excuse(&layer.name, layer.kind)? {
+                blocked_by = Some(layer);
+                break;
+            }
+        }
+        let Some(blocked_by) = blocked_by else {
+            return Ok(Verdict::Clean);
+        };
+        for layer in self.layers.iter().filter(|layer| layer.kind == LayerKind::Allowlist) {
+            if layer.contains_hash(&hash)? {
+                return Ok(Verdict::Allowed { layer: &layer.name, blocked_by: &blocked_by.name });
+            }
+        }
+        Ok(Verdict::Blocked { layer: &blocked_by.name, kind: blocked_by.kind })
+    }
+}
+
+impl fmt::Debug for LayeredChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.layers()).finish()
+    }
+}
+
+fn open_wordlist(path: &Path) -> Result<OwnedBreachChecker, Error> {
+    OwnedBreachChecker::builder(path).validate_layout(false).build()
+}
+
+/// Compiles a wordlist, one password per line, into the prefix-file layout at `output` and
+/// returns the number of distinct passwords.
+///
+/// Lines are hashed byte for byte after stripping the line ending; empty lines are skipped.
+/// Only prefixes holding a password get a file, and `.bin` files already in `output` are
+/// removed first, so recompiling replaces the previous list.
+pub fn compile_wordlist(words: impl BufRead, output: &Path) -> Result<usize, Error> {
+    let mut records: Vec<(u32, [u8; RECORD_SIZE])> = Vec::new();
+    for line in words.split(b'\n') {
+        let line = line?;
+        let word = line.strip_suffix(b"\r").unwrap_or(&line);
+        if word.is_empty() {
+            continue;
+        }
+        let hash: [u8; 20] = Sha1::digest(word).into();
+        records.push((hash_prefix(&hash), search_key(&hash)));
+    }
+    records.sort_unstable();
+    records.dedup();
+
+    std::fs::create_dir_all(output)?;
+    for entry in std::fs::read_dir(output)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "bin") {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    for group in records.chunk_by(|a, b| a.0 == b.0) {
+        let prefix = group[0].0;
+        let len = group.len() * RECORD_SIZE;
+        if len >= READ_BUF_LEN {
+            return Err(Error::PrefixFileTooLarge { prefix, len: len as u64, max: READ_BUF_LEN });
+        }
+        let mut file = BufWriter::new(File::create(output.join(format!("{prefix:05X}.bin")))?);
+        for (_, record) in group {
+            file.write_all(record)?;
+        }
+        file.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+    }
+    Ok(records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_dataset;
+
+    fn layered(dir: &Path) -> LayeredChecker {
+        // HIBP stand-in holding "password123" and "hunter2", with the sentinels and an empty
+        // file for the prefix of "correct horse".
+        let hibp = dir.join("hibp");
+        write_dataset(&hibp, &["password123", "hunter2"], &["correct horse"]);
+        compile_wordlist(
+            &b"Acme2026\r\n\nhunter2\nAcme2026\n"[..],
+            &dir.join("company"),
+        )
+        .unwrap();
+        compile_wordlist(&b"hunter2\n"[..], &dir.join("allowed")).unwrap();
+
+        LayeredChecker::new()
+            .blocklist("company", dir.join("company"))
+            .unwrap()
+            .dataset("hibp", OwnedBreachChecker::open(&hibp).unwrap())
+            .allowlist("allowed", dir.join("allowed"))
+            .unwrap()
+    }
+
+    // Password only in the blocklist → blocked by it; only in HIBP → blocked by HIBP; in
+    // neither → clean, even though the wordlists have no file for its prefix. A missing file
+    // in the full dataset is still an error.
+    #[test]
+    fn reports_matching_layer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let checker = layered(tmp.path());
+
+        assert_eq!(
+            checker.check("Acme2026").unwrap(),
+            Verdict::Blocked { layer: "company", kind: LayerKind::Blocklist }
+        );
+        assert_eq!(
+            checker.check("password123").unwrap(),
+            Verdict::Blocked { layer: "hibp", kind: LayerKind::Dataset }
+        );
+        assert!(checker.check("password123").unwrap().is_blocked());
+        assert_eq!(checker.check("correct horse").unwrap(), Verdict::Clean);
+        assert!(matches!(
+            checker.check("letmein"),
+            Err(Error::PrefixFileMissing { .. })
+        ));
+    }
+
+    // In both rejecting layers and the allowlist → allowed, naming the first rejecting layer.
+    #[test]
+    fn allowlist_overrides_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let checker = layered(tmp.path());
+
+        let verdict = checker.check("hunter2").unwrap();
+        assert_eq!(
+            verdict,
+            Verdict::Allowed { layer: "allowed", blocked_by: "company" }
+        );
+        assert!(!verdict.is_blocked());
+    }
+
+    // Duplicates and blank lines collapse; recompiling drops prefixes no longer in the list.
+    #[test]
+    fn compile_replaces_previous_list() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("list");
+        assert_eq!(compile_wordlist(&b"a\nb\n\na\r\n"[..], &out).unwrap(), 2);
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 2);
+
+        assert_eq!(compile_wordlist(&b"a"[..], &out).unwrap(), 1);
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 1);
+        let list = open_wordlist(&out).unwrap();
+        assert!(list.is_breached("a").unwrap());
+    }
+
+    #[test]
+    fn missing_wordlist_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let err = LayeredChecker::new().blocklist("company", tmp.path().join("missing"));
+        assert!(matches!(err, Err(Error::DatasetMissing(_))));
+    }
+}
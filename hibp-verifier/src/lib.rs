@@ -1,8 +1,8 @@
 //! High-performance library for checking passwords against the Have I Been Pwned
-//! breach database using binary search on a compact 6-byte (sha1t48) format.
+//! breach database using interpolation search on a compact 6-byte (sha1t48) format.
 //!
 //! This library provides sub-microsecond password breach checking by reading
-//! pre-processed HIBP dataset files and performing binary search on sorted records.
+//! pre-processed HIBP dataset files and performing interpolation search on sorted records.
 //! The hot path is zero-allocation for maximum performance.
 //!
 //! # Quick Start
@@ -40,6 +40,13 @@
 //! This format reduces storage from 77 GB (original text) to 13 GB while enabling
 //! O(log n) binary search with direct indexing—no parsing overhead.
 //!
+//! # Record Layout
+//!
+//! Sorted files are searched with [`search::interpolation_search`]. A dataset fetched with
+//! `hibp-bin-fetch fetch --layout eytzinger` stores each file in Eytzinger order instead, and
+//! is searched with [`search::eytzinger_search`]. The layout is recorded in the dataset's
+//! [`search::LAYOUT_FILE`] and read by [`BreachChecker::open`] and the other checkers.
+//!
 //! # Performance
 //!
 //! High concurrency benchmark (10k concurrent lookups, 24 worker threads):
@@ -69,7 +76,7 @@
 //! use std::path::Path;
 //!
 //! #[tokio::main]
-//! async fn main() -> std::io::Result<()> {
+//! async fn main() -> Result<(), hibp_verifier::Error> {
 //!     let checker = BreachChecker::new(Path::new("/path/to/hibp-data"));
 //!
 //!     if checker.is_breached_async("password123").await? {
@@ -96,13 +103,188 @@
 //! This uses compio's native io-uring file I/O. Note that benchmarks show this is
 //! ~1.5x slower than the tokio `spawn_blocking` approach due to the non-work-stealing
 //! model required by io-uring's thread-local buffer requirements.
+//!
+//! # Other Runtimes
+//!
+//! [`pool::PooledChecker`] needs no feature and no particular runtime. It runs lookups on a
+//! small thread pool of its own and returns plain futures, for smol, async-std and other
+//! executors:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::pool::PooledChecker;
+//!
+//! let checker = PooledChecker::new(OwnedBreachChecker::open("/path/to/hibp-data")?, 4)?;
+//! let breached = smol::block_on(checker.is_breached("password123"))?;
+//! ```
+//!
+//! # Batched io_uring Lookups
+//!
+//! With the `io-uring` feature on Linux, [`uring::UringChecker`] checks a batch of passwords
+//! in one submission, reading each prefix file once and keeping hot prefix files open as
+//! direct descriptors across batches.
+//!
+//! # Owned Checker
+//!
+//! [`BreachChecker`] borrows its path. When the checker has to live in an `Arc`, app state or
+//! a spawned task, use [`OwnedBreachChecker`], which has the same lookup methods. It holds
+//! the dataset directory open and resolves prefix files with `openat`, where
+//! [`BreachChecker`] opens them by full path:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//!
+//! let checker = OwnedBreachChecker::open("/path/to/hibp-data")?;
+//! tokio::spawn(async move { checker.is_breached_async("password123").await });
+//! ```
+//!
+//! # Pre-Filter
+//!
+//! A [`prefilter::Prefilter`] built by `hibp-bin-fetch --prefilter` (and kept current by
+//! `hibp-sync-client --prefilter`) rules out ~99.6% of non-breached passwords from memory, so
+//! only the rest open a prefix file:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//!
+//! let checker = OwnedBreachChecker::builder("/path/to/hibp-data").prefilter(true).build()?;
+//! ```
+//!
+//! # Prefix Cache
+//!
+//! A [`cache::PrefixCache`] keeps the records of hot prefix files in memory, bounded in bytes
+//! with LRU eviction, and counts hits and misses. It is invalidated when the dataset changes:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::cache::PrefixCache;
+//!
+//! let cache = Arc::new(PrefixCache::new(64 << 20));
+//! let checker = OwnedBreachChecker::open("/path/to/hibp-data")?.with_cache(cache.clone());
+//! ```
+//!
+//! # In-Memory Dataset
+//!
+//! [`memory::MemoryDataset`] loads the dataset (or a prefix range of it) into RAM, optionally
+//! backed by huge pages, for lookups with no syscalls. After a delta sync it can re-read just
+//! the prefixes the sync client reports as changed:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::memory::MemoryDataset;
+//!
+//! let mut dataset = MemoryDataset::builder("/path/to/hibp-data").huge_pages(true).build()?;
+//! let breached = dataset.is_breached("password123")?;
+//! dataset.update_from_dir("/path/to/hibp-data".as_ref(), commit.changed_prefixes.unwrap())?;
+//! ```
+//!
+//! # Sharding
+//!
+//! A dataset synced with `hibp-sync-client --shard 3/8` holds one [`shard::Shard`] of the
+//! prefixes. A checker limited to that range answers [`Error::PrefixNotInRange`] for the rest,
+//! so a router can send each password to [`shard::Shard::for_password`]:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::{OwnedBreachChecker, shard::Shard};
+//!
+//! let shard: Shard = "3/8".parse()?;
+//! let checker =
+//!     OwnedBreachChecker::builder("/path/to/hibp-data").prefix_range(shard.range()).build()?;
+//! ```
+//!
+//! # Blocklists and Allowlists
+//!
+//! [`layered::compile_wordlist`] compiles a company-specific wordlist into the same prefix-file
+//! layout. A [`layered::LayeredChecker`] stacks such lists on top of HIBP, reports which layer
+//! matched, and lets allowlist layers override matches:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::{OwnedBreachChecker, layered::{LayeredChecker, Verdict}};
+//!
+//! let checker = LayeredChecker::new()
+//!     .dataset("hibp", OwnedBreachChecker::open("/path/to/hibp-data")?)
+//!     .blocklist("company", "/etc/hibp/company-words")?
+//!     .allowlist("exceptions", "/etc/hibp/allowed")?;
+//! if let Verdict::Blocked { layer, .. } = checker.check("Acme2026!")? {
+//!     println!("rejected by {layer}");
+//! }
+//! ```
+//!
+//! # Near Misses
+//!
+//! [`near_miss::NearMissChecker`] also checks normalized variants of a password (trailing
+//! digits and symbols stripped, leetspeak undone, case changed) in a single batch lookup
+//! within a latency budget, and reports which transformation led to a breached password.
+//!
+//! # Password Rules
+//!
+//! [`rules::PasswordRules`] combines a [`layered::LayeredChecker`] with NIST SP 800-63B style
+//! rules (length, context words such as the username, repeated and sequential characters)
+//! and lists every rule a password breaks as a [`rules::Violation`] a UI can show.
+//!
+//! # Failure Policy
+//!
+//! The `policy` feature adds [`policy::PolicyChecker`], which answers `Allow`, `Reject` or
+//! `Degraded(reason)`, applies a fail-open or fail-closed mode, enforces a per-lookup timeout
+//! and trips a circuit breaker on repeated failures.
+//!
+//! # Tower Middleware
+//!
+//! The `tower` feature adds [`tower::BreachCheckLayer`], which checks a password field in
+//! JSON or form request bodies with the tokio API and either rejects breached passwords or
+//! records a [`tower::BreachStatus`] in the request extensions for the handler.
+//!
+//! # Following Dataset Updates
+//!
+//! Enable the `reload` feature for [`reload::ReloadingChecker`], an owned, cloneable checker
+//! that watches the `sync-state.json` written by `hibp-sync-client` and switches to new data
+//! when a sync commits:
+//!
+//! ```rust,ignore
+//! use hibp_verifier::reload::ReloadingChecker;
+//!
+//! let checker = ReloadingChecker::open("/path/to/hibp-data/current")?;
+//! println!("serving dataset from {:?}", checker.version());
+//! let breached = checker.is_breached("password123")?;
+//! ```
 
 use std::fs::File;
 use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd};
 use std::path::{Path, PathBuf};
 
 use sha1::{Digest, Sha1};
 
+pub mod cache;
+pub mod error;
+pub mod layered;
+pub mod memory;
+pub mod near_miss;
+mod owned;
+#[cfg(feature = "policy")]
+pub mod policy;
+pub mod pool;
+pub mod prefilter;
+#[cfg(feature = "reload")]
+pub mod reload;
+pub mod rules;
+pub mod search;
+pub mod shard;
+#[cfg(any(test, feature = "testing"))]
+#[doc(hidden)]
+pub mod test_util;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+
+use cache::PrefixCache;
+pub use error::Error;
+use error::prefix_value;
+pub use owned::{BreachCheckerBuilder, OwnedBreachChecker};
+use prefilter::Prefilter;
+use search::RecordLayout;
+
 /// Environment variable name for specifying the HIBP dataset directory.
 pub const HIBP_DATA_DIR_ENV: &str = "HIBP_DATA_DIR";
 
@@ -123,65 +305,185 @@ pub const RECORD_SIZE: usize = 6;
 /// The length of a SHA1 hash prefix used for file naming (5 hex characters).
 pub const PREFIX_LEN: usize = 5;
 
+/// Number of prefix files in a full dataset.
+pub const PREFIX_COUNT: u32 = 1 << 20;
+
 /// Hex lookup table for prefix conversion.
 pub const HEX_CHARS: &[u8; 16] = b"0123456789ABCDEF";
 
+/// Size of the stack buffer a prefix file is read into. Larger prefix files are rejected.
+pub const READ_BUF_LEN: usize = 16384;
+
+/// Parses a SHA-1 digest written as 40 hex characters in either case, e.g. for
+/// [`OwnedBreachChecker::contains_hash`]. Any other length or character gives `None`.
+pub fn parse_sha1_hex(hex: &str) -> Option<[u8; 20]> {
+    let hex = hex.as_bytes();
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut hash = [0u8; 20];
+    for (byte, pair) in hash.iter_mut().zip(hex.chunks_exact(2)) {
+        *byte = (hex_value(pair[0])? << 4) | hex_value(pair[1])?;
+    }
+    Some(hash)
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Checks that `dataset_path` is a dataset directory: it must exist, be a directory, and hold
+/// the first and last prefix files (`00000.bin` and `FFFFF.bin`) with valid lengths.
+///
+/// This is a cheap sanity check for a wrong path or an unfinished download, not a full scan of
+/// all 1,048,576 files.
+pub fn validate_layout(dataset_path: &Path) -> Result<(), Error> {
+    validate_range_layout(dataset_path, &(0..=PREFIX_COUNT - 1))
+}
+
+/// [`validate_layout`] for a dataset holding only `range`: checks its first and last files.
+pub fn validate_range_layout(
+    dataset_path: &Path,
+    range: &RangeInclusive<u32>,
+) -> Result<(), Error> {
+    match std::fs::metadata(dataset_path) {
+        Ok(meta) if meta.is_dir() => {}
+        Ok(_) => return Err(Error::NotADirectory(dataset_path.to_path_buf())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::DatasetMissing(dataset_path.to_path_buf()));
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    for prefix in [*range.start(), *range.end()] {
+        let meta = std::fs::metadata(dataset_path.join(format!("{prefix:05X}.bin")));
+        check_sentinel(prefix, meta.map(|meta| meta.len()))?;
+    }
+    Ok(())
+}
+
+/// [`validate_range_layout`] for a directory that is already open. Only the two prefix files
+/// are checked, relative to `dir`.
+pub(crate) fn validate_range_layout_at(
+    dir: BorrowedFd<'_>,
+    range: &RangeInclusive<u32>,
+) -> Result<(), Error> {
+    for prefix in [*range.start(), *range.end()] {
+        let len = open_file_at(dir, prefix_to_hex(prefix)).and_then(|file| file.metadata());
+        check_sentinel(prefix, len.map(|meta| meta.len()))?;
+    }
+    Ok(())
+}
+
+/// Checks the length of a first or last prefix file, as read by [`validate_range_layout`].
+fn check_sentinel(prefix: u32, len: io::Result<u64>) -> Result<(), Error> {
+    let len = match len {
+        Ok(len) => len,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::PrefixFileMissing { prefix });
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !len.is_multiple_of(RECORD_SIZE as u64) {
+        return Err(Error::CorruptRecordLength { prefix, len });
+    }
+    if len >= READ_BUF_LEN as u64 {
+        return Err(Error::PrefixFileTooLarge { prefix, len, max: READ_BUF_LEN });
+    }
+    Ok(())
+}
+
 /// Checks if a password has been found in known data breaches.
 ///
 /// This struct holds a reference to the directory containing the HIBP binary dataset files.
+///
+/// Its sync, tokio and compio lookups open each prefix file by its full path. The checker
+/// only borrows the path and [`new`](Self::new) does not touch the filesystem, so there is
+/// no directory handle to resolve file names against, and checkers stay free to create per
+/// request. [`OwnedBreachChecker`] holds the directory open and uses `openat` instead.
 pub struct BreachChecker<'a> {
     dataset_path: &'a Path,
+    prefilter: Option<&'a Prefilter>,
+    range: RangeInclusive<u32>,
+    cache: Option<(&'a PrefixCache, u64)>,
+    layout: RecordLayout,
 }
 
 impl<'a> BreachChecker<'a> {
     /// Creates a new BreachChecker with the given dataset directory path.
     ///
     /// The directory should contain binary files named `{PREFIX}.bin` where PREFIX
-    /// is a 5-character uppercase hex string (00000-FFFFF).
+    /// is a 5-character uppercase hex string (00000-FFFFF). The path is not checked and the
+    /// records are assumed sorted, so lookups on an Eytzinger dataset fail with
+    /// [`Error::RecordLayoutMismatch`]; use [`BreachChecker::open`] to validate it up front and
+    /// read its [record layout](search::RecordLayout).
     pub fn new(dataset_path: &'a Path) -> Self {
-        Self { dataset_path }
+        Self {
+            dataset_path,
+            prefilter: None,
+            range: 0..=PREFIX_COUNT - 1,
+            cache: None,
+            layout: RecordLayout::Sorted,
+        }
+    }
+
+    /// Creates a new BreachChecker after checking the dataset layout with
+    /// [`validate_layout`] and reading its [`search::LAYOUT_FILE`].
+    pub fn open(dataset_path: &'a Path) -> Result<Self, Error> {
+        validate_layout(dataset_path)?;
+        let layout = RecordLayout::load(dataset_path)?;
+        Ok(Self::new(dataset_path).with_record_layout(layout))
+    }
+
+    /// Searches prefix files as `layout` rather than as sorted records. [`open`](Self::open)
+    /// reads the dataset's layout itself.
+    pub fn with_record_layout(mut self, layout: RecordLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Consults `prefilter` before each lookup, so passwords it rules out return `Ok(false)`
+    /// without touching the disk. The filter must have been built from this dataset.
+    pub fn with_prefilter(mut self, prefilter: &'a Prefilter) -> Self {
+        self.prefilter = Some(prefilter);
+        self
+    }
+
+    /// Restricts the checker to the prefixes in `range`, such as a [`shard::Shard::range`].
+    /// Passwords whose prefix falls outside it fail with [`Error::PrefixNotInRange`] instead of
+    /// a missing-file error, so a router can tell "not my shard" from a broken dataset.
+    pub fn with_prefix_range(mut self, range: RangeInclusive<u32>) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Searches prefix files held in `cache` instead of reading them, and adds the files it
+    /// reads. The checker is bound to the cache's current generation; see [`cache`].
+    pub fn with_cache(mut self, cache: &'a PrefixCache) -> Self {
+        self.cache = Some((cache, cache.generation()));
+        self
     }
 
     /// Checks if the given password has been found in a data breach.
     ///
     /// Returns `Ok(true)` if the password was found in the breach database,
     /// `Ok(false)` if it was not found, or an error if the lookup failed.
-    pub fn is_breached(&self, password: &str) -> io::Result<bool> {
-        // Compute SHA1 hash as raw bytes
-        let mut hasher = Sha1::new();
-        hasher.update(password.as_bytes());
-        let hash: [u8; 20] = hasher.finalize().into();
-
-        let prefix_hex = Self::prefix_hex(&hash);
-        let mut file = self.open_file(prefix_hex)?;
-
-        // largest file size currently is 14.6KB for 6-byte records (2495 records in that prefix
-        // file) Use a 16KB stack buffer to avoid allocation. This should provide room for
-        // growth over time.
-        let mut buf = [0u8; 16384];
-
-        // read() is not guaranteed to return the full file in a single call.
-        // This loop logic handles ensuring we always read to the end.
-        //
-        // I've benchmarked this against getting the metadata for the file
-        // upfront and reading until total bytes read == size from metadata, and
-        // that approach was slower. Likely because fstat() has to copy the full
-        // stat structure(144 bytes on x86_64) from kernel to userspace.
-        let mut total = 0usize;
-        loop {
-            match file.read(&mut buf[total..]) {
-                Ok(0) => break,
-                Ok(n) => {
-                    total += n;
-                }
-                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e),
-            }
+    pub fn is_breached(&self, password: &str) -> Result<bool, Error> {
+        let hash = sha1(password);
+        if ruled_out(&self.range, self.prefilter, &hash)? {
+            return Ok(false);
         }
-
-        let search_key: [u8; 6] = unsafe { hash[2..8].try_into().unwrap_unchecked() };
-
-        Ok(buf[..total].as_chunks::<RECORD_SIZE>().0.binary_search(&search_key).is_ok())
+        let prefix_hex = Self::prefix_hex(&hash);
+        search_cached(
+            self.cache,
+            self.layout,
+            prefix_hex,
+            search_key(&hash),
+            || {
+                self.open_file(prefix_hex)
+                    .map_err(|e| Error::from_open(e, prefix_hex, Some(self.dataset_path)))
+            },
+        )
     }
 
     /// Returns the prefix for the hash as hex (first 5 hex chars == first 2.5 bytes)
@@ -200,30 +502,12 @@ impl<'a> BreachChecker<'a> {
         prefix_hex
     }
 
-    // Build file path without allocation: base_path + '/' + prefix + ".bin"
-    #[inline(always)]
-    fn build_path(&self, prefix_hex: [u8; PREFIX_LEN]) -> ([u8; 512], usize) {
-        let base = self.dataset_path.as_os_str().as_encoded_bytes();
-        let mut path_buf = [0u8; 512];
-        let path_len = base.len() + 1 + PREFIX_LEN + 4; // +4 for ".bin"
-        path_buf[..base.len()].copy_from_slice(base);
-        path_buf[base.len()] = b'/';
-        path_buf[base.len() + 1..base.len() + 1 + PREFIX_LEN].copy_from_slice(&prefix_hex);
-        path_buf[base.len() + 1 + PREFIX_LEN..path_len].copy_from_slice(b".bin");
-
-        (path_buf, path_len)
-    }
-
-    /// Build file path without allocation: base_path + '/' + prefix + ".bin"
+    /// Opens a prefix file by its full path, built without allocation for paths up to
+    /// `PATH_BUF_LEN` bytes. See the type docs for why this is not `openat`.
     #[doc(hidden)]
     #[inline(always)]
     pub fn open_file(&self, prefix_hex: [u8; PREFIX_LEN]) -> io::Result<File> {
-        let (path_buf, path_len) = self.build_path(prefix_hex);
-
-        // SAFETY: path_buf contains valid UTF-8 (base path + '/' + hex prefix + ".bin")
-        let file_path = unsafe { std::str::from_utf8_unchecked(&path_buf[..path_len]) };
-
-        File::open(file_path)
+        open_file(self.dataset_path, prefix_hex)
     }
 
     /// Async version of `is_breached` using tokio.
@@ -238,7 +522,7 @@ impl<'a> BreachChecker<'a> {
     /// use std::path::Path;
     ///
     /// #[tokio::main]
-    /// async fn main() -> std::io::Result<()> {
+    /// async fn main() -> Result<(), hibp_verifier::Error> {
     ///     let checker = BreachChecker::new(Path::new("/path/to/hibp-data"));
     ///
     ///     if checker.is_breached_async("password123").await? {
@@ -249,36 +533,16 @@ impl<'a> BreachChecker<'a> {
     /// }
     /// ```
     #[cfg(feature = "tokio")]
-    pub async fn is_breached_async(&self, password: &str) -> io::Result<bool> {
-        let mut hasher = Sha1::new();
-        hasher.update(password.as_bytes());
-        let hash: [u8; 20] = hasher.finalize().into();
-
-        let search_key: [u8; 6] = unsafe { hash[2..8].try_into().unwrap_unchecked() };
-
-        let prefix_hex = Self::prefix_hex(&hash);
-        let (path_buf, path_len) = self.build_path(prefix_hex);
-
-        // Only file I/O goes into spawn_blocking
-        tokio::task::spawn_blocking(move || {
-            let file_path = unsafe { std::str::from_utf8_unchecked(&path_buf[..path_len]) };
-            let mut file = File::open(file_path)?;
-
-            let mut buf = [0u8; 16384];
-            let mut total = 0usize;
-            loop {
-                match file.read(&mut buf[total..]) {
-                    Ok(0) => break,
-                    Ok(n) => total += n,
-                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                }
-            }
-
-            Ok(buf[..total].as_chunks::<RECORD_SIZE>().0.binary_search(&search_key).is_ok())
-        })
+    pub async fn is_breached_async(&self, password: &str) -> Result<bool, Error> {
+        is_breached_tokio(
+            self.dataset_path,
+            &self.range,
+            self.prefilter,
+            self.cache,
+            self.layout,
+            password,
+        )
         .await
-        .expect("spawn_blocking task panicked")
     }
 
     /// Async version of `is_breached` using compio's native io-uring file I/O.
@@ -289,39 +553,393 @@ impl<'a> BreachChecker<'a> {
     /// compio is compatible with ntex's compio runtime feature, making this
     /// suitable for use within ntex web applications that want to use compio.
     #[cfg(feature = "compio")]
-    pub async fn is_breached_compio(&self, password: &str) -> io::Result<bool> {
-        use compio::fs::File;
-        use compio::io::AsyncReadAt;
+    pub async fn is_breached_compio(&self, password: &str) -> Result<bool, Error> {
+        is_breached_compio(
+            self.dataset_path,
+            &self.range,
+            self.prefilter,
+            self.cache,
+            self.layout,
+            password,
+        )
+        .await
+    }
+}
 
-        let mut hasher = Sha1::new();
-        hasher.update(password.as_bytes());
-        let hash: [u8; 20] = hasher.finalize().into();
+#[inline(always)]
+fn sha1(password: &str) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(password.as_bytes());
+    hasher.finalize().into()
+}
 
-        let search_key: [u8; 6] = unsafe { hash[2..8].try_into().unwrap_unchecked() };
+#[inline(always)]
+fn search_key(hash: &[u8; 20]) -> [u8; RECORD_SIZE] {
+    unsafe { hash[2..8].try_into().unwrap_unchecked() }
+}
 
-        let prefix_hex = Self::prefix_hex(&hash);
-        let (path_buf, path_len) = self.build_path(prefix_hex);
-        let file_path = unsafe { std::str::from_utf8_unchecked(&path_buf[..path_len]) };
-
-        let file = File::open(file_path).await?;
-
-        // compio returns the buffer back to us after each operation
-        let mut buf = [0u8; 16384];
-        let mut total = 0usize;
-
-        loop {
-            let buf_result = file.read_at(buf, total as u64).await;
-            buf = buf_result.1;
-            match buf_result.0 {
-                Ok(0) => break,
-                Ok(n) => total += n,
-                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e),
+/// Size of the stack buffer prefix file paths are built in. Longer dataset paths fall back to
+/// a heap-allocated path.
+const PATH_BUF_LEN: usize = 512;
+
+/// Length of a prefix file name: prefix + ".bin".
+const FILE_NAME_LEN: usize = PREFIX_LEN + 4;
+
+/// Full path to a prefix file, built on the stack when it fits.
+#[allow(
+    clippy::large_enum_variant,
+    reason = "the inline path avoids allocating per lookup"
+)]
+enum PrefixPath {
+    Inline([u8; PATH_BUF_LEN], usize),
+    Long(PathBuf),
+}
+
+impl PrefixPath {
+    #[inline(always)]
+    fn as_path(&self) -> &Path {
+        match self {
+            // SAFETY: the buffer holds the bytes of an OsStr followed by ASCII.
+            Self::Inline(buf, len) => {
+                Path::new(unsafe { std::ffi::OsStr::from_encoded_bytes_unchecked(&buf[..*len]) })
+            }
+            Self::Long(path) => path,
+        }
+    }
+}
+
+// Build file path without allocation: base_path + '/' + prefix + ".bin"
+#[inline(always)]
+fn build_path(dataset_path: &Path, prefix_hex: [u8; PREFIX_LEN]) -> PrefixPath {
+    let base = dataset_path.as_os_str().as_encoded_bytes();
+    let path_len = base.len() + 1 + FILE_NAME_LEN;
+    if path_len > PATH_BUF_LEN {
+        let name = file_name(prefix_hex);
+        // SAFETY: file_name is ASCII.
+        let name = unsafe { std::str::from_utf8_unchecked(&name[..FILE_NAME_LEN]) };
+        return PrefixPath::Long(dataset_path.join(name));
+    }
+
+    let mut path_buf = [0u8; PATH_BUF_LEN];
+    path_buf[..base.len()].copy_from_slice(base);
+    path_buf[base.len()] = b'/';
+    path_buf[base.len() + 1..base.len() + 1 + PREFIX_LEN].copy_from_slice(&prefix_hex);
+    path_buf[base.len() + 1 + PREFIX_LEN..path_len].copy_from_slice(b".bin");
+
+    PrefixPath::Inline(path_buf, path_len)
+}
+
+#[inline(always)]
+fn open_file(dataset_path: &Path, prefix_hex: [u8; PREFIX_LEN]) -> io::Result<File> {
+    File::open(build_path(dataset_path, prefix_hex).as_path())
+}
+
+/// NUL-terminated prefix file name: prefix + ".bin\0".
+#[inline(always)]
+fn file_name(prefix_hex: [u8; PREFIX_LEN]) -> [u8; FILE_NAME_LEN + 1] {
+    let mut name = [0u8; FILE_NAME_LEN + 1];
+    name[..PREFIX_LEN].copy_from_slice(&prefix_hex);
+    name[PREFIX_LEN..FILE_NAME_LEN].copy_from_slice(b".bin");
+    name
+}
+
+/// Opens a prefix file relative to an open dataset directory. The kernel only resolves the
+/// file name rather than walking the whole dataset path.
+#[inline(always)]
+fn open_file_at(dir: BorrowedFd<'_>, prefix_hex: [u8; PREFIX_LEN]) -> io::Result<File> {
+    let name = file_name(prefix_hex);
+    // SAFETY: name is NUL-terminated and dir is a valid open fd for the duration of the call.
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr().cast(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openat returned a new fd that nothing else owns.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Opens another file of an open dataset directory, e.g. its [`search::LAYOUT_FILE`], so it
+/// comes from the same directory as the prefix files even if the dataset path is repointed.
+pub(crate) fn open_at(dir: BorrowedFd<'_>, name: &str) -> io::Result<File> {
+    let name = std::ffi::CString::new(name)?;
+    // SAFETY: name is NUL-terminated and dir is a valid open fd for the duration of the call.
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openat returned a new fd that nothing else owns.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Reads a whole prefix file and searches it for `search_key`.
+#[inline(always)]
+fn search_file(
+    file: &mut File,
+    prefix_hex: [u8; PREFIX_LEN],
+    search_key: [u8; RECORD_SIZE],
+    layout: RecordLayout,
+) -> Result<bool, Error> {
+    // largest file size currently is 14.6KB for 6-byte records (2495 records in that prefix
+    // file) Use a 16KB stack buffer to avoid allocation. This should provide room for
+    // growth over time.
+    let mut buf = [0u8; READ_BUF_LEN];
+    let records = read_records(file, prefix_hex, &mut buf)?;
+    layout.contains(records, &search_key)
+}
+
+/// Searches the cached records for `prefix_hex` if there are any, otherwise reads the file
+/// `open` returns and caches its records. Without a cache this is [`search_file`].
+#[inline(always)]
+fn search_cached(
+    cache: Option<(&PrefixCache, u64)>,
+    layout: RecordLayout,
+    prefix_hex: [u8; PREFIX_LEN],
+    search_key: [u8; RECORD_SIZE],
+    open: impl FnOnce() -> Result<File, Error>,
+) -> Result<bool, Error> {
+    let Some((cache, generation)) = cache else {
+        return search_file(&mut open()?, prefix_hex, search_key, layout);
+    };
+    if let Some(found) = search_cache(cache, generation, layout, prefix_hex, search_key) {
+        return found;
+    }
+    let mut buf = [0u8; READ_BUF_LEN];
+    let records = read_records(&mut open()?, prefix_hex, &mut buf)?;
+    cache.insert(generation, prefix_value(prefix_hex), records);
+    layout.contains(records, &search_key)
+}
+
+/// Searches the cached records for `prefix_hex`, or `None` if they are not cached.
+#[inline(always)]
+fn search_cache(
+    cache: &PrefixCache,
+    generation: u64,
+    layout: RecordLayout,
+    prefix_hex: [u8; PREFIX_LEN],
+    search_key: [u8; RECORD_SIZE],
+) -> Option<Result<bool, Error>> {
+    let records = cache.get(generation, prefix_value(prefix_hex))?;
+    Some(layout.contains(&records, &search_key))
+}
+
+/// Reads a whole prefix file into `buf`, returning its records.
+#[inline(always)]
+fn read_records<'a>(
+    file: &mut File,
+    prefix_hex: [u8; PREFIX_LEN],
+    buf: &'a mut [u8; READ_BUF_LEN],
+) -> Result<&'a [[u8; RECORD_SIZE]], Error> {
+    // read() is not guaranteed to return the full file in a single call.
+    // This loop logic handles ensuring we always read to the end.
+    //
+    // I've benchmarked this against getting the metadata for the file
+    // upfront and reading until total bytes read == size from metadata, and
+    // that approach was slower. Likely because fstat() has to copy the full
+    // stat structure(144 bytes on x86_64) from kernel to userspace.
+    let mut total = 0usize;
+    loop {
+        match file.read(&mut buf[total..]) {
+            Ok(0) => break,
+            Ok(n) => {
+                total += n;
             }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // READ_BUF_LEN is not a multiple of RECORD_SIZE, so a full buffer always means the file
+    // was cut short.
+    if total == READ_BUF_LEN {
+        let len = file.metadata()?.len();
+        return Err(Error::PrefixFileTooLarge {
+            prefix: prefix_value(prefix_hex),
+            len,
+            max: READ_BUF_LEN,
+        });
+    }
+    check_record_length(prefix_hex, total)?;
+
+    Ok(buf[..total].as_chunks::<RECORD_SIZE>().0)
+}
+
+/// Prefix file name stem for a numeric prefix, e.g. `0xCBFDA` → `CBFDA`.
+fn prefix_to_hex(prefix: u32) -> [u8; PREFIX_LEN] {
+    let mut hex = [0u8; PREFIX_LEN];
+    for (i, c) in hex.iter_mut().enumerate() {
+        *c = HEX_CHARS[(prefix >> (16 - 4 * i) & 0xF) as usize];
+    }
+    hex
+}
+
+#[inline(always)]
+fn check_record_length(prefix_hex: [u8; PREFIX_LEN], len: usize) -> Result<(), Error> {
+    if !len.is_multiple_of(RECORD_SIZE) {
+        return Err(Error::CorruptRecordLength {
+            prefix: prefix_value(prefix_hex),
+            len: len as u64,
+        });
+    }
+    Ok(())
+}
+
+/// Whether the pre-filter, if any, proves the hash is absent. Fails if the hash's prefix is
+/// outside the checker's range.
+#[inline(always)]
+fn ruled_out(
+    range: &RangeInclusive<u32>,
+    prefilter: Option<&Prefilter>,
+    hash: &[u8; 20],
+) -> Result<bool, Error> {
+    let prefix = hash_prefix(hash);
+    if !range.contains(&prefix) {
+        return Err(Error::PrefixNotInRange { prefix });
+    }
+    Ok(prefilter.is_some_and(|f| !f.may_contain(hash)))
+}
+
+/// Numeric prefix of a hash: its first 20 bits.
+#[inline(always)]
+fn hash_prefix(hash: &[u8; 20]) -> u32 {
+    (hash[0] as u32) << 12 | (hash[1] as u32) << 4 | (hash[2] >> 4) as u32
+}
+
+#[cfg(feature = "tokio")]
+async fn is_breached_tokio(
+    dataset_path: &Path,
+    range: &RangeInclusive<u32>,
+    prefilter: Option<&Prefilter>,
+    cache: Option<(&PrefixCache, u64)>,
+    layout: RecordLayout,
+    password: &str,
+) -> Result<bool, Error> {
+    let hash = sha1(password);
+    if ruled_out(range, prefilter, &hash)? {
+        return Ok(false);
+    }
+    let search_key = search_key(&hash);
+
+    let prefix_hex = BreachChecker::prefix_hex(&hash);
+    let path = build_path(dataset_path, prefix_hex);
+
+    let Some((cache, generation)) = cache else {
+        // Only file I/O goes into spawn_blocking
+        return tokio::task::spawn_blocking(move || {
+            let path = path.as_path();
+            let mut file =
+                File::open(path).map_err(|e| Error::from_open(e, prefix_hex, path.parent()))?;
+            search_file(&mut file, prefix_hex, search_key, layout)
+        })
+        .await
+        .expect("spawn_blocking task panicked");
+    };
+    if let Some(found) = search_cache(cache, generation, layout, prefix_hex, search_key) {
+        return found;
+    }
+    let records = tokio::task::spawn_blocking(move || {
+        let path = path.as_path();
+        let mut file =
+            File::open(path).map_err(|e| Error::from_open(e, prefix_hex, path.parent()))?;
+        read_records_vec(&mut file, prefix_hex)
+    })
+    .await
+    .expect("spawn_blocking task panicked")?;
+    cache.insert(generation, prefix_value(prefix_hex), &records);
+    layout.contains(&records, &search_key)
+}
+
+/// [`read_records`] into a heap buffer that can leave a blocking task.
+#[cfg(feature = "tokio")]
+fn read_records_vec(
+    file: &mut File,
+    prefix_hex: [u8; PREFIX_LEN],
+) -> Result<Vec<[u8; RECORD_SIZE]>, Error> {
+    let mut buf = [0u8; READ_BUF_LEN];
+    Ok(read_records(file, prefix_hex, &mut buf)?.to_vec())
+}
+
+#[cfg(feature = "compio")]
+async fn is_breached_compio(
+    dataset_path: &Path,
+    range: &RangeInclusive<u32>,
+    prefilter: Option<&Prefilter>,
+    cache: Option<(&PrefixCache, u64)>,
+    layout: RecordLayout,
+    password: &str,
+) -> Result<bool, Error> {
+    let hash = sha1(password);
+    if ruled_out(range, prefilter, &hash)? {
+        return Ok(false);
+    }
+    let search_key = search_key(&hash);
+
+    let prefix_hex = BreachChecker::prefix_hex(&hash);
+    if let Some((cache, generation)) = cache
+        && let Some(found) = search_cache(cache, generation, layout, prefix_hex, search_key)
+    {
+        return found;
+    }
+    let path = build_path(dataset_path, prefix_hex);
+
+    let file = compio::fs::File::open(path.as_path())
+        .await
+        .map_err(|e| Error::from_open(e, prefix_hex, Some(dataset_path)))?;
+    search_file_compio(&file, prefix_hex, search_key, layout, cache).await
+}
+
+/// compio counterpart of [`search_file`], adding the records to `cache` if given.
+#[cfg(feature = "compio")]
+async fn search_file_compio(
+    file: &compio::fs::File,
+    prefix_hex: [u8; PREFIX_LEN],
+    search_key: [u8; RECORD_SIZE],
+    layout: RecordLayout,
+    cache: Option<(&PrefixCache, u64)>,
+) -> Result<bool, Error> {
+    use compio::io::AsyncReadAt;
+
+    // compio returns the buffer back to us after each operation
+    let mut buf = [0u8; READ_BUF_LEN];
+    let mut total = 0usize;
+
+    loop {
+        let buf_result = file.read_at(buf, total as u64).await;
+        buf = buf_result.1;
+        match buf_result.0 {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
         }
+    }
+
+    // As in read_records, a full buffer means the file was cut short.
+    if total >= READ_BUF_LEN {
+        let len = file.metadata().await?.len();
+        return Err(Error::PrefixFileTooLarge {
+            prefix: prefix_value(prefix_hex),
+            len,
+            max: READ_BUF_LEN,
+        });
+    }
+    check_record_length(prefix_hex, total)?;
 
-        Ok(buf[..total].as_chunks::<RECORD_SIZE>().0.binary_search(&search_key).is_ok())
+    let records = buf[..total].as_chunks::<RECORD_SIZE>().0;
+    if let Some((cache, generation)) = cache {
+        cache.insert(generation, prefix_value(prefix_hex), records);
     }
+    layout.contains(records, &search_key)
 }
 
 #[cfg(test)]
@@ -346,6 +964,32 @@ mod tests {
         assert_eq!(hash[7], 0xB4);
     }
 
+    // Either case → the digest; wrong length, non-hex or non-ASCII characters → None.
+    #[test]
+    fn test_parse_sha1_hex() {
+        let hash = sha1("password123");
+        assert_eq!(
+            parse_sha1_hex("CBFDAC6008F9CAB4083784CBD1874F76618D2A97"),
+            Some(hash)
+        );
+        assert_eq!(
+            parse_sha1_hex("cbfdac6008f9cab4083784cbd1874f76618d2a97"),
+            Some(hash)
+        );
+
+        for bad in [
+            "",
+            "CBFDA",
+            "CBFDAC6008F9CAB4083784CBD1874F76618D2A9",
+            "CBFDAC6008F9CAB4083784CBD1874F76618D2A970",
+            "CBFDAC6008F9CAB4083784CBD1874F76618D2A9G",
+            "+BFDAC6008F9CAB4083784CBD1874F76618D2A97",
+            "CBFDAC6008F9CAB4083784CBD1874F76618D2Aé",
+        ] {
+            assert_eq!(parse_sha1_hex(bad), None, "{bad:?}");
+        }
+    }
+
     #[test]
     #[ignore = "requires HIBP dataset"]
     fn test_breached_password() {
@@ -551,6 +1195,23 @@ mod compio_tests {
         });
     }
 
+    // CBFDA.bin larger than the read buffer → the error carries the file's size, not the
+    // bytes read.
+    #[test]
+    fn test_compio_reports_oversized_file_len() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("CBFDA.bin"), vec![0u8; READ_BUF_LEN + 6]).unwrap();
+
+        compio_runtime::Runtime::new().unwrap().block_on(async {
+            let checker = BreachChecker::new(tmp.path());
+            let err = checker.is_breached_compio("password123").await.unwrap_err();
+            assert!(matches!(
+                err,
+                Error::PrefixFileTooLarge { prefix: 0xCBFDA, len, .. } if len == READ_BUF_LEN as u64 + 6
+            ));
+        });
+    }
+
     #[test]
     #[ignore = "requires HIBP dataset"]
     fn test_compio_matches_sync() {
@@ -0,0 +1,484 @@
+//! In-memory dataset for latency-critical deployments.
+//!
+//! [`MemoryDataset`] loads every prefix file (or a chosen prefix range) into one contiguous
+//! allocation, indexed by a table of 2^20 + 1 record offsets. A lookup is then a table read
+//! and a [`RecordLayout::contains`] search of the prefix (interpolation search for sorted
+//! records, a branch-free Eytzinger descent otherwise) with no syscalls. The full dataset needs
+//! roughly 13 GB of RAM.
+//!
+//! Each prefix gets a few records of headroom so that [`MemoryDataset::update_prefix`] can
+//! usually rewrite it in place after a delta sync. Prefixes that outgrow their slot move to a
+//! small side table.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+use crate::error::prefix_value;
+use crate::search::RecordLayout;
+use crate::{
+    BreachChecker, Error, PREFIX_COUNT, RECORD_SIZE, hash_prefix, open_file_at, search_key, sha1,
+};
+
+/// Marks a prefix whose records live in the overflow table.
+const OVERFLOW: u32 = u32::MAX;
+
+/// Size of a huge page on x86_64 and aarch64 with the default configuration.
+const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+/// The dataset held in memory.
+///
+/// Lookups take `&self` and updates take `&mut self`; share it behind an `RwLock` (or swap
+/// whole datasets through an `Arc`) when updates run alongside lookups.
+pub struct MemoryDataset {
+    records: Region,
+    /// Start of each prefix's slot, in records. Slot `p` spans
+    /// `offsets[p]..offsets[p + 1]`, which includes its headroom.
+    offsets: Box<[u64]>,
+    /// Records currently used in each slot, or [`OVERFLOW`].
+    lens: Box<[u32]>,
+    range: RangeInclusive<u32>,
+    overflow: HashMap<u32, Box<[u8]>>,
+    layout: RecordLayout,
+}
+
+impl MemoryDataset {
+    /// Loads the full dataset with default options.
+    pub fn load(dataset_path: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::builder(dataset_path).build()
+    }
+
+    /// Starts configuring a load of the dataset at `dataset_path`.
+    pub fn builder(dataset_path: impl Into<PathBuf>) -> MemoryDatasetBuilder {
+        MemoryDatasetBuilder {
+            dataset_path: dataset_path.into(),
+            range: 0..=PREFIX_COUNT - 1,
+            huge_pages: false,
+            headroom: 4,
+        }
+    }
+
+    /// The prefixes held in memory.
+    pub fn range(&self) -> RangeInclusive<u32> {
+        self.range.clone()
+    }
+
+    /// The order of the records within each prefix, as read from the dataset.
+    pub fn record_layout(&self) -> RecordLayout {
+        self.layout
+    }
+
+    /// Bytes used by the record allocation, including headroom.
+    pub fn allocated_bytes(&self) -> usize {
+        self.records.len
+    }
+
+    /// Checks if the given password has been found in a data breach.
+    ///
+    /// Fails with [`Error::PrefixNotInRange`] when the password's prefix was not loaded.
+    pub fn is_breached(&self, password: &str) -> Result<bool, Error> {
+        self.contains_hash(&sha1(password))
+    }
+
+    /// Checks a precomputed SHA1 hash.
+    #[inline(always)]
+    pub fn contains_hash(&self, hash: &[u8; 20]) -> Result<bool, Error> {
+        let records = self.prefix_records(hash_prefix(hash))?;
+        self.layout.contains(records.as_chunks().0, &search_key(hash))
+    }
+
+    /// The raw records currently held for `prefix`, in the dataset's
+    /// [record layout](Self::record_layout).
+    pub fn prefix_records(&self, prefix: u32) -> Result<&[u8], Error> {
+        if !self.range.contains(&prefix) {
+            return Err(Error::PrefixNotInRange { prefix });
+        }
+        let len = self.lens[prefix as usize];
+        if len == OVERFLOW {
+            return Ok(&self.overflow[&prefix]);
+        }
+        let start = self.offsets[prefix as usize] as usize * RECORD_SIZE;
+        Ok(&self.records.as_slice()[start..start + len as usize * RECORD_SIZE])
+    }
+
+    /// Replaces the records for one prefix. `records` must be sha1t48 records in the dataset's
+    /// [record layout](Self::record_layout), as in a prefix file.
+    ///
+    /// The records are written in place when they fit the prefix's slot, and otherwise kept
+    /// in a separate allocation.
+    pub fn update_prefix(&mut self, prefix: u32, records: &[u8]) -> Result<(), Error> {
+        if !self.range.contains(&prefix) {
+            return Err(Error::PrefixNotInRange { prefix });
+        }
+        if !records.len().is_multiple_of(RECORD_SIZE) {
+            return Err(Error::CorruptRecordLength { prefix, len: records.len() as u64 });
+        }
+
+        let count = records.len() / RECORD_SIZE;
+        let p = prefix as usize;
+        let capacity = (self.offsets[p + 1] - self.offsets[p]) as usize;
+        if count <= capacity {
+            let start = self.offsets[p] as usize * RECORD_SIZE;
+            self.records.as_mut_slice()[start..start + records.len()].copy_from_slice(records);
+            self.lens[p] = count as u32;
+            self.overflow.remove(&prefix);
+        } else {
+            self.overflow.insert(prefix, records.into());
+            self.lens[p] = OVERFLOW;
+        }
+        Ok(())
+    }
+
+    /// Re-reads the given prefixes from `dataset_path`, e.g. the `changed_prefixes` reported
+    /// by `hibp-sync-client` after a delta sync. Prefixes outside the loaded range are skipped.
+    pub fn update_from_dir(
+        &mut self,
+        dataset_path: &Path,
+        prefixes: impl IntoIterator<Item = u32>,
+    ) -> Result<(), Error> {
+        let dir = open_dir(dataset_path)?;
+        let mut buf = Vec::new();
+        for prefix in prefixes {
+            if !self.range.contains(&prefix) {
+                continue;
+            }
+            buf.clear();
+            open_prefix(&dir, prefix)?.read_to_end(&mut buf)?;
+            self.update_prefix(prefix, &buf)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for MemoryDataset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryDataset")
+            .field("range", &self.range)
+            .field("allocated_bytes", &self.records.len)
+            .field("overflow_prefixes", &self.overflow.len())
+            .finish()
+    }
+}
+
+/// Options for loading a [`MemoryDataset`], created with [`MemoryDataset::builder`].
+#[derive(Debug, Clone)]
+pub struct MemoryDatasetBuilder {
+    dataset_path: PathBuf,
+    range: RangeInclusive<u32>,
+    huge_pages: bool,
+    headroom: u32,
+}
+
+impl MemoryDatasetBuilder {
+    /// Loads only the prefixes in `range` (e.g. `0x00000..=0x7FFFF`). Lookups for other
+    /// prefixes fail with [`Error::PrefixNotInRange`]. Defaults to the full dataset.
+    pub fn prefix_range(mut self, range: RangeInclusive<u32>) -> Self {
+        self.range = *range.start()..=(*range.end()).min(PREFIX_COUNT - 1);
+        self
+    }
+
+    /// Back the records with huge pages to cut TLB misses. Uses explicit `MAP_HUGETLB` pages
+    /// when the system has them reserved and falls back to transparent huge pages otherwise.
+    /// Defaults to `false`.
+    pub fn huge_pages(mut self, huge_pages: bool) -> Self {
+        self.huge_pages = huge_pages;
+        self
+    }
+
+    /// Spare records reserved per prefix for in-place updates. Defaults to 4, about 24 MB
+    /// across the full dataset.
+    pub fn headroom(mut self, records: u32) -> Self {
+        self.headroom = records;
+        self
+    }
+
+    /// Reads every prefix file in the range into memory. A file that grows or shrinks while
+    /// loading fails the load with an I/O error; don't build while a sync is committing.
+    pub fn build(self) -> Result<MemoryDataset, Error> {
+        let dir = open_dir(&self.dataset_path)?;
+
+        // First pass sizes each slot so the whole range fits one allocation.
+        let mut offsets = vec![0u64; PREFIX_COUNT as usize + 1].into_boxed_slice();
+        let mut lens = vec![0u32; PREFIX_COUNT as usize].into_boxed_slice();
+        let mut total = 0u64;
+        for prefix in 0..PREFIX_COUNT {
+            offsets[prefix as usize] = total;
+            if !self.range.contains(&prefix) {
+                continue;
+            }
+            let len = open_prefix(&dir, prefix)?.metadata()?.len();
+            if !len.is_multiple_of(RECORD_SIZE as u64) {
+                return Err(Error::CorruptRecordLength { prefix, len });
+            }
+            let count = len / RECORD_SIZE as u64;
+            lens[prefix as usize] = count as u32;
+            total += count + self.headroom as u64;
+        }
+        offsets[PREFIX_COUNT as usize] = total;
+
+        let mut records = Region::new(total as usize * RECORD_SIZE, self.huge_pages)?;
+        let buf = records.as_mut_slice();
+        for prefix in self.range.clone() {
+            let start = offsets[prefix as usize] as usize * RECORD_SIZE;
+            let len = lens[prefix as usize] as usize * RECORD_SIZE;
+            read_sized(open_prefix(&dir, prefix)?, &mut buf[start..start + len])?;
+        }
+
+        Ok(MemoryDataset {
+            records,
+            offsets,
+            lens,
+            range: self.range,
+            overflow: HashMap::new(),
+            layout: RecordLayout::load(&self.dataset_path)?,
+        })
+    }
+}
+
+fn open_dir(dataset_path: &Path) -> Result<OwnedFd, Error> {
+    let dir = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECTORY | libc::O_CLOEXEC)
+        .open(dataset_path)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::DatasetMissing(dataset_path.to_path_buf()),
+            io::ErrorKind::NotADirectory => Error::NotADirectory(dataset_path.to_path_buf()),
+            _ => Error::Io(e),
+        })?;
+    Ok(dir.into())
+}
+
+/// Fills `buf` from `file`, which was sized before the read. A file that has since shrunk
+/// fails the read and one that has grown is caught by the 1-byte read past the end.
+fn read_sized(mut file: std::fs::File, buf: &mut [u8]) -> io::Result<()> {
+    file.read_exact(buf)?;
+    if file.read(&mut [0u8; 1])? != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "prefix file grew while loading",
+        ));
+    }
+    Ok(())
+}
+
+fn open_prefix(dir: &OwnedFd, prefix: u32) -> Result<std::fs::File, Error> {
+    let mut prefix_hex = [0u8; crate::PREFIX_LEN];
+    prefix_hex.copy_from_slice(format!("{prefix:05X}").as_bytes());
+    debug_assert_eq!(prefix_value(prefix_hex), prefix);
+    open_file_at(dir.as_fd(), prefix_hex).map_err(|e| Error::from_open(e, prefix_hex, None))
+}
+
+/// Anonymous memory mapping holding the records.
+struct Region {
+    ptr: NonNull<u8>,
+    len: usize,
+    map_len: usize,
+}
+
+// SAFETY: Region owns its mapping exclusively, like a Box<[u8]>.
+unsafe impl Send for Region {}
+unsafe impl Sync for Region {}
+
+impl Region {
+    fn new(len: usize, huge_pages: bool) -> io::Result<Self> {
+        if len == 0 {
+            return Ok(Self { ptr: NonNull::dangling(), len: 0, map_len: 0 });
+        }
+
+        if huge_pages {
+            let map_len = len.next_multiple_of(HUGE_PAGE_SIZE);
+            if let Ok(ptr) = map(map_len, libc::MAP_HUGETLB) {
+                return Ok(Self { ptr, len, map_len });
+            }
+        }
+
+        let ptr = map(len, 0)?;
+        if huge_pages {
+            // Best effort: THP may be disabled, in which case this is a no-op.
+            unsafe { libc::madvise(ptr.as_ptr().cast(), len, libc::MADV_HUGEPAGE) };
+        }
+        Ok(Self { ptr, len, map_len: len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: ptr is valid for len bytes (or dangling with len 0) for the life of self.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: as above, and &mut self guarantees exclusive access.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+fn map(len: usize, flags: libc::c_int) -> io::Result<NonNull<u8>> {
+    // SAFETY: anonymous private mapping with no address hint.
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(NonNull::new(ptr.cast()).expect("mmap returned null"))
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        if self.map_len != 0 {
+            // SAFETY: ptr/map_len describe a mapping created by `map`.
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.map_len) };
+        }
+    }
+}
+
+impl BreachChecker<'_> {
+    /// Loads this checker's dataset into memory; see [`MemoryDataset::load`].
+    pub fn load_into_memory(&self) -> Result<MemoryDataset, Error> {
+        MemoryDataset::load(self.dataset_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGE: RangeInclusive<u32> = 0xCBFD0..=0xCBFDF;
+
+    // Writes empty prefix files for RANGE, with "password123" in CBFDA.bin.
+    fn write_range(dir: &Path) {
+        for prefix in RANGE {
+            std::fs::write(dir.join(format!("{prefix:05X}.bin")), b"").unwrap();
+        }
+        let hash = sha1("password123");
+        std::fs::write(dir.join("CBFDA.bin"), &hash[2..8]).unwrap();
+    }
+
+    fn load(dir: &Path) -> MemoryDataset {
+        MemoryDataset::builder(dir).prefix_range(RANGE).build().unwrap()
+    }
+
+    #[test]
+    fn lookups_match_disk() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        let dataset = load(tmp.path());
+
+        assert!(dataset.is_breached("password123").unwrap());
+        // Default headroom: 4 spare records per loaded prefix.
+        assert_eq!(dataset.allocated_bytes(), (1 + 16 * 4) * RECORD_SIZE);
+    }
+
+    #[test]
+    fn prefix_outside_range_is_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        let dataset = load(tmp.path());
+
+        let prefix = hash_prefix(&sha1("hAwT?}cuC:r#kW5"));
+        assert!(!RANGE.contains(&prefix));
+        let err = dataset.is_breached("hAwT?}cuC:r#kW5").unwrap_err();
+        assert!(matches!(err, Error::PrefixNotInRange { prefix: p } if p == prefix));
+    }
+
+    #[test]
+    fn missing_or_corrupt_file_fails_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        std::fs::write(tmp.path().join("CBFD3.bin"), [0u8; 5]).unwrap();
+        let err = MemoryDataset::builder(tmp.path()).prefix_range(RANGE).build().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CorruptRecordLength { prefix: 0xCBFD3, len: 5 }
+        ));
+
+        std::fs::remove_file(tmp.path().join("CBFD3.bin")).unwrap();
+        let err = MemoryDataset::builder(tmp.path()).prefix_range(RANGE).build().unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { prefix: 0xCBFD3 }));
+    }
+
+    // File at its sized length → read; grown or shrunk since it was sized → error instead of
+    // a silently truncated or short slot.
+    #[test]
+    fn resized_file_fails_read() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("CBFDA.bin");
+        std::fs::write(&path, [7u8; 2 * RECORD_SIZE]).unwrap();
+        let open = || std::fs::File::open(&path).unwrap();
+
+        let mut buf = [0u8; 2 * RECORD_SIZE];
+        read_sized(open(), &mut buf).unwrap();
+        assert_eq!(buf, [7u8; 2 * RECORD_SIZE]);
+
+        let err = read_sized(open(), &mut [0u8; RECORD_SIZE]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_sized(open(), &mut [0u8; 3 * RECORD_SIZE]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    // Update within headroom → in place; update past headroom → overflow; shrink back → in
+    // place again.
+    #[test]
+    fn update_prefix_in_place_and_overflow() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        let mut dataset = MemoryDataset::builder(tmp.path())
+            .prefix_range(RANGE)
+            .headroom(1)
+            .build()
+            .unwrap();
+        let key = sha1("password123");
+        let record = &key[2..8];
+
+        dataset.update_prefix(0xCBFDA, &[]).unwrap();
+        assert!(!dataset.is_breached("password123").unwrap());
+
+        let grown: Vec<u8> = [[0u8; 6].as_slice(), record, &[0xFF; 6]].concat();
+        dataset.update_prefix(0xCBFDA, &grown).unwrap();
+        assert!(dataset.overflow.contains_key(&0xCBFDA));
+        assert!(dataset.is_breached("password123").unwrap());
+
+        dataset.update_prefix(0xCBFDA, record).unwrap();
+        assert!(dataset.overflow.is_empty());
+        assert!(dataset.is_breached("password123").unwrap());
+        // Neighbouring slots are untouched.
+        assert_eq!(dataset.prefix_records(0xCBFDB).unwrap(), &[] as &[u8]);
+
+        let err = dataset.update_prefix(0xCBFDA, &[0u8; 7]).unwrap_err();
+        assert!(matches!(err, Error::CorruptRecordLength { .. }));
+    }
+
+    #[test]
+    fn update_from_dir_rereads_changed_prefixes() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        let mut dataset = load(tmp.path());
+
+        std::fs::write(tmp.path().join("CBFDA.bin"), b"").unwrap();
+        dataset.update_from_dir(tmp.path(), [0x00000, 0xCBFDA]).unwrap();
+        assert!(!dataset.is_breached("password123").unwrap());
+    }
+
+    #[test]
+    fn huge_pages_fall_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_range(tmp.path());
+        let dataset = MemoryDataset::builder(tmp.path())
+            .prefix_range(RANGE)
+            .huge_pages(true)
+            .build()
+            .unwrap();
+        assert!(dataset.is_breached("password123").unwrap());
+    }
+}
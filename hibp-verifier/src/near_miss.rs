@@ -0,0 +1,323 @@
+//! Detection of passwords that are a small edit away from a breached one.
+//!
+//! Users get past a plain breach check with `Password1!` or `p4ssw0rd`. A
+//! [`NearMissChecker`] also checks a bounded set of normalized variants of the password
+//! (trailing digits and symbols stripped, leetspeak undone, case changed) and reports the
+//! first breached variant and the [`Transformation`]s that produced it.
+//!
+//! The password and its variants go through one [`OwnedBreachChecker::contains_hashes`]
+//! batch, so each prefix file is read once. Files are read in the order variants are
+//! generated, and once [`NearMissConfig::budget`] has passed the remaining variants are left
+//! unchecked.
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::near_miss::{NearMiss, NearMissChecker, NearMissConfig};
+//!
+//! let checker = NearMissChecker::new(
+//!     OwnedBreachChecker::open("/path/to/hibp-data")?,
+//!     NearMissConfig::default(),
+//! );
+//! if let NearMiss::Variant { variant, transformations } = checker.check("P4ssw0rd2026!")? {
+//!     println!("close to breached {variant:?} via {transformations:?}");
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::{Error, OwnedBreachChecker, hash_prefix, sha1};
+
+/// Near-miss options. `NearMissConfig::default()` checks up to 11 variants (every
+/// combination of transformations) within 5ms.
+#[derive(Debug, Clone)]
+pub struct NearMissConfig {
+    /// Maximum number of variants checked besides the password itself.
+    pub max_variants: usize,
+    /// Time after which no further prefix files are read. The password's own prefix file is
+    /// always read.
+    pub budget: Duration,
+}
+
+impl Default for NearMissConfig {
+    fn default() -> Self {
+        Self { max_variants: 11, budget: Duration::from_millis(5) }
+    }
+}
+
+/// A normalizing edit applied to the password, in the order they are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transformation {
+    /// Removed trailing digits and symbols, e.g. `password1!` → `password`.
+    StripSuffix,
+    /// Replaced common leetspeak characters, e.g. `p4$$w0rd` → `password`.
+    UndoLeetspeak,
+    /// Lowercased every letter, e.g. `PassWord` → `password`.
+    Lowercase,
+    /// Uppercased the first letter and lowercased the rest, e.g. `pASSWORD` → `Password`.
+    Capitalize,
+}
+
+/// Result of a [`NearMissChecker::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NearMiss {
+    /// The password itself is breached.
+    Exact,
+    /// `variant`, produced from the password by `transformations`, is breached. Variants with
+    /// fewer transformations are reported first.
+    Variant {
+        variant: String,
+        transformations: Vec<Transformation>,
+    },
+    /// Neither the password nor any checked variant is breached. `unchecked` variants were
+    /// skipped because the budget ran out or, on a sharded checker, because they fall outside
+    /// its prefix range.
+    Clean { unchecked: usize },
+}
+
+impl NearMiss {
+    /// Whether the password or one of its variants is breached.
+    pub fn is_breached(&self) -> bool {
+        !matches!(self, Self::Clean { .. })
+    }
+}
+
+/// Checks passwords and their normalized variants against a dataset.
+#[derive(Debug, Clone)]
+pub struct NearMissChecker {
+    checker: OwnedBreachChecker,
+    config: NearMissConfig,
+}
+
+impl NearMissChecker {
+    pub fn new(checker: OwnedBreachChecker, config: NearMissConfig) -> Self {
+        Self { checker, config }
+    }
+
+    /// The wrapped checker.
+    pub fn checker(&self) -> &OwnedBreachChecker {
+        &self.checker
+    }
+
+    /// Checks `password`, then its variants.
+    ///
+    /// Fails if any lookup made within the budget fails, or with [`Error::PrefixNotInRange`]
+    /// if the password itself is outside a sharded checker's range.
+    pub fn check(&self, password: &str) -> Result<NearMiss, Error> {
+        let prefix = hash_prefix(&sha1(password));
+        if !self.checker.prefix_range().contains(&prefix) {
+            return Err(Error::PrefixNotInRange { prefix });
+        }
+        let deadline = Instant::now() + self.config.budget;
+        let variants = variants(password, self.config.max_variants);
+        let hashes: Vec<[u8; 20]> = std::iter::once(password)
+            .chain(variants.iter().map(|(variant, _)| variant.as_str()))
+            .map(sha1)
+            .collect();
+
+        let found = self.checker.contains_hashes_until(&hashes, Some(deadline))?;
+        if found[0] == Some(true) {
+            return Ok(NearMiss::Exact);
+        }
+        let mut unchecked = 0;
+        for ((variant, transformations), found) in variants.into_iter().zip(&found[1..]) {
+            match found {
+                Some(true) => return Ok(NearMiss::Variant { variant, transformations }),
+                Some(false) => {}
+                None => unchecked += 1,
+            }
+        }
+        Ok(NearMiss::Clean { unchecked })
+    }
+}
+
+/// Up to `max` distinct variants of `password`, excluding `password` itself, ordered by the
+/// number of transformations applied.
+fn variants(password: &str, max: usize) -> Vec<(String, Vec<Transformation>)> {
+    let case_changes = [
+        None,
+        Some(Transformation::Lowercase),
+        Some(Transformation::Capitalize),
+    ];
+    let mut plans: Vec<Vec<Transformation>> = Vec::new();
+    for strip in [false, true] {
+        for unleet in [false, true] {
+            for case in case_changes {
+                let plan = [
+                    strip.then_some(Transformation::StripSuffix),
+                    unleet.then_some(Transformation::UndoLeetspeak),
+                    case,
+                ];
+                plans.push(plan.into_iter().flatten().collect());
+            }
+        }
+    }
+    plans.sort_by_key(Vec::len);
+
+    let mut variants: Vec<(String, Vec<Transformation>)> = Vec::new();
+    for plan in plans.into_iter().filter(|plan| !plan.is_empty()) {
+        if variants.len() == max {
+            break;
+        }
+        let variant = plan.iter().fold(password.to_owned(), |s, &t| apply(t, &s));
+        if !variant.is_empty()
+            && variant != password
+            && !variants.iter().any(|(existing, _)| *existing == variant)
+        {
+            variants.push((variant, plan));
+        }
+    }
+    variants
+}
+
+fn apply(transformation: Transformation, s: &str) -> String {
+    match transformation {
+        Transformation::StripSuffix => s
+            .trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation())
+            .to_owned(),
+        Transformation::UndoLeetspeak => s.chars().map(unleet).collect(),
+        Transformation::Lowercase => s.to_lowercase(),
+        Transformation::Capitalize => {
+            let mut chars = s.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_uppercase().chain(chars.as_str().to_lowercase().chars()).collect()
+                }
+                None => String::new(),
+            }
+        }
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use Transformation::*;
+
+    use super::*;
+    use crate::test_util::write_dataset;
+
+    // HIBP stand-in holding "password" and "Dragon", with an empty file for every other
+    // prefix the tests look up.
+    fn checker(dir: &Path, config: NearMissConfig, lookups: &[&str]) -> NearMissChecker {
+        let variant_names: Vec<_> =
+            lookups.iter().flat_map(|p| variants(p, usize::MAX)).map(|(v, _)| v).collect();
+        let clean: Vec<_> = lookups
+            .iter()
+            .copied()
+            .chain(variant_names.iter().map(String::as_str))
+            .collect();
+        write_dataset(dir, &["password", "Dragon"], &clean);
+        NearMissChecker::new(OwnedBreachChecker::open(dir).unwrap(), config)
+    }
+
+    // Variants are distinct, never the password itself, and fewest transformations first.
+    #[test]
+    fn variants_are_bounded_and_ordered() {
+        let all = variants("P4ssw0rd1!", usize::MAX);
+        let names: Vec<_> = all.iter().map(|(v, _)| v.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "p4ssw0rd1!",
+                "Passwordii",
+                "P4ssw0rd",
+                "passwordii",
+                "p4ssw0rd",
+                "Password",
+                "password",
+            ]
+        );
+        assert_eq!(all[0].1, [Lowercase]);
+        assert_eq!(
+            all.last().unwrap().1,
+            [StripSuffix, UndoLeetspeak, Lowercase]
+        );
+        assert_eq!(variants("P4ssw0rd1!", 2).len(), 2);
+        // Nothing to strip and nothing to undo → only case variants.
+        assert_eq!(variants("dragon", usize::MAX).len(), 1);
+        // Stripping everything leaves no variant.
+        assert!(variants("1234!", usize::MAX).iter().all(|(v, _)| !v.is_empty()));
+    }
+
+    #[test]
+    fn reports_exact_variant_and_clean() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lookups = ["password", "P4ssw0rd1!", "DRAGON99", "correct horse"];
+        let checker = checker(tmp.path(), NearMissConfig::default(), &lookups);
+
+        assert_eq!(checker.check("password").unwrap(), NearMiss::Exact);
+        assert_eq!(
+            checker.check("P4ssw0rd1!").unwrap(),
+            NearMiss::Variant {
+                variant: "password".into(),
+                transformations: vec![StripSuffix, UndoLeetspeak, Lowercase],
+            }
+        );
+        assert_eq!(
+            checker.check("DRAGON99").unwrap(),
+            NearMiss::Variant {
+                variant: "Dragon".into(),
+                transformations: vec![StripSuffix, Capitalize]
+            }
+        );
+        let clean = checker.check("correct horse").unwrap();
+        assert_eq!(clean, NearMiss::Clean { unchecked: 0 });
+        assert!(!clean.is_breached());
+    }
+
+    // A zero budget → only the password's own prefix file is read and the other variants
+    // are reported unchecked.
+    #[test]
+    fn budget_limits_variants_checked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = NearMissConfig { budget: Duration::ZERO, ..NearMissConfig::default() };
+        let checker = checker(tmp.path(), config, &["P4ssw0rd1!"]);
+
+        let NearMiss::Clean { unchecked } = checker.check("P4ssw0rd1!").unwrap() else {
+            panic!("expected Clean");
+        };
+        assert!(unchecked > 0);
+    }
+
+    // Checker limited to the password's own prefix → variants in other prefixes, including
+    // the breached "password", are unchecked rather than an error; a password outside the
+    // range → PrefixNotInRange.
+    #[test]
+    fn sharded_checker_skips_out_of_range_variants() {
+        let tmp = tempfile::tempdir().unwrap();
+        checker(tmp.path(), NearMissConfig::default(), &["P4ssw0rd1!"]);
+        let prefix = hash_prefix(&sha1("P4ssw0rd1!"));
+        let sharded = OwnedBreachChecker::builder(tmp.path())
+            .prefix_range(prefix..=prefix)
+            .build()
+            .unwrap();
+        let checker = NearMissChecker::new(sharded, NearMissConfig::default());
+
+        let outside = variants("P4ssw0rd1!", usize::MAX)
+            .iter()
+            .filter(|(variant, _)| hash_prefix(&sha1(variant)) != prefix)
+            .count();
+        assert!(outside > 0);
+        assert_eq!(
+            checker.check("P4ssw0rd1!").unwrap(),
+            NearMiss::Clean { unchecked: outside }
+        );
+        assert!(matches!(
+            checker.check("password"),
+            Err(Error::PrefixNotInRange { .. })
+        ));
+    }
+}
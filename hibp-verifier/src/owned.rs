@@ -0,0 +1,772 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use std::os::fd::BorrowedFd;
+#[cfg(any(feature = "compio", test))]
+use std::os::fd::AsRawFd;
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::cache::PrefixCache;
+use crate::prefilter::Prefilter;
+use crate::search::RecordLayout;
+use crate::{
+    BreachChecker, Error, PREFIX_COUNT, PREFIX_LEN, READ_BUF_LEN, check_record_length, hash_prefix,
+    open_file_at, prefix_to_hex, read_records, ruled_out, search_cached, search_key, sha1,
+    validate_range_layout_at,
+};
+
+/// A breach checker that owns an open handle to its dataset directory.
+///
+/// Being `'static`, it can be stored in an `Arc`, in web framework app state, or moved into
+/// spawned tasks without leaking the path. Prefix files are opened with `openat` relative to
+/// the directory handle, so each lookup resolves only `XXXXX.bin` instead of walking the full
+/// dataset path, and there is no limit on the dataset path length.
+///
+/// The directory is resolved once, when the checker is built. If the dataset path is a
+/// symlink (e.g. `<data-dir>/current` from `hibp-sync-client --keep-versions`), the checker
+/// keeps reading the version it was built on after the link is swapped. Use
+/// `reload::ReloadingChecker` (`reload` feature) to follow updates.
+#[derive(Debug, Clone)]
+pub struct OwnedBreachChecker {
+    dataset_path: PathBuf,
+    dir: Arc<OwnedFd>,
+    prefilter: Option<Arc<Prefilter>>,
+    range: RangeInclusive<u32>,
+    cache: Option<(Arc<PrefixCache>, u64)>,
+    layout: RecordLayout,
+}
+
+impl OwnedBreachChecker {
+    /// Opens the dataset directory with default options, validating its layout.
+    ///
+    /// There is no infallible `new`: the checker holds the directory open from the start.
+    pub fn open(dataset_path: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::builder(dataset_path).build()
+    }
+
+    /// Starts building a checker for the given dataset directory.
+    pub fn builder(dataset_path: impl Into<PathBuf>) -> BreachCheckerBuilder {
+        BreachCheckerBuilder {
+            dataset_path: dataset_path.into(),
+            validate_layout: true,
+            prefilter: false,
+            range: 0..=PREFIX_COUNT - 1,
+        }
+    }
+
+    /// The dataset directory this checker was opened from.
+    pub fn dataset_path(&self) -> &Path {
+        &self.dataset_path
+    }
+
+    /// Borrows this checker as a [`BreachChecker`] with the same pre-filter, prefix range,
+    /// cache and record layout. The borrowed checker opens prefix files by full path.
+    pub fn as_checker(&self) -> BreachChecker<'_> {
+        BreachChecker {
+            dataset_path: &self.dataset_path,
+            prefilter: self.prefilter(),
+            range: self.range.clone(),
+            cache: self.bound_cache(),
+            layout: self.layout,
+        }
+    }
+
+    /// The pre-filter consulted before each lookup, if one was loaded.
+    pub fn prefilter(&self) -> Option<&Prefilter> {
+        self.prefilter.as_deref()
+    }
+
+    /// The prefixes this checker answers for. Others fail with [`Error::PrefixNotInRange`].
+    pub fn prefix_range(&self) -> &RangeInclusive<u32> {
+        &self.range
+    }
+
+    /// The order of the records in this dataset's prefix files, read from its
+    /// [`LAYOUT_FILE`](crate::search::LAYOUT_FILE) when the checker was built.
+    pub fn record_layout(&self) -> RecordLayout {
+        self.layout
+    }
+
+    /// Searches prefix files held in `cache` instead of reading them, and adds the files it
+    /// reads. The checker is bound to the cache's current generation; see
+    /// [`cache`](crate::cache). Clones share the cache.
+    pub fn with_cache(mut self, cache: Arc<PrefixCache>) -> Self {
+        let generation = cache.generation();
+        self.cache = Some((cache, generation));
+        self
+    }
+
+    /// The prefix cache this checker uses, if one was attached.
+    pub fn cache(&self) -> Option<&PrefixCache> {
+        self.cache.as_ref().map(|(cache, _)| &**cache)
+    }
+
+    fn bound_cache(&self) -> Option<(&PrefixCache, u64)> {
+        self.cache.as_ref().map(|(cache, generation)| (&**cache, *generation))
+    }
+
+    /// Checks if the given password has been found in a data breach.
+    ///
+    /// See [`BreachChecker::is_breached`].
+    pub fn is_breached(&self, password: &str) -> Result<bool, Error> {
+        self.contains_hash(&sha1(password))
+    }
+
+    /// [`is_breached`](Self::is_breached) for a SHA-1 hash computed elsewhere, e.g. one a
+    /// client sent instead of the password. Only the first 8 bytes are compared, as that is
+    /// all the dataset stores.
+    pub fn contains_hash(&self, hash: &[u8; 20]) -> Result<bool, Error> {
+        if ruled_out(&self.range, self.prefilter(), hash)? {
+            return Ok(false);
+        }
+        let prefix_hex = BreachChecker::prefix_hex(hash);
+        search_cached(
+            self.bound_cache(),
+            self.layout,
+            prefix_hex,
+            search_key(hash),
+            || self.open_file(prefix_hex).map_err(|e| Error::from_open(e, prefix_hex, None)),
+        )
+    }
+
+    /// [`contains_hash`](Self::contains_hash) for several hashes, reading each prefix file
+    /// once however many of the hashes share it. Results are in the order of `hashes`.
+    pub fn contains_hashes(&self, hashes: &[[u8; 20]]) -> Result<Vec<bool>, Error> {
+        if let Some(prefix) = hashes.iter().map(hash_prefix).find(|p| !self.range.contains(p)) {
+            return Err(Error::PrefixNotInRange { prefix });
+        }
+        let found = self.contains_hashes_until(hashes, None)?;
+        Ok(found.into_iter().map(|found| found.expect("no deadline was set")).collect())
+    }
+
+    /// Batch lookup that stops opening prefix files once `deadline` has passed. Hashes that
+    /// were not looked up, because of the deadline or because they are outside the prefix
+    /// range, are `None`. Prefix files are visited in order of their first hash in `hashes`,
+    /// and the first in range is always read.
+    pub(crate) fn contains_hashes_until(
+        &self,
+        hashes: &[[u8; 20]],
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Option<bool>>, Error> {
+        let mut found = vec![None; hashes.len()];
+        let mut order: Vec<usize> = (0..hashes.len()).collect();
+        order.sort_unstable_by_key(|&i| (hash_prefix(&hashes[i]), i));
+        let mut groups: Vec<&[usize]> = order
+            .chunk_by(|&a, &b| hash_prefix(&hashes[a]) == hash_prefix(&hashes[b]))
+            .collect();
+        groups.retain(|group| self.range.contains(&hash_prefix(&hashes[group[0]])));
+        // Each group is sorted by index, so its first entry is the earliest hash.
+        groups.sort_unstable_by_key(|group| group[0]);
+
+        let mut buf = [0u8; READ_BUF_LEN];
+        for (n, group) in groups.into_iter().enumerate() {
+            if n > 0 && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+            let mut pending = Vec::with_capacity(group.len());
+            for &i in group {
+                if ruled_out(&self.range, self.prefilter(), &hashes[i])? {
+                    found[i] = Some(false);
+                } else {
+                    pending.push(i);
+                }
+            }
+            let Some(&first) = pending.first() else {
+                continue;
+            };
+            let prefix_hex = BreachChecker::prefix_hex(&hashes[first]);
+            let prefix = hash_prefix(&hashes[first]);
+            let cached =
+                self.bound_cache().and_then(|(cache, generation)| cache.get(generation, prefix));
+            let records = match &cached {
+                Some(records) => &records[..],
+                None => {
+                    let mut file = self
+                        .open_file(prefix_hex)
+                        .map_err(|e| Error::from_open(e, prefix_hex, None))?;
+                    let records = read_records(&mut file, prefix_hex, &mut buf)?;
+                    if let Some((cache, generation)) = self.bound_cache() {
+                        cache.insert(generation, prefix, records);
+                    }
+                    records
+                }
+            };
+            for i in pending {
+                found[i] = Some(self.layout.contains(records, &search_key(&hashes[i]))?);
+            }
+        }
+        Ok(found)
+    }
+
+    /// The raw sha1t48 records in `prefix`'s file, in ascending order.
+    pub fn prefix_records(&self, prefix: u32) -> Result<Vec<u8>, Error> {
+        if !self.range.contains(&prefix) {
+            return Err(Error::PrefixNotInRange { prefix });
+        }
+        let prefix_hex = prefix_to_hex(prefix);
+        let mut file =
+            self.open_file(prefix_hex).map_err(|e| Error::from_open(e, prefix_hex, None))?;
+        let mut records = Vec::new();
+        file.read_to_end(&mut records)?;
+        check_record_length(prefix_hex, records.len())?;
+        if self.layout != RecordLayout::Sorted {
+            records = self.layout.to_sorted(records.as_chunks().0).concat();
+        }
+        Ok(records)
+    }
+
+    /// The dataset directory handle prefix files are opened relative to.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub(crate) fn dir_fd(&self) -> BorrowedFd<'_> {
+        self.dir.as_fd()
+    }
+
+    /// Opens a prefix file relative to the dataset directory handle.
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn open_file(&self, prefix_hex: [u8; PREFIX_LEN]) -> io::Result<File> {
+        open_file_at(self.dir.as_fd(), prefix_hex)
+    }
+
+    /// A path to a prefix file through `/proc/self/fd`, which the kernel resolves relative to
+    /// the directory handle as `openat` does, for async runtimes that only open by path.
+    #[cfg(any(feature = "compio", test))]
+    fn fd_path(&self, prefix_hex: [u8; PREFIX_LEN]) -> PathBuf {
+        let name = std::str::from_utf8(&prefix_hex).expect("prefix hex is ASCII");
+        PathBuf::from(format!("/proc/self/fd/{}/{name}.bin", self.dir.as_raw_fd()))
+    }
+
+    /// Async version of `is_breached` using tokio.
+    ///
+    /// See [`BreachChecker::is_breached_async`].
+    #[cfg(feature = "tokio")]
+    pub async fn is_breached_async(&self, password: &str) -> Result<bool, Error> {
+        let hash = sha1(password);
+        if ruled_out(&self.range, self.prefilter(), &hash)? {
+            return Ok(false);
+        }
+        let search_key = search_key(&hash);
+        let prefix_hex = BreachChecker::prefix_hex(&hash);
+        let layout = self.layout;
+
+        // The blocking task holds its own reference to the directory so the fd stays open
+        // even if this future is dropped before the task runs.
+        let dir = self.dir.clone();
+        let Some((cache, generation)) = self.bound_cache() else {
+            return tokio::task::spawn_blocking(move || {
+                let mut file = open_file_at(dir.as_fd(), prefix_hex)
+                    .map_err(|e| Error::from_open(e, prefix_hex, None))?;
+                crate::search_file(&mut file, prefix_hex, search_key, layout)
+            })
+            .await
+            .expect("spawn_blocking task panicked");
+        };
+        if let Some(found) = crate::search_cache(cache, generation, layout, prefix_hex, search_key)
+        {
+            return found;
+        }
+        let records = tokio::task::spawn_blocking(move || {
+            let mut file = open_file_at(dir.as_fd(), prefix_hex)
+                .map_err(|e| Error::from_open(e, prefix_hex, None))?;
+            crate::read_records_vec(&mut file, prefix_hex)
+        })
+        .await
+        .expect("spawn_blocking task panicked")?;
+        cache.insert(generation, hash_prefix(&hash), &records);
+        layout.contains(&records, &search_key)
+    }
+
+    /// Async version of `is_breached` using compio's native io-uring file I/O.
+    ///
+    /// Like the borrowed checker, the open and the reads both go through io-uring. The file
+    /// is opened by its `/proc/self/fd` path, so it still comes from the pinned directory.
+    ///
+    /// See [`BreachChecker::is_breached_compio`].
+    #[cfg(feature = "compio")]
+    pub async fn is_breached_compio(&self, password: &str) -> Result<bool, Error> {
+        let hash = sha1(password);
+        if ruled_out(&self.range, self.prefilter(), &hash)? {
+            return Ok(false);
+        }
+        let prefix_hex = BreachChecker::prefix_hex(&hash);
+        if let Some((cache, generation)) = self.bound_cache()
+            && let Some(found) = crate::search_cache(
+                cache,
+                generation,
+                self.layout,
+                prefix_hex,
+                search_key(&hash),
+            )
+        {
+            return found;
+        }
+
+        let file = compio::fs::File::open(self.fd_path(prefix_hex))
+            .await
+            .map_err(|e| Error::from_open(e, prefix_hex, None))?;
+        let (search_key, cache) = (search_key(&hash), self.bound_cache());
+        crate::search_file_compio(&file, prefix_hex, search_key, self.layout, cache).await
+    }
+}
+
+/// Options for an [`OwnedBreachChecker`], created with [`OwnedBreachChecker::builder`].
+#[derive(Debug, Clone)]
+pub struct BreachCheckerBuilder {
+    pub(crate) dataset_path: PathBuf,
+    validate_layout: bool,
+    prefilter: bool,
+    range: RangeInclusive<u32>,
+}
+
+impl BreachCheckerBuilder {
+    /// Whether [`build`](Self::build) runs [`validate_layout`](crate::validate_layout) on the
+    /// dataset, checking the first and last files of the [prefix range](Self::prefix_range).
+    /// Defaults to `true`. Disable it for partial datasets that lack those files.
+    pub fn validate_layout(mut self, validate_layout: bool) -> Self {
+        self.validate_layout = validate_layout;
+        self
+    }
+
+    /// Former switch for the up-front directory check.
+    ///
+    /// [`build`](Self::build) now opens the directory, so it must exist either way. This
+    /// only sets [`validate_layout`](Self::validate_layout).
+    #[deprecated(note = "the directory is opened by build(); use validate_layout")]
+    pub fn verify_dir(self, verify_dir: bool) -> Self {
+        self.validate_layout(verify_dir)
+    }
+
+    /// Whether [`build`](Self::build) loads the dataset's
+    /// [`PREFILTER_FILE`](crate::prefilter::PREFILTER_FILE) and consults it before each
+    /// lookup. Defaults to `false`. Building fails if the file is missing or corrupt.
+    pub fn prefilter(mut self, prefilter: bool) -> Self {
+        self.prefilter = prefilter;
+        self
+    }
+
+    /// Limits the checker to the prefixes in `range`, for a dataset synced with
+    /// `hibp-sync-client --shard`. Lookups outside it fail with [`Error::PrefixNotInRange`].
+    /// Defaults to all prefixes.
+    pub fn prefix_range(mut self, range: RangeInclusive<u32>) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Opens the dataset directory and reads its [record layout](crate::search::RecordLayout),
+    /// failing if it does not exist, is not a directory, or (unless disabled) does not pass
+    /// [`validate_layout`](crate::validate_layout).
+    pub fn build(self) -> Result<OwnedBreachChecker, Error> {
+        let dir = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_CLOEXEC)
+            .open(&self.dataset_path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => Error::DatasetMissing(self.dataset_path.clone()),
+                io::ErrorKind::NotADirectory => Error::NotADirectory(self.dataset_path.clone()),
+                _ => Error::Io(e),
+            })?;
+        // Everything below is read through `dir`, so it all comes from the directory the
+        // checker pins even if a `current` symlink is repointed while building.
+        if self.validate_layout {
+            validate_range_layout_at(dir.as_fd(), &self.range)?;
+        }
+        let layout = RecordLayout::load_at(dir.as_fd())?;
+        let prefilter = match self.prefilter {
+            true => Some(Arc::new(Prefilter::load_at(dir.as_fd())?)),
+            false => None,
+        };
+        Ok(OwnedBreachChecker {
+            dataset_path: self.dataset_path,
+            dir: Arc::new(dir.into()),
+            prefilter,
+            range: self.range,
+            cache: None,
+            layout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::READ_BUF_LEN;
+    use crate::test_util::write_dataset;
+
+    #[test]
+    fn owned_matches_borrowed() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let owned = OwnedBreachChecker::open(tmp.path()).unwrap();
+        let borrowed = BreachChecker::open(tmp.path()).unwrap();
+
+        assert!(owned.is_breached("password123").unwrap());
+        assert!(borrowed.is_breached("password123").unwrap());
+        assert!(owned.as_checker().is_breached("password123").unwrap());
+    }
+
+    // Repeated lookups, single and batched, read CBFDA.bin once; a cache attached before
+    // invalidation is bypassed.
+    #[test]
+    fn cache_serves_repeated_lookups() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let cache = Arc::new(PrefixCache::new(1 << 20));
+        let checker = OwnedBreachChecker::open(tmp.path()).unwrap().with_cache(cache.clone());
+        let mut near = sha1("password123");
+        near[7] ^= 1;
+
+        assert!(checker.is_breached("password123").unwrap());
+        assert!(!checker.contains_hash(&near).unwrap());
+        assert_eq!(
+            checker.contains_hashes(&[near, sha1("password123")]).unwrap(),
+            [false, true]
+        );
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+        assert_eq!(stats.bytes, 6 + crate::cache::ENTRY_OVERHEAD);
+
+        let borrowed = BreachChecker::new(tmp.path()).with_cache(&cache);
+        assert!(borrowed.is_breached("password123").unwrap());
+        assert_eq!(cache.stats().hits, 3);
+
+        cache.invalidate();
+        assert!(checker.is_breached("password123").unwrap());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    // CBFDA.bin rewritten in Eytzinger order with a layout marker → lookups still find
+    // "password123" and prefix_records comes back ascending; `BreachChecker::new`, which
+    // assumes sorted records, fails instead of reporting it clean.
+    #[test]
+    fn eytzinger_dataset() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let key = search_key(&sha1("password123"));
+        let mut sorted: Vec<[u8; 6]> = (0..100u8)
+            .map(|i| [key[0], key[1], i.wrapping_mul(37), i, 0, 0])
+            .chain([key])
+            .collect();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let tree = crate::search::to_eytzinger(&sorted);
+        std::fs::write(tmp.path().join("CBFDA.bin"), tree.concat()).unwrap();
+        RecordLayout::Eytzinger.write(tmp.path()).unwrap();
+
+        let checker = OwnedBreachChecker::open(tmp.path()).unwrap();
+        assert_eq!(checker.record_layout(), RecordLayout::Eytzinger);
+        assert!(checker.is_breached("password123").unwrap());
+        let mut near = sha1("password123");
+        near[7] ^= 1;
+        assert!(!checker.contains_hash(&near).unwrap());
+        assert_eq!(checker.prefix_records(0xCBFDA).unwrap(), sorted.concat());
+        assert!(BreachChecker::open(tmp.path()).unwrap().is_breached("password123").unwrap());
+        let err = BreachChecker::new(tmp.path()).is_breached("password123").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::RecordLayoutMismatch(RecordLayout::Sorted)
+        ));
+    }
+
+    // Dataset moved aside and replaced at its path → the /proc/self/fd path compio opens still
+    // reaches the pinned directory's file, and a missing file is still reported as missing.
+    #[test]
+    fn fd_path_resolves_through_directory_handle() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("current");
+        write_dataset(&path, &["password123"], &[]);
+        let checker = OwnedBreachChecker::open(&path).unwrap();
+        std::fs::rename(&path, tmp.path().join("old")).unwrap();
+        write_dataset(&path, &[], &[]);
+
+        let hash = sha1("password123");
+        let records = std::fs::read(checker.fd_path(*b"CBFDA")).unwrap();
+        assert_eq!(records, &hash[2..8]);
+        let err = File::open(checker.fd_path(*b"12345")).unwrap_err();
+        assert!(matches!(
+            Error::from_open(err, *b"12345", None),
+            Error::PrefixFileMissing { prefix: 0x12345 }
+        ));
+    }
+
+    // Directory opened, then moved aside and replaced at its path by a sorted, unfiltered
+    // dataset missing FFFFF.bin → layout, filter and sentinels still come from the open one.
+    #[test]
+    fn metadata_read_through_directory_handle() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("current");
+        write_dataset(&path, &["password123"], &[]);
+        RecordLayout::Eytzinger.write(&path).unwrap();
+        let filter = Prefilter::build(&path, Some("v1")).unwrap();
+        filter.write(&path.join(crate::prefilter::PREFILTER_FILE)).unwrap();
+        let dir = File::open(&path).unwrap();
+
+        std::fs::rename(&path, tmp.path().join("old")).unwrap();
+        write_dataset(&path, &[], &[]);
+        std::fs::remove_file(path.join("FFFFF.bin")).unwrap();
+
+        assert_eq!(
+            RecordLayout::load_at(dir.as_fd()).unwrap(),
+            RecordLayout::Eytzinger
+        );
+        let loaded = Prefilter::load_at(dir.as_fd()).unwrap();
+        assert_eq!(loaded.dataset_version(), Some("v1"));
+        validate_range_layout_at(dir.as_fd(), &(0..=PREFIX_COUNT - 1)).unwrap();
+        assert!(crate::validate_layout(&path).is_err());
+    }
+
+    #[test]
+    fn open_rejects_missing_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let missing = tmp.path().join("missing");
+        let err = OwnedBreachChecker::open(&missing).unwrap_err();
+        assert!(matches!(err, Error::DatasetMissing(p) if p == missing));
+        assert!(matches!(
+            BreachChecker::open(&missing),
+            Err(Error::DatasetMissing(_))
+        ));
+    }
+
+    #[test]
+    fn open_rejects_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let err = OwnedBreachChecker::open(tmp.path().join("CBFDA.bin")).unwrap_err();
+        assert!(matches!(err, Error::NotADirectory(_)));
+    }
+
+    // Directory without FFFFF.bin → layout rejected unless validation is disabled.
+    #[test]
+    fn open_rejects_incomplete_layout() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        std::fs::remove_file(tmp.path().join("FFFFF.bin")).unwrap();
+
+        let err = OwnedBreachChecker::open(tmp.path()).unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { prefix: 0xFFFFF }));
+
+        let checker =
+            OwnedBreachChecker::builder(tmp.path()).validate_layout(false).build().unwrap();
+        assert!(checker.is_breached("password123").unwrap());
+
+        #[allow(deprecated)]
+        let checker = OwnedBreachChecker::builder(tmp.path()).verify_dir(false).build().unwrap();
+        assert!(checker.is_breached("password123").unwrap());
+    }
+
+    #[test]
+    fn open_rejects_corrupt_sentinel() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        std::fs::write(tmp.path().join("00000.bin"), [0u8; 7]).unwrap();
+
+        let err = OwnedBreachChecker::open(tmp.path()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CorruptRecordLength { prefix: 0, len: 7 }
+        ));
+    }
+
+    // Lookup errors distinguish a missing prefix file, a bad record length, an oversized
+    // file and a missing dataset directory.
+    #[test]
+    fn lookup_errors_are_typed() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let owned = OwnedBreachChecker::open(tmp.path()).unwrap();
+        let borrowed = BreachChecker::open(tmp.path()).unwrap();
+
+        std::fs::remove_file(tmp.path().join("CBFDA.bin")).unwrap();
+        let err = owned.is_breached("password123").unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { prefix: 0xCBFDA }));
+        assert_eq!(err.to_string(), "prefix file CBFDA.bin not found");
+        let err = borrowed.is_breached("password123").unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { prefix: 0xCBFDA }));
+
+        std::fs::write(tmp.path().join("CBFDA.bin"), [0u8; 10]).unwrap();
+        let err = owned.is_breached("password123").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CorruptRecordLength { prefix: 0xCBFDA, len: 10 }
+        ));
+
+        std::fs::write(tmp.path().join("CBFDA.bin"), vec![0u8; READ_BUF_LEN + 6]).unwrap();
+        let err = borrowed.is_breached("password123").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::PrefixFileTooLarge { prefix: 0xCBFDA, .. }
+        ));
+
+        let missing = tmp.path().join("missing");
+        let err = BreachChecker::new(&missing).is_breached("password123").unwrap_err();
+        assert!(err.is_dataset_error());
+    }
+
+    // Dataset path longer than the 512-byte path buffer → both checkers still find the record.
+    #[test]
+    fn long_dataset_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut dir = tmp.path().to_path_buf();
+        while dir.as_os_str().len() <= 600 {
+            dir.push("a".repeat(100));
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        write_dataset(&dir, &["password123"], &[]);
+
+        assert!(OwnedBreachChecker::open(&dir).unwrap().is_breached("password123").unwrap());
+        assert!(BreachChecker::new(&dir).is_breached("password123").unwrap());
+    }
+
+    // The directory handle pins what the path resolved to when the checker was built.
+    #[test]
+    fn directory_handle_survives_rename() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("v1");
+        std::fs::create_dir(&dir).unwrap();
+        write_dataset(&dir, &["password123"], &[]);
+
+        let checker = OwnedBreachChecker::open(&dir).unwrap();
+        std::fs::rename(&dir, tmp.path().join("v2")).unwrap();
+        assert!(checker.is_breached("password123").unwrap());
+    }
+
+    // Password the filter rules out, whose prefix file is absent → unfiltered checkers fail
+    // to open it, filtered ones answer `false` without I/O.
+    #[test]
+    fn prefilter_skips_disk_for_misses() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let filter = Prefilter::build(tmp.path(), None).unwrap();
+        filter.write(&tmp.path().join(crate::prefilter::PREFILTER_FILE)).unwrap();
+
+        let owned = OwnedBreachChecker::builder(tmp.path()).prefilter(true).build().unwrap();
+        let borrowed = BreachChecker::new(tmp.path()).with_prefilter(&filter);
+        assert!(owned.is_breached("password123").unwrap());
+        assert!(borrowed.is_breached("password123").unwrap());
+
+        let miss = "hAwT?}cuC:r#kW5";
+        assert!(!filter.may_contain(&sha1(miss)));
+        assert!(!owned.is_breached(miss).unwrap());
+        assert!(!borrowed.is_breached(miss).unwrap());
+        let err = OwnedBreachChecker::open(tmp.path()).unwrap().is_breached(miss).unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { .. }));
+    }
+
+    #[test]
+    fn prefilter_required_when_enabled() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let err = OwnedBreachChecker::builder(tmp.path()).prefilter(true).build().unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::NotFound));
+    }
+
+    // Records come back as stored; a prefix outside the range or without a file is an error.
+    #[test]
+    fn prefix_records_reads_whole_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let checker = OwnedBreachChecker::open(tmp.path()).unwrap();
+        let hash = sha1("password123");
+
+        assert_eq!(checker.prefix_records(0xCBFDA).unwrap(), &hash[2..8]);
+        assert!(checker.contains_hash(&hash).unwrap());
+        assert!(checker.prefix_records(0x00000).unwrap().is_empty());
+        assert!(matches!(
+            checker.prefix_records(0x12345),
+            Err(Error::PrefixFileMissing { prefix: 0x12345 })
+        ));
+        assert!(matches!(
+            checker.prefix_records(PREFIX_COUNT),
+            Err(Error::PrefixNotInRange { .. })
+        ));
+    }
+
+    // Two hashes sharing CBFDA.bin plus one filtered out → answers in input order; a hash
+    // whose file is missing fails the whole batch.
+    #[test]
+    fn contains_hashes_groups_by_prefix() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let checker = OwnedBreachChecker::open(tmp.path()).unwrap();
+        let hit = sha1("password123");
+        let mut near = hit;
+        near[7] ^= 1;
+
+        assert_eq!(
+            checker.contains_hashes(&[near, hit, near]).unwrap(),
+            [false, true, false]
+        );
+        assert!(checker.contains_hashes(&[]).unwrap().is_empty());
+        assert!(matches!(
+            checker.contains_hashes(&[hit, sha1("letmein")]),
+            Err(Error::PrefixFileMissing { .. })
+        ));
+
+        // A deadline already passed → only the first prefix file is read.
+        let past = Some(Instant::now());
+        let found = checker.contains_hashes_until(&[hit, [0; 20], near], past).unwrap();
+        assert_eq!(found, [Some(true), None, Some(false)]);
+    }
+
+    // Shard 12/16 (C0000-CFFFF) on disk without 00000.bin → validated against the shard's own
+    // bounds; passwords from other shards are refused rather than reported missing, and left
+    // unchecked by deadline batches.
+    #[test]
+    fn shard_answers_only_its_range() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("C0000.bin"), b"").unwrap();
+        std::fs::write(tmp.path().join("CFFFF.bin"), b"").unwrap();
+        std::fs::write(tmp.path().join("CBFDA.bin"), &sha1("password123")[2..8]).unwrap();
+        let range = crate::shard::Shard::new(12, 16).unwrap().range();
+
+        assert!(OwnedBreachChecker::open(tmp.path()).is_err());
+        let owned = OwnedBreachChecker::builder(tmp.path())
+            .prefix_range(range.clone())
+            .build()
+            .unwrap();
+        let borrowed = BreachChecker::new(tmp.path()).with_prefix_range(range);
+
+        assert!(owned.is_breached("password123").unwrap());
+        assert!(borrowed.is_breached("password123").unwrap());
+        // "password" hashes to prefix 5BAA6.
+        for err in [
+            owned.is_breached("password"),
+            borrowed.is_breached("password"),
+        ] {
+            assert!(matches!(
+                err,
+                Err(Error::PrefixNotInRange { prefix: 0x5BAA6 })
+            ));
+        }
+
+        let hashes = [sha1("password"), sha1("password123")];
+        assert!(matches!(
+            owned.contains_hashes(&hashes),
+            Err(Error::PrefixNotInRange { prefix: 0x5BAA6 })
+        ));
+        let found = owned.contains_hashes_until(&hashes, Some(Instant::now())).unwrap();
+        assert_eq!(found, [None, Some(true)]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn owned_checker_moves_into_spawned_task() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let checker = Arc::new(OwnedBreachChecker::open(tmp.path()).unwrap());
+
+        let task = tokio::spawn({
+            let checker = checker.clone();
+            async move { checker.is_breached_async("password123").await }
+        });
+        assert!(task.await.unwrap().unwrap());
+
+        std::fs::remove_file(tmp.path().join("CBFDA.bin")).unwrap();
+        let err = checker.is_breached_async("password123").await.unwrap_err();
+        assert!(matches!(err, Error::PrefixFileMissing { prefix: 0xCBFDA }));
+    }
+}
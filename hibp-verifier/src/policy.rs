@@ -0,0 +1,380 @@
+//! Fail-open/fail-closed decisions for authentication flows.
+//!
+//! A signup or password-change flow has to decide what to do when the dataset cannot be read.
+//! [`PolicyChecker`] turns every lookup into a [`Decision`]. A clean lookup gives `Allow` or
+//! `Reject`. Anything else gives `Degraded` with the reason, and the configured
+//! [`FailureMode`] decides whether a degraded password is allowed. A circuit breaker stops
+//! issuing lookups against a dataset that keeps failing (e.g. a dead network mount) and
+//! degrades immediately until a cooldown has passed.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{Error, OwnedBreachChecker};
+
+/// What to do with a password when the breach check cannot be completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Accept the password. Availability wins over the breach check.
+    Open,
+    /// Refuse the password. The breach check is mandatory.
+    Closed,
+}
+
+/// Why a check could not be completed.
+#[derive(Debug)]
+pub enum DegradedReason {
+    /// The lookup failed.
+    Lookup(Error),
+    /// The lookup did not finish within [`PolicyConfig::timeout`].
+    Timeout,
+    /// The circuit breaker is open, so no lookup was attempted.
+    CircuitOpen,
+}
+
+impl fmt::Display for DegradedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lookup(e) => write!(f, "lookup failed: {e}"),
+            Self::Timeout => f.write_str("lookup timed out"),
+            Self::CircuitOpen => f.write_str("circuit breaker open"),
+        }
+    }
+}
+
+/// Outcome of a policy check.
+#[derive(Debug)]
+pub enum Decision {
+    /// The password was not found in the breach database.
+    Allow,
+    /// The password was found in the breach database.
+    Reject,
+    /// The check could not be completed. Whether the password is accepted depends on the
+    /// failure mode; see [`Decision::is_allowed`].
+    Degraded {
+        reason: DegradedReason,
+        failure_mode: FailureMode,
+    },
+}
+
+impl Decision {
+    /// Whether the password should be accepted, applying the failure mode to degraded
+    /// decisions.
+    pub fn is_allowed(&self) -> bool {
+        match self {
+            Self::Allow => true,
+            Self::Reject => false,
+            Self::Degraded { failure_mode, .. } => *failure_mode == FailureMode::Open,
+        }
+    }
+}
+
+/// Policy options. `PolicyConfig::default()` fails closed with a 100ms timeout and a breaker
+/// that opens after 5 consecutive failures for 30 seconds.
+#[derive(Debug, Clone)]
+pub struct PolicyConfig {
+    pub failure_mode: FailureMode,
+    /// Maximum time for a single lookup. Only enforced by
+    /// [`PolicyChecker::check_async`]; a sync lookup cannot be abandoned.
+    pub timeout: Duration,
+    /// Consecutive failed lookups that open the circuit breaker.
+    pub breaker_threshold: u32,
+    /// How long the breaker stays open before a single trial lookup is let through.
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            failure_mode: FailureMode::Closed,
+            timeout: Duration::from_millis(100),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A breach checker that answers with a [`Decision`] instead of an error.
+pub struct PolicyChecker {
+    checker: OwnedBreachChecker,
+    config: PolicyConfig,
+    breaker: CircuitBreaker,
+}
+
+impl PolicyChecker {
+    pub fn new(checker: OwnedBreachChecker, config: PolicyConfig) -> Self {
+        let breaker = CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown);
+        Self { checker, config, breaker }
+    }
+
+    /// The wrapped checker.
+    pub fn checker(&self) -> &OwnedBreachChecker {
+        &self.checker
+    }
+
+    /// Whether the circuit breaker is currently refusing lookups.
+    pub fn is_circuit_open(&self) -> bool {
+        self.breaker.is_open()
+    }
+
+    /// Checks `password` with the sync API.
+    pub fn check(&self, password: &str) -> Decision {
+        if !self.breaker.try_acquire() {
+            return self.degraded(DegradedReason::CircuitOpen);
+        }
+        self.finish(self.checker.is_breached(password).map_err(DegradedReason::Lookup))
+    }
+
+    /// Checks `password` with the tokio API, giving up after [`PolicyConfig::timeout`].
+    #[cfg(feature = "tokio")]
+    pub async fn check_async(&self, password: &str) -> Decision {
+        if !self.breaker.try_acquire() {
+            return self.degraded(DegradedReason::CircuitOpen);
+        }
+        let lookup = self.checker.is_breached_async(password);
+        let result = match tokio::time::timeout(self.config.timeout, lookup).await {
+            Ok(result) => result.map_err(DegradedReason::Lookup),
+            Err(_) => Err(DegradedReason::Timeout),
+        };
+        self.finish(result)
+    }
+
+    fn finish(&self, result: Result<bool, DegradedReason>) -> Decision {
+        match result {
+            Ok(breached) => {
+                self.breaker.record_success();
+                if breached {
+                    Decision::Reject
+                } else {
+                    Decision::Allow
+                }
+            }
+            Err(reason) => {
+                if self.breaker.record_failure() {
+                    tracing::error!(
+                        threshold = self.config.breaker_threshold,
+                        cooldown_ms = self.config.breaker_cooldown.as_millis() as u64,
+                        "breach check circuit breaker opened"
+                    );
+                }
+                self.degraded(reason)
+            }
+        }
+    }
+
+    fn degraded(&self, reason: DegradedReason) -> Decision {
+        let failure_mode = self.config.failure_mode;
+        tracing::warn!(reason = %reason, ?failure_mode, "breach check degraded");
+        Decision::Degraded { reason, failure_mode }
+    }
+}
+
+/// Lock-free consecutive-failure breaker. Times are nanoseconds since `epoch`; `open_until`
+/// of zero means closed.
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    epoch: Instant,
+    failures: AtomicU32,
+    open_until: AtomicU64,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            epoch: Instant::now(),
+            failures: AtomicU32::new(0),
+            open_until: AtomicU64::new(0),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        // +1 so a reading taken right at the epoch is never mistaken for "closed".
+        self.epoch.elapsed().as_nanos() as u64 + 1
+    }
+
+    fn is_open(&self) -> bool {
+        let until = self.open_until.load(Ordering::Acquire);
+        until != 0 && self.now() < until
+    }
+
+    /// Whether a lookup may go ahead. Once the cooldown has passed, exactly one caller wins
+    /// the trial lookup; the others keep seeing an open breaker until it reports back.
+    fn try_acquire(&self) -> bool {
+        let until = self.open_until.load(Ordering::Acquire);
+        if until == 0 {
+            return true;
+        }
+        let now = self.now();
+        now >= until
+            && self
+                .open_until
+                .compare_exchange(
+                    until,
+                    now + self.cooldown_nanos(),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+    }
+
+    fn record_success(&self) {
+        self.failures.store(0, Ordering::Release);
+        self.open_until.store(0, Ordering::Release);
+    }
+
+    /// Records a failure and returns whether it opened the breaker.
+    fn record_failure(&self) -> bool {
+        let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+        if failures < self.threshold {
+            return false;
+        }
+        let was_closed = self.open_until.load(Ordering::Acquire) == 0;
+        self.open_until.store(self.now() + self.cooldown_nanos(), Ordering::Release);
+        was_closed
+    }
+
+    fn cooldown_nanos(&self) -> u64 {
+        self.cooldown.as_nanos() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::test_util::write_dataset;
+
+    fn policy(dir: &Path, config: PolicyConfig) -> PolicyChecker {
+        PolicyChecker::new(OwnedBreachChecker::open(dir).unwrap(), config)
+    }
+
+    #[test]
+    fn clean_lookups_allow_or_reject() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let checker = policy(tmp.path(), PolicyConfig::default());
+
+        assert!(matches!(checker.check("password123"), Decision::Reject));
+        assert!(matches!(checker.check("hAwT?}cuC:r#kW5"), Decision::Allow));
+    }
+
+    // Missing prefix file → Degraded; is_allowed follows the failure mode.
+    #[test]
+    fn lookup_failure_applies_failure_mode() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        std::fs::remove_file(tmp.path().join("CBFDA.bin")).unwrap();
+
+        for (failure_mode, allowed) in [(FailureMode::Open, true), (FailureMode::Closed, false)] {
+            let config = PolicyConfig { failure_mode, ..PolicyConfig::default() };
+            let decision = policy(tmp.path(), config).check("password123");
+            assert!(matches!(
+                decision,
+                Decision::Degraded {
+                    reason: DegradedReason::Lookup(Error::PrefixFileMissing { .. }),
+                    ..
+                }
+            ));
+            assert_eq!(decision.is_allowed(), allowed);
+        }
+    }
+
+    // 2 failures → breaker opens and skips lookups → after the cooldown one trial lookup
+    // succeeds → breaker closes.
+    #[test]
+    fn circuit_breaker_opens_and_recovers() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let bin = tmp.path().join("CBFDA.bin");
+        let records = std::fs::read(&bin).unwrap();
+        std::fs::remove_file(&bin).unwrap();
+
+        let config = PolicyConfig {
+            breaker_threshold: 2,
+            breaker_cooldown: Duration::from_millis(50),
+            ..PolicyConfig::default()
+        };
+        let checker = policy(tmp.path(), config);
+
+        for _ in 0..2 {
+            let decision = checker.check("password123");
+            assert!(matches!(
+                decision,
+                Decision::Degraded { reason: DegradedReason::Lookup(_), .. }
+            ));
+        }
+        assert!(checker.is_circuit_open());
+
+        // The dataset is repaired, but the breaker is still open.
+        std::fs::write(&bin, records).unwrap();
+        let decision = checker.check("password123");
+        assert!(matches!(
+            decision,
+            Decision::Degraded { reason: DegradedReason::CircuitOpen, .. }
+        ));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(matches!(checker.check("password123"), Decision::Reject));
+        assert!(!checker.is_circuit_open());
+    }
+
+    // Failed trial lookup after the cooldown → breaker reopens.
+    #[test]
+    fn failed_trial_reopens_breaker() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        std::fs::remove_file(tmp.path().join("CBFDA.bin")).unwrap();
+
+        let config = PolicyConfig {
+            breaker_threshold: 1,
+            breaker_cooldown: Duration::from_millis(20),
+            ..PolicyConfig::default()
+        };
+        let checker = policy(tmp.path(), config);
+
+        checker.check("password123");
+        assert!(checker.is_circuit_open());
+        std::thread::sleep(Duration::from_millis(30));
+        let decision = checker.check("password123");
+        assert!(matches!(
+            decision,
+            Decision::Degraded { reason: DegradedReason::Lookup(_), .. }
+        ));
+        assert!(checker.is_circuit_open());
+    }
+
+    // Prefix file is a FIFO, so opening it blocks until a writer appears → the async check
+    // times out.
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_check_times_out() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let bin = tmp.path().join("CBFDA.bin");
+        std::fs::remove_file(&bin).unwrap();
+        let c_path = std::ffi::CString::new(bin.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        let config = PolicyConfig {
+            timeout: Duration::from_millis(20),
+            failure_mode: FailureMode::Open,
+            ..PolicyConfig::default()
+        };
+        let decision = policy(tmp.path(), config).check_async("password123").await;
+        assert!(matches!(
+            decision,
+            Decision::Degraded { reason: DegradedReason::Timeout, .. }
+        ));
+        assert!(decision.is_allowed());
+
+        // Unblock the abandoned lookup so the runtime can shut down.
+        drop(std::fs::OpenOptions::new().write(true).open(&bin).unwrap());
+    }
+}
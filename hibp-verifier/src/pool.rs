@@ -0,0 +1,294 @@
+//! Runtime-agnostic async lookups on a dedicated thread pool.
+//!
+//! `is_breached_async` needs tokio and `is_breached_compio` needs compio. A [`PooledChecker`]
+//! owns a few worker threads of its own and hands back plain [`Future`]s that are woken by
+//! those threads, so it works the same under smol, async-std, `futures::executor` or any
+//! other executor.
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::pool::PooledChecker;
+//!
+//! let checker = PooledChecker::new(OwnedBreachChecker::open("/path/to/hibp-data")?, 4)?;
+//! smol::block_on(async {
+//!     if checker.is_breached("password123").await? {
+//!         println!("Password found in breach database!");
+//!     }
+//!     Ok::<_, hibp_verifier::Error>(())
+//! })?;
+//! ```
+//!
+//! SHA-1 hashing and the pre-filter run on the calling task; only the file read goes to the
+//! pool, the same split `is_breached_async` makes with `spawn_blocking`.
+
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use crate::{Error, OwnedBreachChecker, ruled_out, sha1};
+
+type Outcome = thread::Result<Result<bool, Error>>;
+
+/// A lookup waiting for a worker, and where to deliver its result.
+struct Job {
+    hash: [u8; 20],
+    slot: Arc<Slot>,
+}
+
+/// Hand-off between a worker and the [`Lookup`] future.
+#[derive(Default)]
+struct Slot {
+    state: Mutex<SlotState>,
+}
+
+#[derive(Default)]
+struct SlotState {
+    outcome: Option<Outcome>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn complete(&self, outcome: Outcome) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.outcome = Some(outcome);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// An [`OwnedBreachChecker`] whose lookups run on its own worker threads.
+///
+/// Cloning is cheap and shares the pool. The workers exit once the last clone is dropped,
+/// after finishing the lookups already queued.
+#[derive(Clone)]
+pub struct PooledChecker {
+    checker: OwnedBreachChecker,
+    pool: Arc<Pool>,
+}
+
+struct Pool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl PooledChecker {
+    /// Starts `threads` worker threads (at least one) for `checker`.
+    ///
+    /// Lookups are short and mostly wait on the page cache, so a handful of threads is
+    /// usually enough; raise it for cold datasets on slow disks.
+    pub fn new(checker: OwnedBreachChecker, threads: usize) -> Result<Self, Error> {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut workers = Vec::with_capacity(threads.max(1));
+        for i in 0..threads.max(1) {
+            let checker = checker.clone();
+            let receiver = receiver.clone();
+            let worker = thread::Builder::new()
+                .name(format!("hibp-lookup-{i}"))
+                .spawn(move || work(&checker, &receiver));
+            match worker {
+                Ok(worker) => workers.push(worker),
+                Err(e) => {
+                    // Dropping the pool stops the workers already started.
+                    drop(Pool { jobs: Some(jobs), workers });
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(Self { checker, pool: Arc::new(Pool { jobs: Some(jobs), workers }) })
+    }
+
+    /// The wrapped checker, for sync lookups.
+    pub fn checker(&self) -> &OwnedBreachChecker {
+        &self.checker
+    }
+
+    /// Checks if the given password has been found in a data breach.
+    ///
+    /// See [`BreachChecker::is_breached`](crate::BreachChecker::is_breached).
+    pub fn is_breached(&self, password: &str) -> Lookup {
+        self.contains_hash(sha1(password))
+    }
+
+    /// [`is_breached`](Self::is_breached) for a SHA-1 hash computed elsewhere.
+    pub fn contains_hash(&self, hash: [u8; 20]) -> Lookup {
+        match ruled_out(self.checker.prefix_range(), self.checker.prefilter(), &hash) {
+            Ok(false) => {}
+            Ok(true) => return Lookup::ready(Ok(false)),
+            Err(e) => return Lookup::ready(Err(e)),
+        }
+        let slot = Arc::new(Slot::default());
+        let job = Job { hash, slot: slot.clone() };
+        self.pool
+            .jobs
+            .as_ref()
+            .expect("jobs is only taken on drop")
+            .send(job)
+            .expect("workers only exit once the sender is dropped");
+        Lookup { state: LookupState::Queued(slot) }
+    }
+}
+
+impl fmt::Debug for PooledChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledChecker")
+            .field("checker", &self.checker)
+            .field("threads", &self.pool.workers.len())
+            .finish()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Closing the channel lets each worker drain the queue and exit.
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(checker: &OwnedBreachChecker, jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| checker.contains_hash(&job.hash)));
+        job.slot.complete(outcome);
+    }
+}
+
+/// Future returned by [`PooledChecker::is_breached`] and [`PooledChecker::contains_hash`].
+///
+/// Dropping it does not cancel the lookup; its result is discarded.
+#[must_use = "futures do nothing unless awaited"]
+pub struct Lookup {
+    state: LookupState,
+}
+
+enum LookupState {
+    Ready(Option<Result<bool, Error>>),
+    Queued(Arc<Slot>),
+}
+
+impl Lookup {
+    fn ready(result: Result<bool, Error>) -> Self {
+        Self { state: LookupState::Ready(Some(result)) }
+    }
+}
+
+impl Future for Lookup {
+    type Output = Result<bool, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let slot = match &mut self.get_mut().state {
+            LookupState::Ready(result) => {
+                return Poll::Ready(result.take().expect("Lookup polled after completion"));
+            }
+            LookupState::Queued(slot) => slot,
+        };
+        let mut state = slot.state.lock().unwrap();
+        match state.outcome.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                match &mut state.waker {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    None => state.waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Lookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            LookupState::Ready(_) => "ready",
+            LookupState::Queued(_) => "queued",
+        };
+        f.debug_struct("Lookup").field("state", &state).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use futures::executor::block_on;
+    use futures::future::join_all;
+
+    use super::*;
+    use crate::test_util::write_dataset;
+
+    // Hit, miss in the same file and a missing prefix file, awaited together on a
+    // non-tokio executor → results in order.
+    #[test]
+    fn lookups_complete_on_any_executor() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let checker = PooledChecker::new(OwnedBreachChecker::open(tmp.path()).unwrap(), 2).unwrap();
+        let mut near = sha1("password123");
+        near[7] ^= 1;
+
+        let results = block_on(join_all([
+            checker.is_breached("password123"),
+            checker.contains_hash(near),
+            checker.is_breached("letmein"),
+        ]));
+        assert!(results[0].as_ref().unwrap());
+        assert!(!results[1].as_ref().unwrap());
+        assert!(matches!(results[2], Err(Error::PrefixFileMissing { .. })));
+    }
+
+    // Many lookups from several threads through clones of one pool, then every clone
+    // dropped → the workers are joined.
+    #[test]
+    fn clones_share_the_pool() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let checker = PooledChecker::new(OwnedBreachChecker::open(tmp.path()).unwrap(), 0).unwrap();
+        assert_eq!(checker.pool.workers.len(), 1);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let checker = checker.clone();
+                s.spawn(move || {
+                    let lookups = (0..100).map(|_| checker.is_breached("password123"));
+                    assert!(block_on(join_all(lookups)).into_iter().all(|r| r.unwrap()));
+                });
+            }
+        });
+        drop(checker);
+    }
+
+    // A shard checker → out-of-range passwords fail without reaching the pool.
+    #[test]
+    fn range_errors_are_immediate() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &[]);
+        let owned = OwnedBreachChecker::builder(tmp.path())
+            .validate_layout(false)
+            .prefix_range(0xC0000..=0xCFFFF)
+            .build()
+            .unwrap();
+        let checker = PooledChecker::new(owned, 1).unwrap();
+
+        let lookup = checker.is_breached("password");
+        assert!(matches!(lookup.state, LookupState::Ready(_)));
+        assert!(matches!(
+            block_on(lookup),
+            Err(Error::PrefixNotInRange { prefix: 0x5BAA6 })
+        ));
+    }
+}
@@ -0,0 +1,574 @@
+//! Compact probabilistic pre-filter over every record in the dataset.
+//!
+//! Most passwords checked at login are not breached, yet each lookup costs a file open and a
+//! read. A [`Prefilter`] answers "definitely not breached" from memory for ~99.6% of those
+//! passwords, so only filter hits (real ones and a 1-in-256 false positive rate) reach the
+//! prefix file. It never gives false negatives for the data it was built from.
+//!
+//! The filter is a [binary fuse filter](https://arxiv.org/abs/2201.01174) with 8-bit
+//! fingerprints, about 9 bits per record (~1 GB for the full dataset). It is split into 256
+//! shards by the first hash byte so that a delta sync only rebuilds the shards it touched.
+//!
+//! `hibp-bin-fetch --prefilter` writes it as [`PREFILTER_FILE`] next to the prefix files and
+//! `hibp-sync-client --prefilter` rebuilds it before each commit is published, so the filter in
+//! a dataset directory always matches the prefix files beside it.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::os::fd::BorrowedFd;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{Error, RECORD_SIZE, build_path};
+
+/// File name of the pre-filter inside a dataset directory.
+pub const PREFILTER_FILE: &str = "prefilter.fuse";
+
+const MAGIC: &[u8; 8] = b"HIBPFUSE";
+const FORMAT_VERSION: u32 = 1;
+const SHARD_COUNT: usize = 256;
+/// Prefix files per shard: a shard covers one value of the first hash byte.
+const PREFIXES_PER_SHARD: u32 = 1 << 12;
+const SHARD_HEADER_LEN: usize = 20;
+
+/// A filter over the sha1t64 of every record in a dataset.
+pub struct Prefilter {
+    dataset_version: Option<String>,
+    shards: Box<[Shard]>,
+}
+
+impl Prefilter {
+    /// Loads [`PREFILTER_FILE`] from a dataset directory.
+    pub fn load(dataset_path: &Path) -> Result<Self, Error> {
+        Self::open(&dataset_path.join(PREFILTER_FILE))
+    }
+
+    /// [`load`](Self::load) from a dataset directory that is already open.
+    pub(crate) fn load_at(dir: BorrowedFd<'_>) -> Result<Self, Error> {
+        Self::read(crate::open_at(dir, PREFILTER_FILE)?)
+    }
+
+    /// Loads a pre-filter file.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        Self::read(File::open(path)?)
+    }
+
+    fn read(mut file: File) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Self::decode(&bytes)
+    }
+
+    /// Builds a filter from every prefix file in `dataset_path`, using all available cores.
+    ///
+    /// Missing prefix files are treated as empty, so partial datasets can be filtered too.
+    /// `dataset_version` is stored in the file and returned by
+    /// [`dataset_version`](Self::dataset_version); the sync client uses the dataset's
+    /// `last_updated` timestamp.
+    pub fn build(dataset_path: &Path, dataset_version: Option<&str>) -> Result<Self, Error> {
+        let shards = build_shards(dataset_path, (0..SHARD_COUNT).collect())?;
+        Ok(Self { dataset_version: dataset_version.map(str::to_owned), shards: shards.into() })
+    }
+
+    /// Builds a filter for an updated dataset by rebuilding only the shards that contain one
+    /// of `changed_prefixes` and copying the rest from `self`.
+    ///
+    /// `self` must have been built from the dataset as it was before those prefixes changed.
+    pub fn rebuild(
+        &self,
+        dataset_path: &Path,
+        changed_prefixes: &[u32],
+        dataset_version: Option<&str>,
+    ) -> Result<Self, Error> {
+        let mut touched: Vec<usize> =
+            changed_prefixes.iter().map(|&p| (p / PREFIXES_PER_SHARD) as usize).collect();
+        touched.sort_unstable();
+        touched.dedup();
+        touched.retain(|&s| s < SHARD_COUNT);
+
+        let mut shards = self.shards.to_vec();
+        for (index, shard) in touched.iter().zip(build_shards(dataset_path, touched.clone())?) {
+            shards[*index] = shard;
+        }
+        Ok(Self { dataset_version: dataset_version.map(str::to_owned), shards: shards.into() })
+    }
+
+    /// Writes the filter to `path` via a temporary file and rename, so readers never see a
+    /// partial file.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("fuse.tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        out.write_all(&(SHARD_COUNT as u32).to_le_bytes())?;
+        let version = self.dataset_version.as_deref().unwrap_or("").as_bytes();
+        out.write_all(&(version.len() as u32).to_le_bytes())?;
+        out.write_all(version)?;
+        for shard in &self.shards {
+            out.write_all(&shard.seed.to_le_bytes())?;
+            out.write_all(&shard.segment_length.to_le_bytes())?;
+            out.write_all(&shard.segment_count_length.to_le_bytes())?;
+            out.write_all(&(shard.fingerprints.len() as u32).to_le_bytes())?;
+        }
+        for shard in &self.shards {
+            out.write_all(&shard.fingerprints)?;
+        }
+        out.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// The dataset version this filter was built for, if one was recorded.
+    pub fn dataset_version(&self) -> Option<&str> {
+        self.dataset_version.as_deref()
+    }
+
+    /// Bytes of fingerprint data held in memory.
+    pub fn size_bytes(&self) -> usize {
+        self.shards.iter().map(|s| s.fingerprints.len()).sum()
+    }
+
+    /// Whether the dataset may contain `hash`. `false` is definite; `true` needs a lookup.
+    #[inline(always)]
+    pub fn may_contain(&self, hash: &[u8; 20]) -> bool {
+        let key = u64::from_be_bytes(hash[..8].try_into().unwrap());
+        self.shards[hash[0] as usize].contains(key)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut cursor = Cursor(bytes);
+        if cursor.take(MAGIC.len())? != MAGIC {
+            return Err(Error::CorruptPrefilter("bad magic"));
+        }
+        if cursor.u32()? != FORMAT_VERSION {
+            return Err(Error::CorruptPrefilter("unsupported format version"));
+        }
+        if cursor.u32()? as usize != SHARD_COUNT {
+            return Err(Error::CorruptPrefilter("unexpected shard count"));
+        }
+        let version_len = cursor.u32()? as usize;
+        let version = std::str::from_utf8(cursor.take(version_len)?)
+            .map_err(|_| Error::CorruptPrefilter("dataset version is not UTF-8"))?;
+        let dataset_version = (!version.is_empty()).then(|| version.to_owned());
+
+        let mut headers = Cursor(cursor.take(SHARD_COUNT * SHARD_HEADER_LEN)?);
+        let mut shards = Vec::with_capacity(SHARD_COUNT);
+        for _ in 0..SHARD_COUNT {
+            let seed = headers.u64()?;
+            let segment_length = headers.u32()?;
+            let segment_count_length = headers.u32()?;
+            let len = headers.u32()? as usize;
+            if len != 0
+                && (!segment_length.is_power_of_two()
+                    || len != segment_count_length as usize + 2 * segment_length as usize)
+            {
+                return Err(Error::CorruptPrefilter("inconsistent shard header"));
+            }
+            shards.push(Shard {
+                seed,
+                segment_length,
+                segment_length_mask: segment_length.wrapping_sub(1),
+                segment_count_length,
+                fingerprints: cursor.take(len)?.into(),
+            });
+        }
+        if !cursor.0.is_empty() {
+            return Err(Error::CorruptPrefilter("trailing bytes"));
+        }
+        Ok(Self { dataset_version, shards: shards.into() })
+    }
+}
+
+impl std::fmt::Debug for Prefilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prefilter")
+            .field("dataset_version", &self.dataset_version)
+            .field("size_bytes", &self.size_bytes())
+            .finish()
+    }
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::CorruptPrefilter("truncated"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Builds the given shards in parallel, returned in the same order.
+fn build_shards(dataset_path: &Path, indices: Vec<usize>) -> Result<Vec<Shard>, Error> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(indices.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..indices.len()).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&shard) = indices.get(i) else { break };
+                    let built = read_shard_keys(dataset_path, shard).map(Shard::build);
+                    let failed = built.is_err();
+                    results.lock().unwrap()[i] = Some(built);
+                    if failed {
+                        // Stop handing out work; the first error is returned below.
+                        next.store(indices.len(), Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    let mut shards = Vec::with_capacity(indices.len());
+    for result in results.into_inner().unwrap() {
+        match result {
+            Some(shard) => shards.push(shard?),
+            None => unreachable!("shard skipped without an earlier error"),
+        }
+    }
+    Ok(shards)
+}
+
+/// Reads the sha1t64 key of every record in one shard's prefix files.
+fn read_shard_keys(dataset_path: &Path, shard: usize) -> Result<Vec<u64>, Error> {
+    let mut keys = Vec::new();
+    let mut buf = Vec::new();
+    let first = shard as u32 * PREFIXES_PER_SHARD;
+    for prefix in first..first + PREFIXES_PER_SHARD {
+        let mut prefix_hex = [0u8; crate::PREFIX_LEN];
+        prefix_hex.copy_from_slice(format!("{prefix:05X}").as_bytes());
+        let mut file = match File::open(build_path(dataset_path, prefix_hex).as_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        buf.clear();
+        file.read_to_end(&mut buf)?;
+        if !buf.len().is_multiple_of(RECORD_SIZE) {
+            return Err(Error::CorruptRecordLength { prefix, len: buf.len() as u64 });
+        }
+        keys.extend(
+            buf.as_chunks::<RECORD_SIZE>().0.iter().map(|record| record_key(prefix, record)),
+        );
+    }
+    Ok(keys)
+}
+
+/// The sha1t64 of a record: the 20 prefix bits followed by the low 44 bits of the record
+/// (its top nibble repeats the last prefix digit).
+fn record_key(prefix: u32, record: &[u8; RECORD_SIZE]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes[2..].copy_from_slice(record);
+    (prefix as u64) << 44 | (u64::from_be_bytes(bytes) & ((1 << 44) - 1))
+}
+
+/// One binary fuse filter with 8-bit fingerprints and three hash functions.
+#[derive(Clone)]
+struct Shard {
+    seed: u64,
+    segment_length: u32,
+    segment_length_mask: u32,
+    segment_count_length: u32,
+    fingerprints: Box<[u8]>,
+}
+
+impl Shard {
+    #[inline(always)]
+    fn contains(&self, key: u64) -> bool {
+        if self.fingerprints.is_empty() {
+            return false;
+        }
+        let hash = mix(key, self.seed);
+        let [h0, h1, h2] = self.positions(hash);
+        fingerprint(hash) ^ self.fingerprints[h0] ^ self.fingerprints[h1] ^ self.fingerprints[h2]
+            == 0
+    }
+
+    #[inline(always)]
+    fn positions(&self, hash: u64) -> [usize; 3] {
+        let h0 = ((hash as u128 * self.segment_count_length as u128) >> 64) as u32;
+        let h1 = (h0 + self.segment_length) ^ ((hash >> 18) as u32 & self.segment_length_mask);
+        let h2 = (h0 + 2 * self.segment_length) ^ (hash as u32 & self.segment_length_mask);
+        [h0 as usize, h1 as usize, h2 as usize]
+    }
+
+    /// Builds a filter over `keys`, following the construction of Graf and Lemire's
+    /// reference implementation.
+    fn build(mut keys: Vec<u64>) -> Self {
+        // A repeated key can never be peeled, so drop duplicates first.
+        keys.sort_unstable();
+        keys.dedup();
+        let size = keys.len();
+        if size == 0 {
+            return Self {
+                seed: 0,
+                segment_length: 0,
+                segment_length_mask: 0,
+                segment_count_length: 0,
+                fingerprints: Box::new([]),
+            };
+        }
+
+        let segment_length =
+            (1u32 << ((size as f64).ln() / 3.33f64.ln() + 2.25).floor() as u32).min(1 << 18);
+        let capacity = if size > 1 {
+            let size_factor = (0.875 + 0.25 * 1e6f64.ln() / (size as f64).ln()).max(1.125);
+            (size as f64 * size_factor).round() as u32
+        } else {
+            0
+        };
+        let segments = capacity.div_ceil(segment_length);
+        let segment_count = if segments <= 2 { 1 } else { segments - 2 };
+        let array_length = ((segment_count + 2) * segment_length) as usize;
+
+        let mut shard = Self {
+            seed: 0,
+            segment_length,
+            segment_length_mask: segment_length - 1,
+            segment_count_length: segment_count * segment_length,
+            fingerprints: Box::new([]),
+        };
+
+        // Fixed RNG start so the same records always produce the same file.
+        let mut rng = 0x726b_2b9d_438b_9d4d;
+        let mut reverse_order = vec![0u64; size + 1];
+        reverse_order[size] = 1;
+        let mut reverse_h = vec![0u8; size];
+        let mut alone = vec![0u32; array_length];
+        let mut t2count = vec![0u8; array_length];
+        let mut t2hash = vec![0u64; array_length];
+
+        let block_bits = (u32::BITS - (segment_count - 1).leading_zeros()).max(1);
+        let block = 1usize << block_bits;
+        let mut start_pos = vec![0usize; block];
+
+        let stack_size = loop {
+            shard.seed = splitmix64(&mut rng);
+
+            // Bucket hashes by their top bits so the counting pass walks memory in order.
+            for (i, pos) in start_pos.iter_mut().enumerate() {
+                *pos = ((i as u64 * size as u64) >> block_bits) as usize;
+            }
+            for &key in &keys {
+                let hash = mix(key, shard.seed);
+                let mut segment = (hash >> (64 - block_bits)) as usize;
+                while reverse_order[start_pos[segment]] != 0 {
+                    segment = (segment + 1) & (block - 1);
+                }
+                reverse_order[start_pos[segment]] = hash;
+                start_pos[segment] += 1;
+            }
+
+            // Each slot counts its keys (times 4, in the high bits), records which of the
+            // key's three positions it is (low bits, XORed) and XORs the key hashes together.
+            let mut overflowed = false;
+            for &hash in &reverse_order[..size] {
+                for (slot, h) in shard.positions(hash).into_iter().enumerate() {
+                    t2count[h] = t2count[h].wrapping_add(4) ^ slot as u8;
+                    t2hash[h] ^= hash;
+                    overflowed |= t2count[h] < 4;
+                }
+            }
+
+            let mut stack_size = 0;
+            if !overflowed {
+                // Peel slots holding a single key until none are left.
+                let mut queue = 0;
+                for (i, &count) in t2count.iter().enumerate() {
+                    alone[queue] = i as u32;
+                    queue += usize::from(count >> 2 == 1);
+                }
+                while queue > 0 {
+                    queue -= 1;
+                    let index = alone[queue] as usize;
+                    if t2count[index] >> 2 != 1 {
+                        continue;
+                    }
+                    let hash = t2hash[index];
+                    let found = t2count[index] & 3;
+                    reverse_h[stack_size] = found;
+                    reverse_order[stack_size] = hash;
+                    stack_size += 1;
+
+                    let [h0, h1, h2] = shard.positions(hash);
+                    let h012 = [h0, h1, h2, h0, h1];
+                    for step in 1..=2 {
+                        let other = h012[found as usize + step];
+                        alone[queue] = other as u32;
+                        queue += usize::from(t2count[other] >> 2 == 2);
+                        t2count[other] =
+                            t2count[other].wrapping_sub(4) ^ ((found + step as u8) % 3);
+                        t2hash[other] ^= hash;
+                    }
+                }
+                if stack_size == size {
+                    break stack_size;
+                }
+            }
+
+            reverse_order[..size].fill(0);
+            t2count.fill(0);
+            t2hash.fill(0);
+        };
+
+        let mut fingerprints = vec![0u8; array_length];
+        for i in (0..stack_size).rev() {
+            let hash = reverse_order[i];
+            let [h0, h1, h2] = shard.positions(hash);
+            let h012 = [h0, h1, h2, h0, h1];
+            let found = reverse_h[i] as usize;
+            fingerprints[h012[found]] =
+                fingerprint(hash) ^ fingerprints[h012[found + 1]] ^ fingerprints[h012[found + 2]];
+        }
+        shard.fingerprints = fingerprints.into();
+        shard
+    }
+}
+
+#[inline(always)]
+fn mix(key: u64, seed: u64) -> u64 {
+    let mut h = key.wrapping_add(seed);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+#[inline(always)]
+fn fingerprint(hash: u64) -> u8 {
+    (hash ^ (hash >> 32)) as u8
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search_key, sha1};
+
+    // Key for a hash as derived from its prefix file record.
+    fn hash_key(hash: &[u8; 20]) -> u64 {
+        let prefix = (hash[0] as u32) << 12 | (hash[1] as u32) << 4 | (hash[2] >> 4) as u32;
+        record_key(prefix, &search_key(hash))
+    }
+
+    fn keys(n: u64, salt: &str) -> Vec<u64> {
+        (0..n).map(|i| hash_key(&sha1(&format!("{salt}{i}")))).collect()
+    }
+
+    #[test]
+    fn record_key_matches_hash_prefix() {
+        let hash = sha1("password123");
+        assert_eq!(
+            hash_key(&hash),
+            u64::from_be_bytes(hash[..8].try_into().unwrap())
+        );
+    }
+
+    // Every inserted key is found; unrelated keys hit at roughly 1/256.
+    #[test]
+    fn shard_has_no_false_negatives() {
+        for n in [1, 2, 3, 100, 10_000, 200_000] {
+            let inserted = keys(n, "in");
+            let shard = Shard::build(inserted.clone());
+            assert!(inserted.iter().all(|&k| shard.contains(k)), "n = {n}");
+        }
+
+        let shard = Shard::build(keys(200_000, "in"));
+        let false_positives =
+            keys(200_000, "out").into_iter().filter(|&k| shard.contains(k)).count();
+        assert!(
+            false_positives < 200_000 / 128,
+            "{false_positives} false positives"
+        );
+    }
+
+    #[test]
+    fn shard_tolerates_duplicates() {
+        let mut inserted = keys(1000, "dup");
+        inserted.extend_from_within(..500);
+        let shard = Shard::build(inserted.clone());
+        assert!(inserted.iter().all(|&k| shard.contains(k)));
+    }
+
+    #[test]
+    fn empty_shard_contains_nothing() {
+        let shard = Shard::build(Vec::new());
+        assert!(!shard.contains(0));
+    }
+
+    fn write_prefix(dir: &Path, passwords: &[&str]) {
+        let mut records: Vec<[u8; 6]> = passwords.iter().map(|p| search_key(&sha1(p))).collect();
+        records.sort_unstable();
+        let prefix = crate::BreachChecker::prefix_hex(&sha1(passwords[0]));
+        let name = format!("{}.bin", std::str::from_utf8(&prefix).unwrap());
+        std::fs::write(dir.join(name), records.concat()).unwrap();
+    }
+
+    // Build → write → load round trip, then a delta rebuild of one shard.
+    #[test]
+    fn build_write_load_rebuild() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_prefix(tmp.path(), &["password123"]);
+
+        let filter = Prefilter::build(tmp.path(), Some("2026-01-01T00:00:00Z")).unwrap();
+        assert!(filter.may_contain(&sha1("password123")));
+        filter.write(&tmp.path().join(PREFILTER_FILE)).unwrap();
+
+        let loaded = Prefilter::load(tmp.path()).unwrap();
+        assert_eq!(loaded.dataset_version(), Some("2026-01-01T00:00:00Z"));
+        assert!(loaded.may_contain(&sha1("password123")));
+        assert!(!loaded.may_contain(&sha1("hAwT?}cuC:r#kW5")));
+
+        std::fs::write(tmp.path().join("CBFDA.bin"), b"").unwrap();
+        let rebuilt = loaded.rebuild(tmp.path(), &[0xCBFDA], Some("2026-01-02T00:00:00Z")).unwrap();
+        assert!(!rebuilt.may_contain(&sha1("password123")));
+        assert_eq!(rebuilt.dataset_version(), Some("2026-01-02T00:00:00Z"));
+    }
+
+    #[test]
+    fn corrupt_file_is_rejected() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(PREFILTER_FILE);
+        let empty = Prefilter {
+            dataset_version: None,
+            shards: (0..SHARD_COUNT).map(|_| Shard::build(Vec::new())).collect(),
+        };
+        empty.write(&path).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            Prefilter::open(&path),
+            Err(Error::CorruptPrefilter(_))
+        ));
+
+        std::fs::write(&path, b"not a filter").unwrap();
+        assert!(matches!(
+            Prefilter::open(&path),
+            Err(Error::CorruptPrefilter("bad magic"))
+        ));
+    }
+}
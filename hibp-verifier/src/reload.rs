@@ -0,0 +1,517 @@
+//! A checker that follows dataset updates made by `hibp-sync-client`.
+//!
+//! [`ReloadingChecker`] watches the dataset's `sync-state.json` and switches to the new data
+//! as soon as a sync commits. Each lookup takes a snapshot of the dataset it starts on, so with
+//! the versioned layout (`hibp-sync-client --keep-versions`, checker pointed at
+//! `<data-dir>/current`) an in-flight lookup always finishes against a single version even if
+//! the `current` symlink is swapped underneath it. With the flat layout files are replaced in
+//! place and only the reported version is switched atomically.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+use std::{fs, io};
+
+use crate::cache::PrefixCache;
+use crate::{BreachCheckerBuilder, Error, OwnedBreachChecker};
+
+/// Name of the state file written by `hibp-sync-client` after each commit.
+pub const STATE_FILE: &str = "sync-state.json";
+
+/// How often the dataset is re-checked when inotify is unavailable. With inotify this is only
+/// an upper bound on how long a missed event can go unnoticed.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One version of the dataset, as resolved at reload time.
+#[derive(Debug)]
+pub struct Dataset {
+    checker: OwnedBreachChecker,
+    last_updated: Option<String>,
+}
+
+impl Dataset {
+    /// Builds the version `builder`'s dataset path currently resolves to, with its options.
+    fn load(builder: &BreachCheckerBuilder) -> Result<Self, Error> {
+        let dataset_path = &builder.dataset_path;
+        // Resolving the path pins the version directory behind a `current` symlink, so
+        // lookups against this snapshot are unaffected by a later swap.
+        let dir = fs::canonicalize(dataset_path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::DatasetMissing(dataset_path.to_path_buf()),
+            _ => Error::Io(e),
+        })?;
+        let last_updated = match fs::read(dir.join(STATE_FILE)) {
+            Ok(bytes) => parse_last_updated(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        // Validation (unless the builder turns it off) keeps a half-written or broken version
+        // from replacing a working one.
+        let mut version = builder.clone();
+        version.dataset_path = dir;
+        Ok(Self { checker: version.build()?, last_updated })
+    }
+
+    /// The resolved directory holding this version's prefix files.
+    pub fn dir(&self) -> &Path {
+        self.checker.dataset_path()
+    }
+
+    /// The server `last_updated` timestamp recorded by the sync that produced this version,
+    /// or `None` if the directory has no state file.
+    pub fn last_updated(&self) -> Option<&str> {
+        self.last_updated.as_deref()
+    }
+
+    /// A checker that reads from this version.
+    pub fn checker(&self) -> &OwnedBreachChecker {
+        &self.checker
+    }
+
+    fn is_same_version(&self, other: &Self) -> bool {
+        self.dir() == other.dir() && self.last_updated == other.last_updated
+    }
+}
+
+fn parse_last_updated(bytes: &[u8]) -> io::Result<Option<String>> {
+    let state: serde_json::Value = serde_json::from_slice(bytes)?;
+    Ok(state.get("last_updated").and_then(|v| v.as_str()).map(str::to_owned))
+}
+
+struct Shared {
+    builder: BreachCheckerBuilder,
+    current: RwLock<Arc<Dataset>>,
+    cache: Option<Arc<PrefixCache>>,
+}
+
+impl Shared {
+    fn snapshot(&self) -> Arc<Dataset> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn reload(&self) -> Result<bool, Error> {
+        let next = Dataset::load(&self.builder)?;
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        if current.is_same_version(&next) {
+            return Ok(false);
+        }
+        *current = Arc::new(attach_cache(self.cache.as_ref(), next));
+        Ok(true)
+    }
+}
+
+/// Starts a new cache generation for `dataset`, so lookups still running on the previous
+/// version stop using the cache.
+fn attach_cache(cache: Option<&Arc<PrefixCache>>, mut dataset: Dataset) -> Dataset {
+    if let Some(cache) = cache {
+        cache.invalidate();
+        dataset.checker = dataset.checker.with_cache(cache.clone());
+    }
+    dataset
+}
+
+/// An owned, cloneable breach checker that reloads when `hibp-sync-client` commits new data.
+///
+/// Clones share the same dataset and watcher. The background watcher thread exits at its next
+/// wakeup after the last clone is dropped.
+#[derive(Clone)]
+pub struct ReloadingChecker {
+    shared: Arc<Shared>,
+}
+
+impl ReloadingChecker {
+    /// Opens the dataset at `dataset_path` and starts watching it with the default poll
+    /// interval.
+    pub fn open(dataset_path: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::with_poll_interval(dataset_path, DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Opens the dataset at `dataset_path` and starts watching it, re-checking at least every
+    /// `poll_interval`.
+    pub fn with_poll_interval(
+        dataset_path: impl Into<PathBuf>,
+        poll_interval: Duration,
+    ) -> Result<Self, Error> {
+        Self::from_builder(
+            OwnedBreachChecker::builder(dataset_path),
+            poll_interval,
+            None,
+        )
+    }
+
+    /// [`with_poll_interval`](Self::with_poll_interval), with lookups going through `cache`.
+    /// The cache is invalidated when the checker opens and on every new dataset version.
+    pub fn with_cache(
+        dataset_path: impl Into<PathBuf>,
+        poll_interval: Duration,
+        cache: Arc<PrefixCache>,
+    ) -> Result<Self, Error> {
+        Self::from_builder(
+            OwnedBreachChecker::builder(dataset_path),
+            poll_interval,
+            Some(cache),
+        )
+    }
+
+    /// Opens the dataset with `builder`'s options and starts watching it, building every new
+    /// version the same way. Use it to serve a shard synced with `hibp-sync-client --shard`
+    /// ([`prefix_range`](BreachCheckerBuilder::prefix_range)) or to load the pre-filter the
+    /// client keeps with `--prefilter` ([`prefilter`](BreachCheckerBuilder::prefilter)).
+    /// `cache` is used as in [`with_cache`](Self::with_cache).
+    pub fn from_builder(
+        builder: BreachCheckerBuilder,
+        poll_interval: Duration,
+        cache: Option<Arc<PrefixCache>>,
+    ) -> Result<Self, Error> {
+        let dataset = attach_cache(cache.as_ref(), Dataset::load(&builder)?);
+        let current = RwLock::new(Arc::new(dataset));
+        let shared = Arc::new(Shared { builder, current, cache });
+
+        let weak = Arc::downgrade(&shared);
+        std::thread::Builder::new()
+            .name("hibp-reload".into())
+            .spawn(move || watch(weak, poll_interval))?;
+
+        Ok(Self { shared })
+    }
+
+    /// The dataset version lookups currently start on.
+    pub fn current(&self) -> Arc<Dataset> {
+        self.shared.snapshot()
+    }
+
+    /// The `last_updated` timestamp of the version currently being served.
+    pub fn version(&self) -> Option<String> {
+        self.shared.snapshot().last_updated.clone()
+    }
+
+    /// Re-reads the dataset immediately instead of waiting for the watcher. Returns whether
+    /// a new version was picked up.
+    pub fn reload(&self) -> Result<bool, Error> {
+        self.shared.reload()
+    }
+
+    /// Checks if the given password has been found in a data breach.
+    ///
+    /// See [`BreachChecker::is_breached`](crate::BreachChecker::is_breached).
+    pub fn is_breached(&self, password: &str) -> Result<bool, Error> {
+        self.shared.snapshot().checker().is_breached(password)
+    }
+
+    /// Async version of `is_breached` using tokio.
+    ///
+    /// See [`BreachChecker::is_breached_async`](crate::BreachChecker::is_breached_async).
+    #[cfg(feature = "tokio")]
+    pub async fn is_breached_async(&self, password: &str) -> Result<bool, Error> {
+        let dataset = self.shared.snapshot();
+        dataset.checker().is_breached_async(password).await
+    }
+
+    /// Async version of `is_breached` using compio's native io-uring file I/O.
+    ///
+    /// See [`BreachChecker::is_breached_compio`](crate::BreachChecker::is_breached_compio).
+    #[cfg(feature = "compio")]
+    pub async fn is_breached_compio(&self, password: &str) -> Result<bool, Error> {
+        let dataset = self.shared.snapshot();
+        dataset.checker().is_breached_compio(password).await
+    }
+}
+
+// Runs on the watcher thread. It only holds a weak reference between wakeups, so it exits at
+// the first wakeup after the last checker clone is dropped.
+fn watch(shared: Weak<Shared>, poll_interval: Duration) {
+    let mut watcher = match shared.upgrade() {
+        Some(s) => Watcher::new(&s.builder.dataset_path).ok(),
+        None => return,
+    };
+    loop {
+        match &mut watcher {
+            Some(w) => w.wait(poll_interval),
+            None => std::thread::sleep(poll_interval),
+        }
+
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        // A failed read usually means a sync is mid-commit; keep serving the current version
+        // and try again on the next event.
+        if let Ok(true) = shared.reload()
+            && let Some(w) = &mut watcher
+        {
+            w.rewatch(&shared.builder.dataset_path);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct Watcher {
+    fd: std::os::fd::OwnedFd,
+    dataset_wd: libc::c_int,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    const MASK: u32 = libc::IN_CLOSE_WRITE
+        | libc::IN_MOVED_TO
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_DELETE_SELF;
+
+    fn new(dataset_path: &Path) -> io::Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        // SAFETY: inotify_init1 has no preconditions; the returned fd is owned below.
+        let raw = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(raw) };
+
+        // The parent sees the `current` symlink being swapped; the dataset directory itself
+        // sees the state file being replaced in the flat layout.
+        if let Some(parent) = dataset_path.parent() {
+            add_watch(&fd, parent)?;
+        }
+        let dataset_wd = add_watch(&fd, dataset_path)?;
+        Ok(Self { fd, dataset_wd })
+    }
+
+    /// Moves the dataset watch to the directory the dataset path now resolves to.
+    fn rewatch(&mut self, dataset_path: &Path) {
+        use std::os::fd::AsRawFd;
+
+        unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), self.dataset_wd) };
+        if let Ok(wd) = add_watch(&self.fd, dataset_path) {
+            self.dataset_wd = wd;
+        }
+    }
+
+    /// Blocks until an event arrives or `timeout` elapses, then drains pending events.
+    fn wait(&mut self, timeout: Duration) {
+        use std::os::fd::AsRawFd;
+
+        let mut pfd = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn add_watch(fd: &std::os::fd::OwnedFd, path: &Path) -> io::Result<libc::c_int> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), Watcher::MASK) };
+    if wd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(wd)
+}
+
+/// Polling-only fallback for platforms without inotify.
+#[cfg(not(target_os = "linux"))]
+struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    fn new(_dataset_path: &Path) -> io::Result<Self> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn rewatch(&mut self, _dataset_path: &Path) {}
+
+    fn wait(&mut self, timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+    use std::time::Instant;
+
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::prefilter::{PREFILTER_FILE, Prefilter};
+    use crate::shard::Shard;
+
+    const T1: &str = "2026-01-01T01:00:00Z";
+    const T2: &str = "2026-01-02T01:00:00Z";
+
+    // Writes a version directory whose CBFDA.bin holds "password123" only if `breached`.
+    fn write_version(dir: &Path, last_updated: &str, breached: bool) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("00000.bin"), b"").unwrap();
+        fs::write(dir.join("FFFFF.bin"), b"").unwrap();
+        let hash: [u8; 20] = Sha1::digest(b"password123").into();
+        let records: &[u8] = if breached { &hash[2..8] } else { &[] };
+        fs::write(dir.join("CBFDA.bin"), records).unwrap();
+        fs::write(
+            dir.join(STATE_FILE),
+            format!("{{\"last_updated\":\"{last_updated}\"}}"),
+        )
+        .unwrap();
+    }
+
+    fn swap_current(data: &Path, version: &str) {
+        let tmp = data.join(".current.tmp");
+        symlink(Path::new("versions").join(version), &tmp).unwrap();
+        fs::rename(&tmp, data.join("current")).unwrap();
+    }
+
+    // current → v1 (clean) → swap to v2 (breached) → reload() picks up v2, unless the watcher
+    // thread got there first. A snapshot taken before the swap keeps reading v1.
+    #[test]
+    fn reload_follows_current_symlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path();
+        write_version(&data.join("versions/v1"), T1, false);
+        write_version(&data.join("versions/v2"), T2, true);
+        swap_current(data, "v1");
+
+        let checker =
+            ReloadingChecker::with_poll_interval(data.join("current"), Duration::MAX).unwrap();
+        assert_eq!(checker.version().as_deref(), Some(T1));
+        assert!(!checker.is_breached("password123").unwrap());
+
+        let before = checker.current();
+        swap_current(data, "v2");
+        checker.reload().unwrap();
+        assert!(!checker.reload().unwrap());
+
+        assert_eq!(checker.version().as_deref(), Some(T2));
+        assert!(checker.is_breached("password123").unwrap());
+        assert!(!before.checker().is_breached("password123").unwrap());
+    }
+
+    // Shard 12/16 (C0000-CFFFF) with a pre-filter, which `open` would reject for lacking
+    // 00000.bin → from_builder serves it, and the swapped-in version is built with the same
+    // range and filter.
+    #[test]
+    fn from_builder_keeps_options_across_reloads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path();
+        for (version, last_updated, breached) in [("v1", T1, false), ("v2", T2, true)] {
+            let dir = data.join("versions").join(version);
+            write_version(&dir, last_updated, breached);
+            fs::remove_file(dir.join("00000.bin")).unwrap();
+            fs::remove_file(dir.join("FFFFF.bin")).unwrap();
+            fs::write(dir.join("C0000.bin"), b"").unwrap();
+            fs::write(dir.join("CFFFF.bin"), b"").unwrap();
+            let filter = Prefilter::build(&dir, Some(last_updated)).unwrap();
+            filter.write(&dir.join(PREFILTER_FILE)).unwrap();
+        }
+        swap_current(data, "v1");
+
+        let range = Shard::new(12, 16).unwrap().range();
+        assert!(ReloadingChecker::open(data.join("current")).is_err());
+        let builder = OwnedBreachChecker::builder(data.join("current"))
+            .prefix_range(range)
+            .prefilter(true);
+        let checker = ReloadingChecker::from_builder(builder, Duration::MAX, None).unwrap();
+        assert!(!checker.is_breached("password123").unwrap());
+
+        swap_current(data, "v2");
+        checker.reload().unwrap();
+        assert_eq!(checker.version().as_deref(), Some(T2));
+        assert!(checker.is_breached("password123").unwrap());
+        let dataset = checker.current();
+        assert!(dataset.checker().prefilter().is_some());
+        assert!(matches!(
+            checker.is_breached("password"),
+            Err(Error::PrefixNotInRange { .. })
+        ));
+    }
+
+    // Flat layout: state file is replaced in place → the watcher notices without reload().
+    #[test]
+    fn watcher_picks_up_flat_layout_commit() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_version(tmp.path(), T1, false);
+
+        let checker =
+            ReloadingChecker::with_poll_interval(tmp.path(), Duration::from_millis(50)).unwrap();
+        assert_eq!(checker.version().as_deref(), Some(T1));
+
+        write_version(tmp.path(), T2, true);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while checker.version().as_deref() != Some(T2) {
+            assert!(Instant::now() < deadline, "watcher did not reload");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(checker.is_breached("password123").unwrap());
+    }
+
+    // Flat layout with a cache: CBFDA.bin is cached clean, then replaced in place → the
+    // reload invalidates the cache and the old snapshot no longer reads from it.
+    #[test]
+    fn reload_invalidates_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_version(tmp.path(), T1, false);
+        let cache = Arc::new(PrefixCache::new(1 << 20));
+        let checker =
+            ReloadingChecker::with_cache(tmp.path(), Duration::MAX, cache.clone()).unwrap();
+        assert!(!checker.is_breached("password123").unwrap());
+        assert!(!checker.is_breached("password123").unwrap());
+        assert_eq!((cache.stats().hits, cache.stats().misses), (1, 1));
+
+        let before = checker.current();
+        write_version(tmp.path(), T2, true);
+        // The watcher may already have picked the commit up.
+        checker.reload().unwrap();
+        assert_eq!(checker.version().as_deref(), Some(T2));
+        assert_eq!(cache.stats().entries, 0);
+        assert!(checker.is_breached("password123").unwrap());
+        assert!(before.checker().is_breached("password123").unwrap());
+        assert!(checker.is_breached("password123").unwrap());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 3, 1));
+    }
+
+    // Unparseable state file → reload fails and the previous version keeps serving.
+    #[test]
+    fn corrupt_state_keeps_current_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_version(tmp.path(), T1, false);
+        let checker = ReloadingChecker::with_poll_interval(tmp.path(), Duration::MAX).unwrap();
+
+        fs::write(tmp.path().join(STATE_FILE), b"{").unwrap();
+        assert!(checker.reload().is_err());
+        assert_eq!(checker.version().as_deref(), Some(T1));
+    }
+
+    #[test]
+    fn missing_state_file_has_no_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_version(tmp.path(), T1, false);
+        fs::remove_file(tmp.path().join(STATE_FILE)).unwrap();
+        let checker = ReloadingChecker::with_poll_interval(tmp.path(), Duration::MAX).unwrap();
+        assert_eq!(checker.version(), None);
+    }
+
+    // A new version missing FFFFF.bin fails validation → the old version keeps serving.
+    #[test]
+    fn invalid_version_is_not_loaded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path();
+        write_version(&data.join("versions/v1"), T1, false);
+        write_version(&data.join("versions/v2"), T2, true);
+        fs::remove_file(data.join("versions/v2/FFFFF.bin")).unwrap();
+        swap_current(data, "v1");
+
+        let checker =
+            ReloadingChecker::with_poll_interval(data.join("current"), Duration::MAX).unwrap();
+        swap_current(data, "v2");
+        assert!(matches!(
+            checker.reload(),
+            Err(Error::PrefixFileMissing { prefix: 0xFFFFF })
+        ));
+        assert_eq!(checker.version().as_deref(), Some(T1));
+    }
+}
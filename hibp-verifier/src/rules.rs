@@ -0,0 +1,442 @@
+//! Password rules in the spirit of NIST SP 800-63B, with breach and blocklist checks.
+//!
+//! HIBP membership is one of several reasons to refuse a password. [`PasswordRules`] runs a
+//! [`LayeredChecker`] alongside length, context-word, repetition and sequence rules and
+//! reports every rule the password breaks as a [`Violation`]. Each violation has a stable
+//! [`code`](Violation::code) for a UI to translate and a [`Display`](fmt::Display) message
+//! that can be shown as is.
+//!
+//! ```rust,ignore
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::layered::LayeredChecker;
+//! use hibp_verifier::rules::{PasswordRules, RulesConfig};
+//!
+//! let layers = LayeredChecker::new()
+//!     .blocklist("company", "/etc/hibp/company-words")?
+//!     .dataset("hibp", OwnedBreachChecker::open("/path/to/hibp-data")?);
+//! let rules = PasswordRules::new(layers, RulesConfig::default());
+//!
+//! for violation in rules.check("alice2026!", &["alice", "alice@example.com"])? {
+//!     println!("{}: {violation}", violation.code());
+//! }
+//! ```
+
+use std::fmt;
+
+use crate::layered::{LayerKind, LayeredChecker, Verdict};
+use crate::{Error, sha1};
+
+/// Context strings are split into words at non-alphanumeric characters; shorter words are
+/// ignored so that e.g. the `io` of an email domain does not match.
+const MIN_CONTEXT_WORD_LEN: usize = 3;
+
+/// Rule options. `RulesConfig::default()` requires 8 characters, has no maximum length,
+/// refuses runs of 4 or more repeated or sequential characters and refuses every breached
+/// password regardless of its count.
+#[derive(Debug, Clone)]
+pub struct RulesConfig {
+    /// Minimum length in characters.
+    pub min_length: usize,
+    /// Maximum length in characters. NIST asks that at least 64 be allowed.
+    pub max_length: Option<usize>,
+    /// Longest run of one repeated character allowed, e.g. 3 allows `aaa` but not `aaaa`.
+    pub max_repeated: Option<usize>,
+    /// Longest run of ascending or descending letters or digits allowed, e.g. 3 allows `abc`
+    /// but not `abcd` or `4321`.
+    pub max_sequential: Option<usize>,
+    /// Breached passwords seen at most this many times are allowed. Only applies when a
+    /// [`PrevalenceSource`] knows the count; sha1t48 datasets store no counts, so their
+    /// matches are always refused.
+    pub max_prevalence: Option<u64>,
+}
+
+impl Default for RulesConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: None,
+            max_repeated: Some(3),
+            max_sequential: Some(3),
+            max_prevalence: None,
+        }
+    }
+}
+
+/// Looks up how often a password appears in a breach corpus.
+pub trait PrevalenceSource: Send + Sync {
+    /// The number of times the password with SHA-1 `hash` was seen, or `None` if unknown.
+    fn prevalence(&self, hash: &[u8; 20]) -> Result<Option<u64>, Error>;
+}
+
+impl<F> PrevalenceSource for F
+where
+    F: Fn(&[u8; 20]) -> Result<Option<u64>, Error> + Send + Sync,
+{
+    fn prevalence(&self, hash: &[u8; 20]) -> Result<Option<u64>, Error> {
+        self(hash)
+    }
+}
+
+/// A rule the password breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// `length` characters, fewer than the configured `min`.
+    TooShort { min: usize, length: usize },
+    /// `length` characters, more than the configured `max`.
+    TooLong { max: usize, length: usize },
+    /// Found in the `Dataset` layer `layer`, `count` times if the count is known.
+    Breached { layer: String, count: Option<u64> },
+    /// Found in the `Blocklist` layer `layer`.
+    Blocklisted { layer: String },
+    /// Contains `word`, taken from the context strings passed to [`PasswordRules::check`].
+    ContainsContextWord { word: String },
+    /// Contains `run`, a run of one repeated character.
+    RepeatedCharacters { run: String },
+    /// Contains `run`, a run of ascending or descending letters or digits.
+    SequentialCharacters { run: String },
+}
+
+impl Violation {
+    /// A stable identifier for the rule, for UIs that show their own text.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TooShort { .. } => "too_short",
+            Self::TooLong { .. } => "too_long",
+            Self::Breached { .. } => "breached",
+            Self::Blocklisted { .. } => "blocklisted",
+            Self::ContainsContextWord { .. } => "contains_context_word",
+            Self::RepeatedCharacters { .. } => "repeated_characters",
+            Self::SequentialCharacters { .. } => "sequential_characters",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort { min, .. } => write!(f, "must be at least {min} characters long"),
+            Self::TooLong { max, .. } => write!(f, "must be at most {max} characters long"),
+            Self::Breached { count: Some(count), .. } => {
+                write!(f, "has appeared in data breaches {count} times")
+            }
+            Self::Breached { count: None, .. } => f.write_str("has appeared in a data breach"),
+            Self::Blocklisted { .. } => f.write_str("is not allowed"),
+            Self::ContainsContextWord { word } => write!(f, "must not contain \"{word}\""),
+            Self::RepeatedCharacters { run } => {
+                write!(f, "must not repeat a character as in \"{run}\"")
+            }
+            Self::SequentialCharacters { run } => {
+                write!(f, "must not contain a sequence such as \"{run}\"")
+            }
+        }
+    }
+}
+
+/// Evaluates passwords against a [`RulesConfig`] and a stack of breach and blocklist layers.
+pub struct PasswordRules {
+    layers: LayeredChecker,
+    config: RulesConfig,
+    prevalence: Option<Box<dyn PrevalenceSource>>,
+}
+
+impl PasswordRules {
+    pub fn new(layers: LayeredChecker, config: RulesConfig) -> Self {
+        Self { layers, config, prevalence: None }
+    }
+
+    /// Uses `source` for the counts [`RulesConfig::max_prevalence`] is compared against.
+    pub fn prevalence_source(mut self, source: impl PrevalenceSource + 'static) -> Self {
+        self.prevalence = Some(Box::new(source));
+        self
+    }
+
+    pub fn config(&self) -> &RulesConfig {
+        &self.config
+    }
+
+    /// Checks `password` against every rule and returns the ones it breaks, in a fixed
+    /// order: length, breach and blocklist layers, context words, repetition, sequences. An
+    /// empty list means the password is acceptable.
+    ///
+    /// `context` holds strings the password must not contain, such as the username, email
+    /// address and service name. Matching ignores case.
+    ///
+    /// Fails if a layer lookup fails; the local rules alone are not a reason to accept a
+    /// password that could not be checked.
+    pub fn check(&self, password: &str, context: &[&str]) -> Result<Vec<Violation>, Error> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.config.min_length {
+            violations.push(Violation::TooShort { min: self.config.min_length, length });
+        }
+        if let Some(max) = self.config.max_length
+            && length > max
+        {
+            violations.push(Violation::TooLong { max, length });
+        }
+        if let Some(violation) = self.check_layers(password)? {
+            violations.push(violation);
+        }
+        violations.extend(
+            context_words(password, context)
+                .into_iter()
+                .map(|word| Violation::ContainsContextWord { word }),
+        );
+        if let Some(max) = self.config.max_repeated
+            && let Some(run) = longest_run(password, max, |a, b| a == b)
+        {
+            violations.push(Violation::RepeatedCharacters { run });
+        }
+        if let Some(max) = self.config.max_sequential
+            && let Some(run) = sequential_run(password, max)
+        {
+            violations.push(Violation::SequentialCharacters { run });
+        }
+        Ok(violations)
+    }
+
+    /// The first layer match not excused by [`RulesConfig::max_prevalence`], which only
+    /// excuses dataset matches, so a later blocklist still rejects the password.
+    fn check_layers(&self, password: &str) -> Result<Option<Violation>, Error> {
+        // The count is looked up at most once, for the first dataset that holds the password.
+        let mut count = None;
+        let verdict = self.layers.check_excusing(password, |_, kind| {
+            if kind != LayerKind::Dataset {
+                return Ok(false);
+            }
+            if count.is_none() {
+                count = Some(match &self.prevalence {
+                    Some(source) => source.prevalence(&sha1(password))?,
+                    None => None,
+                });
+            }
+            Ok(matches!(
+                (self.config.max_prevalence, count.flatten()),
+                (Some(max), Some(count)) if count <= max
+            ))
+        })?;
+        let violation = match verdict {
+            Verdict::Blocked { layer, kind: LayerKind::Dataset } => {
+                Violation::Breached { layer: layer.to_owned(), count: count.flatten() }
+            }
+            Verdict::Blocked { layer, .. } => Violation::Blocklisted { layer: layer.to_owned() },
+            Verdict::Clean | Verdict::Allowed { .. } => return Ok(None),
+        };
+        Ok(Some(violation))
+    }
+}
+
+impl fmt::Debug for PasswordRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordRules")
+            .field("layers", &self.layers)
+            .field("config", &self.config)
+            .field("prevalence", &self.prevalence.is_some())
+            .finish()
+    }
+}
+
+/// The distinct context words, lowercased, that appear in `password` ignoring case.
+fn context_words(password: &str, context: &[&str]) -> Vec<String> {
+    let password = password.to_lowercase();
+    let mut found: Vec<String> = Vec::new();
+    for word in context.iter().flat_map(|c| c.split(|ch: char| !ch.is_alphanumeric())) {
+        let word = word.to_lowercase();
+        if word.chars().count() >= MIN_CONTEXT_WORD_LEN
+            && password.contains(&word)
+            && !found.contains(&word)
+        {
+            found.push(word);
+        }
+    }
+    found
+}
+
+/// The first run of more than `max` characters in which every adjacent pair satisfies
+/// `linked`.
+fn longest_run(password: &str, max: usize, linked: impl Fn(char, char) -> bool) -> Option<String> {
+    let chars: Vec<char> = password.chars().collect();
+    let mut start = 0;
+    for end in 1..=chars.len() {
+        if end == chars.len() || !linked(chars[end - 1], chars[end]) {
+            if end - start > max {
+                return Some(chars[start..end].iter().collect());
+            }
+            start = end;
+        }
+    }
+    None
+}
+
+/// The first ascending or descending run of more than `max` ASCII letters or digits, ignoring
+/// case, e.g. `abcd`, `4321` or `XyZ`.
+fn sequential_run(password: &str, max: usize) -> Option<String> {
+    let step = |a: char, b: char| {
+        let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+        let same_class = (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+            || (a.is_ascii_digit() && b.is_ascii_digit());
+        same_class.then(|| b as i32 - a as i32).filter(|d| d.abs() == 1)
+    };
+    longest_run(password, max, |a, b| step(a, b) == Some(1))
+        .or_else(|| longest_run(password, max, |a, b| step(a, b) == Some(-1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::OwnedBreachChecker;
+    use crate::layered::compile_wordlist;
+    use crate::test_util::write_dataset;
+
+    fn rules(dir: &Path, config: RulesConfig) -> PasswordRules {
+        // HIBP stand-in holding "password123" and "Tr0ub4dor&3", with the sentinels and an
+        // empty file for every other prefix the tests look up.
+        let hibp = dir.join("hibp");
+        write_dataset(
+            &hibp,
+            &["password123", "Tr0ub4dor&3"],
+            &["correct horse battery", "alice2026!", "aaaa1234", "Abcd"],
+        );
+        compile_wordlist(&b"Acme2026\n"[..], &dir.join("company")).unwrap();
+
+        let layers = LayeredChecker::new()
+            .blocklist("company", dir.join("company"))
+            .unwrap()
+            .dataset("hibp", OwnedBreachChecker::open(&hibp).unwrap());
+        PasswordRules::new(layers, config)
+    }
+
+    #[test]
+    fn clean_password_passes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let rules = rules(tmp.path(), RulesConfig::default());
+        assert_eq!(rules.check("correct horse battery", &["bob"]).unwrap(), []);
+    }
+
+    // Every local rule broken at once → all reported in order, with codes and messages.
+    #[test]
+    fn reports_every_local_violation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = RulesConfig { max_length: Some(6), ..RulesConfig::default() };
+        let rules = rules(tmp.path(), config);
+
+        let violations = rules.check("aaaa1234", &["Aaaa@example.com"]).unwrap();
+        assert_eq!(
+            violations,
+            [
+                Violation::TooLong { max: 6, length: 8 },
+                Violation::ContainsContextWord { word: "aaaa".into() },
+                Violation::RepeatedCharacters { run: "aaaa".into() },
+                Violation::SequentialCharacters { run: "1234".into() },
+            ]
+        );
+        let codes: Vec<_> = violations.iter().map(Violation::code).collect();
+        assert_eq!(
+            codes,
+            [
+                "too_long",
+                "contains_context_word",
+                "repeated_characters",
+                "sequential_characters"
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "must be at most 6 characters long"
+        );
+
+        assert_eq!(
+            rules.check("Abcd", &[]).unwrap(),
+            [
+                Violation::TooShort { min: 8, length: 4 },
+                Violation::SequentialCharacters { run: "Abcd".into() },
+            ]
+        );
+    }
+
+    // Short context words and the email domain's "io" are ignored; usernames are matched
+    // case-insensitively.
+    #[test]
+    fn context_words_ignore_case_and_short_words() {
+        assert_eq!(
+            context_words("Alice2026!", &["alice", "al@x.io"]),
+            ["alice"]
+        );
+        assert_eq!(context_words("xyz", &["io", "x.y.z"]), Vec::<String>::new());
+    }
+
+    // Runs of exactly `max` are allowed; descending and mixed-case runs count.
+    #[test]
+    fn runs_respect_limits() {
+        assert_eq!(longest_run("xaaay", 3, |a, b| a == b), None);
+        assert_eq!(sequential_run("pw987654", 3).as_deref(), Some("987654"));
+        assert_eq!(sequential_run("abc-xyz", 3), None);
+        assert_eq!(sequential_run("9:;<", 3), None);
+    }
+
+    // HIBP match → Breached; blocklist match → Blocklisted, reported by layer name.
+    #[test]
+    fn layer_matches_are_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let rules = rules(tmp.path(), RulesConfig::default());
+
+        assert_eq!(
+            rules.check("password123", &[]).unwrap(),
+            [Violation::Breached { layer: "hibp".into(), count: None }]
+        );
+        assert_eq!(
+            rules.check("Acme2026", &[]).unwrap(),
+            [Violation::Blocklisted { layer: "company".into() }]
+        );
+        assert!(matches!(
+            rules.check("letmein!", &[]),
+            Err(Error::PrefixFileMissing { .. })
+        ));
+    }
+
+    // With counts: at or under max_prevalence → allowed; over → Breached with the count.
+    #[test]
+    fn max_prevalence_uses_known_counts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = RulesConfig { max_prevalence: Some(10), ..RulesConfig::default() };
+        let common = sha1("password123");
+        let rules = rules(tmp.path(), config).prevalence_source(move |hash: &[u8; 20]| {
+            Ok(Some(if *hash == common { 250_000 } else { 3 }))
+        });
+
+        assert_eq!(rules.check("Tr0ub4dor&3", &[]).unwrap(), []);
+        let violations = rules.check("password123", &[]).unwrap();
+        assert_eq!(
+            violations,
+            [Violation::Breached { layer: "hibp".into(), count: Some(250_000) }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "has appeared in data breaches 250000 times"
+        );
+    }
+    // A dataset then a blocklist, both holding the password: a count under max_prevalence
+    // excuses only the dataset match → still Blocklisted by the later layer.
+    #[test]
+    fn max_prevalence_does_not_skip_later_blocklists() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hibp = tmp.path().join("hibp");
+        write_dataset(&hibp, &["password123", "Tr0ub4dor&3"], &[]);
+        compile_wordlist(&b"Tr0ub4dor&3\n"[..], &tmp.path().join("company")).unwrap();
+        let layers = LayeredChecker::new()
+            .dataset("hibp", OwnedBreachChecker::open(&hibp).unwrap())
+            .blocklist("company", tmp.path().join("company"))
+            .unwrap();
+        let config = RulesConfig { max_prevalence: Some(10), ..RulesConfig::default() };
+        let rules =
+            PasswordRules::new(layers, config).prevalence_source(|_: &[u8; 20]| Ok(Some(3)));
+
+        assert_eq!(
+            rules.check("Tr0ub4dor&3", &[]).unwrap(),
+            [Violation::Blocklisted { layer: "company".into() }]
+        );
+        assert_eq!(rules.check("password123", &[]).unwrap(), []);
+    }
+}
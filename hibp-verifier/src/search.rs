@@ -0,0 +1,315 @@
+//! Searching the records of a prefix file.
+//!
+//! Records are truncated SHA-1 hashes, so within a prefix file they are close to uniformly
+//! distributed 48-bit values. On the default sorted layout that makes interpolation search a
+//! good fit: [`interpolation_search`] guesses a record's position from its value and usually
+//! lands within a few records of it, where `binary_search` would take about 11 probes.
+//!
+//! A dataset can instead be written in Eytzinger (breadth-first) order, selected at build
+//! time with `hibp-bin-fetch fetch --layout eytzinger`. A search then walks down an implicit
+//! binary tree whose first levels share a few cache lines, with no data-dependent branches.
+//! The layout is recorded in the dataset's [`LAYOUT_FILE`]; checkers read it when opened.
+//! Searches also compare the first two records of each file, which are in opposite order in
+//! the two layouts, so a dataset searched with the wrong layout fails rather than reporting
+//! breached passwords as clean.
+//!
+//! Eytzinger datasets are for fetched, read-only copies: the sync server and clients exchange
+//! sorted files, so `hibp-sync-client` cannot keep one current.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::fd::BorrowedFd;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Error, RECORD_SIZE};
+
+/// Name of the file recording a dataset's [`RecordLayout`]. Datasets without one are
+/// [`RecordLayout::Sorted`].
+pub const LAYOUT_FILE: &str = "record-layout";
+
+/// Interpolation steps taken before falling back to binary search. Uniform data converges in
+/// about log log n steps; the cap bounds the cost on skewed files.
+const INTERPOLATION_STEPS: usize = 4;
+
+/// Ranges this small are finished with a binary search.
+const INTERPOLATION_CUTOFF: usize = 8;
+
+/// Order of the records within each prefix file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordLayout {
+    /// Ascending order, as served by the HIBP API and the sync server.
+    #[default]
+    Sorted,
+    /// Eytzinger (breadth-first) order of a complete binary search tree; see [`to_eytzinger`].
+    Eytzinger,
+}
+
+impl RecordLayout {
+    /// Reads the layout recorded in `dataset_path`'s [`LAYOUT_FILE`], or
+    /// [`Sorted`](Self::Sorted) if there is none.
+    pub fn load(dataset_path: &Path) -> Result<Self, Error> {
+        Self::read(File::open(dataset_path.join(LAYOUT_FILE)))
+    }
+
+    /// [`load`](Self::load) from a dataset directory that is already open.
+    pub(crate) fn load_at(dir: BorrowedFd<'_>) -> Result<Self, Error> {
+        Self::read(crate::open_at(dir, LAYOUT_FILE))
+    }
+
+    fn read(file: io::Result<File>) -> Result<Self, Error> {
+        let mut name = String::new();
+        match file.and_then(|mut file| file.read_to_string(&mut name)) {
+            Ok(_) => name.trim().parse(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::Sorted),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Records this layout in `dataset_path`'s [`LAYOUT_FILE`].
+    pub fn write(self, dataset_path: &Path) -> io::Result<()> {
+        fs::write(dataset_path.join(LAYOUT_FILE), format!("{self}\n"))
+    }
+
+    /// Whether `records`, in this layout, contain `key`.
+    ///
+    /// Fails with [`Error::RecordLayoutMismatch`] if the first two records are ordered the
+    /// other way. An Eytzinger file's root sorts after its left child, so searching an
+    /// Eytzinger dataset as sorted (e.g. through `BreachChecker::new`) errors instead of
+    /// missing breached passwords, without reading the dataset's [`LAYOUT_FILE`].
+    #[inline(always)]
+    pub fn contains(
+        self,
+        records: &[[u8; RECORD_SIZE]],
+        key: &[u8; RECORD_SIZE],
+    ) -> Result<bool, Error> {
+        if let [first, second, ..] = records
+            && match self {
+                Self::Sorted => first > second,
+                Self::Eytzinger => first < second,
+            }
+        {
+            return Err(Error::RecordLayoutMismatch(self));
+        }
+        Ok(match self {
+            Self::Sorted => interpolation_search(records, key),
+            Self::Eytzinger => eytzinger_search(records, key),
+        })
+    }
+
+    /// `records` in this layout, reordered ascending.
+    pub fn to_sorted(self, records: &[[u8; RECORD_SIZE]]) -> Vec<[u8; RECORD_SIZE]> {
+        match self {
+            Self::Sorted => records.to_vec(),
+            Self::Eytzinger => from_eytzinger(records),
+        }
+    }
+}
+
+impl fmt::Display for RecordLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sorted => "sorted",
+            Self::Eytzinger => "eytzinger",
+        })
+    }
+}
+
+impl FromStr for RecordLayout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "sorted" => Ok(Self::Sorted),
+            "eytzinger" => Ok(Self::Eytzinger),
+            _ => Err(Error::UnknownRecordLayout(s.to_owned())),
+        }
+    }
+}
+
+/// The 48-bit value of a record.
+#[inline(always)]
+fn value(record: &[u8; RECORD_SIZE]) -> u64 {
+    let [a, b, c, d, e, f] = *record;
+    u64::from_be_bytes([0, 0, a, b, c, d, e, f])
+}
+
+/// Whether the ascending `records` contain `key`, probing where `key`'s value falls between
+/// the ends of the remaining range.
+#[inline]
+pub fn interpolation_search(records: &[[u8; RECORD_SIZE]], key: &[u8; RECORD_SIZE]) -> bool {
+    let target = value(key);
+    let (mut lo, mut hi) = (0, records.len());
+    for _ in 0..INTERPOLATION_STEPS {
+        if hi - lo <= INTERPOLATION_CUTOFF {
+            break;
+        }
+        let (first, last) = (value(&records[lo]), value(&records[hi - 1]));
+        if target < first || target > last {
+            return false;
+        }
+        // A 48-bit difference times a prefix file's record count (below 2^12) fits in u64;
+        // a larger slice, e.g. a `MemoryDataset` prefix updated in place, may need u128.
+        let (delta, span, range) = (target - first, (hi - 1 - lo) as u64, (last - first).max(1));
+        let offset = match delta.checked_mul(span) {
+            Some(product) => product / range,
+            None => (delta as u128 * span as u128 / range as u128) as u64,
+        };
+        let probe = lo + offset as usize;
+        match value(&records[probe]).cmp(&target) {
+            std::cmp::Ordering::Equal => return true,
+            std::cmp::Ordering::Less => lo = probe + 1,
+            std::cmp::Ordering::Greater => hi = probe,
+        }
+    }
+    records[lo..hi].binary_search(key).is_ok()
+}
+
+/// Whether `tree`, in Eytzinger order, contains `key`.
+#[inline]
+pub fn eytzinger_search(tree: &[[u8; RECORD_SIZE]], key: &[u8; RECORD_SIZE]) -> bool {
+    // 1-based node numbers: node k is tree[k - 1], with children 2k and 2k + 1. Descend to
+    // a leaf without branching on the comparison, then undo the right turns taken after the
+    // last left turn to recover the smallest node not less than `key`.
+    let mut k = 1;
+    while k <= tree.len() {
+        k = 2 * k + usize::from(tree[k - 1] < *key);
+    }
+    k >>= k.trailing_ones() + 1;
+    k != 0 && tree[k - 1] == *key
+}
+
+/// Reorders ascending `sorted` records into Eytzinger order.
+pub fn to_eytzinger(sorted: &[[u8; RECORD_SIZE]]) -> Vec<[u8; RECORD_SIZE]> {
+    let mut tree = vec![[0; RECORD_SIZE]; sorted.len()];
+    let mut next = sorted.iter();
+    fill(&mut tree, &mut next, 1);
+    tree
+}
+
+/// Assigns the subtree under node `k` from `sorted`, in order.
+fn fill<'a>(
+    tree: &mut [[u8; RECORD_SIZE]],
+    sorted: &mut impl Iterator<Item = &'a [u8; RECORD_SIZE]>,
+    k: usize,
+) {
+    if k <= tree.len() {
+        fill(tree, sorted, 2 * k);
+        tree[k - 1] = *sorted.next().expect("one record per node");
+        fill(tree, sorted, 2 * k + 1);
+    }
+}
+
+/// Reorders Eytzinger-ordered records ascending.
+pub fn from_eytzinger(tree: &[[u8; RECORD_SIZE]]) -> Vec<[u8; RECORD_SIZE]> {
+    let mut sorted = Vec::with_capacity(tree.len());
+    walk(tree, &mut sorted, 1);
+    sorted
+}
+
+fn walk(tree: &[[u8; RECORD_SIZE]], sorted: &mut Vec<[u8; RECORD_SIZE]>, k: usize) {
+    if k <= tree.len() {
+        walk(tree, sorted, 2 * k);
+        sorted.push(tree[k - 1]);
+        walk(tree, sorted, 2 * k + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(v: u64) -> [u8; RECORD_SIZE] {
+        v.to_be_bytes()[2..].try_into().unwrap()
+    }
+
+    // Uniform, clustered and tiny inputs → both searches agree with binary_search for every
+    // record and for the values between them.
+    #[test]
+    fn searches_match_binary_search() {
+        let mut inputs: Vec<Vec<u64>> = vec![
+            vec![],
+            vec![7],
+            (0..9).map(|i| i * 3).collect(),
+            (0..2000u64)
+                .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A) & 0x0FFF_FFFF_FFFF)
+                .collect(),
+            // A dense cluster at each end, the worst case for interpolation.
+            (0..500).chain((0..500).map(|i| 0xFFFF_FFFF_FFFF - i)).collect(),
+        ];
+        for values in &mut inputs {
+            values.sort_unstable();
+            values.dedup();
+            let sorted: Vec<_> = values.iter().map(|&v| record(v)).collect();
+            let tree = to_eytzinger(&sorted);
+            assert_eq!(from_eytzinger(&tree), sorted);
+
+            let probes = values.iter().flat_map(|&v| [v, v.wrapping_sub(1), v + 1]);
+            for probe in probes.chain([0, 0xFFFF_FFFF_FFFF]) {
+                let key = record(probe & 0xFFFF_FFFF_FFFF);
+                let expected = sorted.binary_search(&key).is_ok();
+                assert_eq!(interpolation_search(&sorted, &key), expected, "{probe:x}");
+                assert_eq!(eytzinger_search(&tree, &key), expected, "{probe:x}");
+            }
+        }
+    }
+
+    // 100k records spread over the full 48-bit range → products past u64 are widened rather
+    // than overflowing, and every record is still found.
+    #[test]
+    fn interpolation_handles_large_slices() {
+        let sorted: Vec<_> = (0..100_000u64).map(|i| record(i * 0xA7C5_AC47 + 5)).collect();
+        assert!(value(sorted.last().unwrap()) <= 0xFFFF_FFFF_FFFF);
+        for (i, key) in sorted.iter().enumerate().step_by(97) {
+            assert!(interpolation_search(&sorted, key), "record {i}");
+            let absent = record(value(key) + 1);
+            assert!(!interpolation_search(&sorted, &absent), "after record {i}");
+        }
+        assert!(interpolation_search(&sorted, sorted.last().unwrap()));
+        assert!(!interpolation_search(&sorted, &record(0)));
+    }
+
+    // Files of two or more records searched with the wrong layout → RecordLayoutMismatch;
+    // a single record is the same in both layouts.
+    #[test]
+    fn contains_rejects_wrong_layout() {
+        let sorted: Vec<_> = (1..=7).map(record).collect();
+        let tree = to_eytzinger(&sorted);
+        let key = record(3);
+        assert!(RecordLayout::Sorted.contains(&sorted, &key).unwrap());
+        assert!(RecordLayout::Eytzinger.contains(&tree, &key).unwrap());
+        assert!(matches!(
+            RecordLayout::Sorted.contains(&tree, &key),
+            Err(Error::RecordLayoutMismatch(RecordLayout::Sorted))
+        ));
+        assert!(matches!(
+            RecordLayout::Eytzinger.contains(&sorted, &key),
+            Err(Error::RecordLayoutMismatch(RecordLayout::Eytzinger))
+        ));
+        assert!(RecordLayout::Sorted.contains(&to_eytzinger(&sorted[..2]), &key).is_err());
+        assert!(RecordLayout::Eytzinger.contains(&sorted[..1], &record(1)).unwrap());
+    }
+
+    // No marker → sorted; written marker round-trips; unknown name → error.
+    #[test]
+    fn layout_file_round_trips() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            RecordLayout::load(tmp.path()).unwrap(),
+            RecordLayout::Sorted
+        );
+
+        RecordLayout::Eytzinger.write(tmp.path()).unwrap();
+        assert_eq!(
+            RecordLayout::load(tmp.path()).unwrap(),
+            RecordLayout::Eytzinger
+        );
+
+        fs::write(tmp.path().join(LAYOUT_FILE), "btree\n").unwrap();
+        assert!(matches!(
+            RecordLayout::load(tmp.path()),
+            Err(Error::UnknownRecordLayout(name)) if name == "btree"
+        ));
+    }
+}
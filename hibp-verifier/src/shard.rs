@@ -0,0 +1,129 @@
+//! Splitting the dataset into prefix ranges that can live on separate hosts.
+//!
+//! Shard `i` of `n` holds roughly `1/n` of the 1,048,576 prefixes. `hibp-sync-client --shard
+//! i/n` syncs only that range, a checker built with [`Shard::range`] answers for it alone, and
+//! a router sends each password to [`Shard::containing`] its prefix.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::{PREFIX_COUNT, hash_prefix, sha1};
+
+/// One of `count` contiguous, near-equal slices of the prefix space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shard {
+    index: u32,
+    count: u32,
+}
+
+/// A shard index and count that do not describe a shard.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid shard {0:?}: expected INDEX/COUNT with INDEX < COUNT <= 1048576")]
+pub struct InvalidShard(String);
+
+impl Shard {
+    /// Shard `index` (zero-based) of `count`.
+    pub fn new(index: u32, count: u32) -> Result<Self, InvalidShard> {
+        if count == 0 || count > PREFIX_COUNT || index >= count {
+            return Err(InvalidShard(format!("{index}/{count}")));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// The shard of `count` that holds `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is zero or larger than the number of prefixes.
+    pub fn containing(prefix: u32, count: u32) -> Self {
+        assert!(
+            count != 0 && count <= PREFIX_COUNT,
+            "invalid shard count {count}"
+        );
+        let index =
+            (prefix.min(PREFIX_COUNT - 1) as u64 * count as u64 / PREFIX_COUNT as u64) as u32;
+        Self { index, count }
+    }
+
+    /// The shard of `count` that holds `password`'s prefix.
+    pub fn for_password(password: &str, count: u32) -> Self {
+        Self::containing(hash_prefix(&sha1(password)), count)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The prefixes this shard holds.
+    pub fn range(&self) -> RangeInclusive<u32> {
+        let bound = |i: u32| (i as u64 * PREFIX_COUNT as u64).div_ceil(self.count as u64) as u32;
+        bound(self.index)..=bound(self.index + 1) - 1
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = InvalidShard;
+
+    /// Parses `INDEX/COUNT`, e.g. `3/8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidShard(s.to_owned());
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse().map_err(|_| invalid())?;
+        let count = count.trim().parse().map_err(|_| invalid())?;
+        Self::new(index, count).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shards of any count tile the prefix space with no gaps or overlaps.
+    #[test]
+    fn shards_tile_prefix_space() {
+        for count in [1, 3, 7, 8, 1000, PREFIX_COUNT] {
+            let mut next = 0;
+            for index in [0, 1, count / 2, count - 1] {
+                let shard = Shard::new(index.min(count - 1), count).unwrap();
+                let range = shard.range();
+                assert_eq!(Shard::containing(*range.start(), count), shard);
+                assert_eq!(Shard::containing(*range.end(), count), shard);
+                if index == 0 {
+                    assert_eq!(*range.start(), 0);
+                }
+                next = *range.end() + 1;
+            }
+            assert_eq!(next, PREFIX_COUNT);
+        }
+        assert_eq!(Shard::new(3, 8).unwrap().range(), 0x60000..=0x7FFFF);
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let shard: Shard = "3/8".parse().unwrap();
+        assert_eq!(shard, Shard::new(3, 8).unwrap());
+        assert_eq!(shard.to_string(), "3/8");
+
+        for bad in ["8/8", "0/0", "3", "a/8", "1/2097152"] {
+            assert!(bad.parse::<Shard>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn password_routes_to_its_prefix() {
+        // password123 hashes to prefix CBFDA.
+        assert_eq!(Shard::for_password("password123", 16).index(), 0xC);
+        assert!(Shard::for_password("password123", 8).range().contains(&0xCBFDA));
+    }
+}
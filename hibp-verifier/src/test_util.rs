@@ -0,0 +1,42 @@
+//! Dataset fixtures for tests, in this crate and (with the `testing` feature) in the crates
+//! built on it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{BreachChecker, RECORD_SIZE, sha1};
+
+/// Writes a small dataset to `dir` holding the records of `breached`.
+///
+/// Lookups outside those prefixes would fail on a missing file, so each of `clean` gets an
+/// empty prefix file unless it shares one with a breached password. The empty `00000.bin`
+/// and `FFFFF.bin` let the dataset pass [`validate_layout`](crate::validate_layout).
+pub fn write_dataset(dir: &Path, breached: &[&str], clean: &[&str]) {
+    // Written by hand rather than with `layered::compile_wordlist`, so a bug there cannot
+    // corrupt the fixtures and the expected results alike.
+    let mut files: BTreeMap<String, Vec<[u8; RECORD_SIZE]>> = BTreeMap::new();
+    for password in breached {
+        let hash = sha1(password);
+        let record = hash[2..2 + RECORD_SIZE].try_into().unwrap();
+        files.entry(prefix_file(password)).or_default().push(record);
+    }
+    std::fs::create_dir_all(dir).unwrap();
+    for (name, mut records) in files {
+        records.sort_unstable();
+        records.dedup();
+        std::fs::write(dir.join(name), records.concat()).unwrap();
+    }
+    let sentinels = ["00000.bin".to_owned(), "FFFFF.bin".to_owned()];
+    for name in clean.iter().map(|password| prefix_file(password)).chain(sentinels) {
+        let path = dir.join(name);
+        if !path.exists() {
+            std::fs::write(path, b"").unwrap();
+        }
+    }
+}
+
+/// Name of the prefix file `password` is looked up in, e.g. `CBFDA.bin` for "password123".
+pub fn prefix_file(password: &str) -> String {
+    let prefix = BreachChecker::prefix_hex(&sha1(password));
+    format!("{}.bin", std::str::from_utf8(&prefix).unwrap())
+}
@@ -0,0 +1,490 @@
+//! Tower middleware that checks a password field in request bodies.
+//!
+//! [`BreachCheckLayer`] buffers the body of each request its extractor is interested in,
+//! pulls the password out and checks it with [`PolicyChecker::check_async`]. By default a
+//! breached password is answered with `422 Unprocessable Entity` and the inner service is
+//! never called. With [`BreachCheckLayer::annotate_only`] every request is forwarded and the
+//! handler reads the [`BreachStatus`] from the request extensions instead.
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use hibp_verifier::OwnedBreachChecker;
+//! use hibp_verifier::policy::{PolicyChecker, PolicyConfig};
+//! use hibp_verifier::tower::BreachCheckLayer;
+//!
+//! let checker = PolicyChecker::new(OwnedBreachChecker::open("/path/to/hibp-data")?, PolicyConfig::default());
+//! let app = axum::Router::new()
+//!     .route("/signup", axum::routing::post(signup))
+//!     .layer(BreachCheckLayer::new(Arc::new(checker)).field("new_password"));
+//! ```
+//!
+//! The request body type must be rebuildable from [`Bytes`], which axum's `Body` and
+//! `http_body_util::Full` are. Requests the extractor is not interested in, such as those
+//! with another content type, are forwarded without their body being read.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{HeaderValue, Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::policy::{Decision, PolicyChecker};
+
+/// Default limit on the bytes buffered from a request body.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Breach-check outcome added to the request extensions of every forwarded request whose
+/// password was found by the extractor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreachStatus {
+    /// The password was not found in the breach database.
+    NotBreached,
+    /// The password was found in the breach database.
+    Breached,
+    /// The check could not be completed (lookup error, timeout or open circuit breaker).
+    /// Only forwarded in reject mode when the failure mode is `Open`.
+    Unavailable,
+}
+
+/// Finds the password in a buffered request.
+pub trait PasswordExtractor: Send + Sync + 'static {
+    /// Whether the body of this request should be buffered and passed to
+    /// [`extract`](Self::extract). Requests that return `false` are forwarded untouched.
+    fn wants(&self, parts: &Parts) -> bool {
+        let _ = parts;
+        true
+    }
+
+    /// The password in this request, or `None` to forward it unchecked.
+    fn extract(&self, parts: &Parts, body: &[u8]) -> Option<String>;
+}
+
+impl<F> PasswordExtractor for F
+where
+    F: Fn(&Parts, &[u8]) -> Option<String> + Send + Sync + 'static,
+{
+    fn extract(&self, parts: &Parts, body: &[u8]) -> Option<String> {
+        self(parts, body)
+    }
+}
+
+/// Reads a top-level string field from a JSON (`application/json` or `*+json`) or
+/// `application/x-www-form-urlencoded` body.
+#[derive(Debug, Clone)]
+pub struct FieldExtractor {
+    field: String,
+}
+
+impl FieldExtractor {
+    pub fn new(field: impl Into<String>) -> Self {
+        Self { field: field.into() }
+    }
+}
+
+enum BodyFormat {
+    Json,
+    Form,
+}
+
+fn body_format(parts: &Parts) -> Option<BodyFormat> {
+    let content_type = parts.headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let essence = content_type.split(';').next()?.trim();
+    if essence.eq_ignore_ascii_case("application/json")
+        || essence.to_ascii_lowercase().ends_with("+json")
+    {
+        Some(BodyFormat::Json)
+    } else if essence.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+        Some(BodyFormat::Form)
+    } else {
+        None
+    }
+}
+
+impl PasswordExtractor for FieldExtractor {
+    fn wants(&self, parts: &Parts) -> bool {
+        body_format(parts).is_some()
+    }
+
+    fn extract(&self, parts: &Parts, body: &[u8]) -> Option<String> {
+        match body_format(parts)? {
+            BodyFormat::Json => {
+                let mut value: serde_json::Value = serde_json::from_slice(body).ok()?;
+                match value.get_mut(&self.field)?.take() {
+                    serde_json::Value::String(password) => Some(password),
+                    _ => None,
+                }
+            }
+            BodyFormat::Form => form_urlencoded::parse(body)
+                .find(|(name, _)| *name == self.field)
+                .map(|(_, password)| password.into_owned()),
+        }
+    }
+}
+
+/// A fixed response the middleware answers with instead of calling the inner service.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    status: StatusCode,
+    content_type: HeaderValue,
+    body: Bytes,
+}
+
+impl Rejection {
+    pub fn new(status: StatusCode, content_type: &'static str, body: impl Into<Bytes>) -> Self {
+        Self { status, content_type: HeaderValue::from_static(content_type), body: body.into() }
+    }
+
+    fn json(status: StatusCode, body: &'static str) -> Self {
+        Self::new(status, "application/json", body)
+    }
+
+    fn response<B: From<Bytes>>(&self) -> Response<B> {
+        let mut response = Response::new(B::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        response.headers_mut().insert(CONTENT_TYPE, self.content_type.clone());
+        response
+    }
+}
+
+/// [`Layer`] that wraps a service in [`BreachCheck`].
+pub struct BreachCheckLayer<E = FieldExtractor> {
+    checker: Arc<PolicyChecker>,
+    extractor: Arc<E>,
+    max_body_bytes: usize,
+    breached: Option<Rejection>,
+    unavailable: Rejection,
+}
+
+impl BreachCheckLayer {
+    /// Checks the `password` field of JSON and form bodies, rejecting breached passwords.
+    pub fn new(checker: Arc<PolicyChecker>) -> Self {
+        Self {
+            checker,
+            extractor: Arc::new(FieldExtractor::new("password")),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            breached: Some(Rejection::json(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                r#"{"error":"breached_password","message":"This password has appeared in a data breach. Please choose a different one."}"#,
+            )),
+            unavailable: Rejection::json(
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"error":"breach_check_unavailable","message":"The password could not be checked. Please try again later."}"#,
+            ),
+        }
+    }
+
+    /// Checks the field named `field` instead of `password`.
+    pub fn field(self, field: impl Into<String>) -> Self {
+        self.extractor(FieldExtractor::new(field))
+    }
+}
+
+impl<E> BreachCheckLayer<E> {
+    /// Finds passwords with `extractor` instead of a [`FieldExtractor`].
+    pub fn extractor<E2: PasswordExtractor>(self, extractor: E2) -> BreachCheckLayer<E2> {
+        BreachCheckLayer {
+            checker: self.checker,
+            extractor: Arc::new(extractor),
+            max_body_bytes: self.max_body_bytes,
+            breached: self.breached,
+            unavailable: self.unavailable,
+        }
+    }
+
+    /// Bodies larger than this are answered with `413 Payload Too Large`. Defaults to
+    /// [`DEFAULT_MAX_BODY_BYTES`].
+    pub fn max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// Answers breached passwords with `rejection`.
+    pub fn reject_with(mut self, rejection: Rejection) -> Self {
+        self.breached = Some(rejection);
+        self
+    }
+
+    /// Answers with `rejection` when the check could not be completed and the failure mode
+    /// is `Closed`. Defaults to `503 Service Unavailable`.
+    pub fn unavailable_with(mut self, rejection: Rejection) -> Self {
+        self.unavailable = rejection;
+        self
+    }
+
+    /// Forwards every request, leaving the decision to the handler via [`BreachStatus`].
+    pub fn annotate_only(mut self) -> Self {
+        self.breached = None;
+        self
+    }
+}
+
+impl<E> Clone for BreachCheckLayer<E> {
+    fn clone(&self) -> Self {
+        Self {
+            checker: self.checker.clone(),
+            extractor: self.extractor.clone(),
+            max_body_bytes: self.max_body_bytes,
+            breached: self.breached.clone(),
+            unavailable: self.unavailable.clone(),
+        }
+    }
+}
+
+impl<S, E> Layer<S> for BreachCheckLayer<E> {
+    type Service = BreachCheck<S, E>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BreachCheck { inner, layer: self.clone() }
+    }
+}
+
+/// Middleware that checks request passwords before calling the inner service. Created by
+/// [`BreachCheckLayer`].
+pub struct BreachCheck<S, E = FieldExtractor> {
+    inner: S,
+    layer: BreachCheckLayer<E>,
+}
+
+impl<S: Clone, E> Clone for BreachCheck<S, E> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone(), layer: self.layer.clone() }
+    }
+}
+
+const PAYLOAD_TOO_LARGE: &str = r#"{"error":"payload_too_large"}"#;
+const INVALID_BODY: &str = r#"{"error":"invalid_body"}"#;
+
+impl<S, E, B, ResBody> Service<Request<B>> for BreachCheck<S, E>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    E: PasswordExtractor,
+    B: Body + From<Bytes> + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResBody: From<Bytes>,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = Response<ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        // Call the instance that was polled ready and leave the clone for the next request.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            if !layer.extractor.wants(&parts) {
+                return inner.call(Request::from_parts(parts, body)).await;
+            }
+            let body = match Limited::new(body, layer.max_body_bytes).collect().await {
+                Ok(collected) => collected.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    return Ok(
+                        Rejection::json(StatusCode::PAYLOAD_TOO_LARGE, PAYLOAD_TOO_LARGE)
+                            .response(),
+                    );
+                }
+                Err(_) => {
+                    return Ok(Rejection::json(StatusCode::BAD_REQUEST, INVALID_BODY).response());
+                }
+            };
+
+            if let Some(password) = layer.extractor.extract(&parts, &body) {
+                let decision = layer.checker.check_async(&password).await;
+                let status = match decision {
+                    Decision::Allow => BreachStatus::NotBreached,
+                    Decision::Reject => BreachStatus::Breached,
+                    Decision::Degraded { .. } => BreachStatus::Unavailable,
+                };
+                if let Some(breached) = &layer.breached {
+                    if status == BreachStatus::Breached {
+                        tracing::debug!(path = %parts.uri.path(), "rejected a breached password");
+                        return Ok(breached.response());
+                    }
+                    if !decision.is_allowed() {
+                        return Ok(layer.unavailable.response());
+                    }
+                }
+                parts.extensions.insert(status);
+            }
+            inner.call(Request::from_parts(parts, B::from(body))).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::path::Path;
+
+    use http_body_util::Full;
+
+    use super::*;
+    use crate::OwnedBreachChecker;
+    use crate::policy::{FailureMode, PolicyConfig};
+    use crate::test_util::write_dataset;
+
+    fn layer(dir: &Path, failure_mode: FailureMode) -> BreachCheckLayer {
+        let config = PolicyConfig { failure_mode, ..PolicyConfig::default() };
+        let checker = PolicyChecker::new(OwnedBreachChecker::open(dir).unwrap(), config);
+        BreachCheckLayer::new(Arc::new(checker))
+    }
+
+    /// Inner service that echoes the request's BreachStatus and body.
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<Full<Bytes>>> for Echo {
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+        type Response = Response<Full<Bytes>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<Full<Bytes>>) -> Self::Future {
+            Box::pin(async move {
+                let status = request.extensions().get::<BreachStatus>().copied();
+                let body = request.into_body().collect().await.unwrap().to_bytes();
+                let echo = format!("{status:?} {}", String::from_utf8_lossy(&body));
+                Ok(Response::new(Full::from(echo)))
+            })
+        }
+    }
+
+    async fn send<E: PasswordExtractor>(
+        layer: &BreachCheckLayer<E>,
+        content_type: &str,
+        body: &str,
+    ) -> (StatusCode, String) {
+        let mut service = layer.layer(Echo);
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let request = Request::post("/signup")
+            .header(CONTENT_TYPE, content_type)
+            .body(Full::from(body.to_owned()))
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    // Breached JSON password → 422 from the layer; clean form password → forwarded with
+    // NotBreached and the body intact.
+    #[tokio::test]
+    async fn rejects_breached_and_forwards_clean() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let layer = layer(tmp.path(), FailureMode::Closed);
+
+        let (status, body) =
+            send(&layer, "application/json", r#"{"password":"password123"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.contains("breached_password"));
+
+        let form = "user=bob&password=hAwT%3F%7DcuC%3Ar%23kW5";
+        let (status, body) = send(&layer, "application/x-www-form-urlencoded", form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("Some(NotBreached) {form}"));
+    }
+
+    // Annotate mode and a custom rejection: breached → forwarded with Breached; reject mode
+    // with a custom response → that response.
+    #[tokio::test]
+    async fn annotate_only_and_custom_rejection() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let json = r#"{"new_password":"password123"}"#;
+
+        let annotate = layer(tmp.path(), FailureMode::Closed).field("new_password").annotate_only();
+        let (status, body) = send(&annotate, "application/json; charset=utf-8", json).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("Some(Breached) {json}"));
+
+        let custom = layer(tmp.path(), FailureMode::Closed).field("new_password").reject_with(
+            Rejection::new(StatusCode::BAD_REQUEST, "text/plain", "pick another"),
+        );
+        assert_eq!(
+            send(&custom, "application/json", json).await,
+            (StatusCode::BAD_REQUEST, "pick another".to_owned())
+        );
+    }
+
+    // No password field, a non-string field or another content type → forwarded unchecked.
+    #[tokio::test]
+    async fn requests_without_a_password_pass_through() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let layer = layer(tmp.path(), FailureMode::Closed);
+
+        for (content_type, body) in [
+            ("application/json", r#"{"username":"bob"}"#),
+            ("application/json", r#"{"password":123}"#),
+            ("text/plain", "password=password123"),
+        ] {
+            assert_eq!(
+                send(&layer, content_type, body).await,
+                (StatusCode::OK, format!("None {body}"))
+            );
+        }
+    }
+
+    // Missing prefix file → 503 when failing closed, forwarded as Unavailable when failing
+    // open. A body over the limit → 413.
+    #[tokio::test]
+    async fn degraded_checks_and_body_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        std::fs::remove_file(tmp.path().join("CBFDA.bin")).unwrap();
+        let json = r#"{"password":"password123"}"#;
+
+        let (status, _) = send(
+            &layer(tmp.path(), FailureMode::Closed),
+            "application/json",
+            json,
+        )
+        .await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (status, body) = send(
+            &layer(tmp.path(), FailureMode::Open),
+            "application/json",
+            json,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("Some(Unavailable) {json}"));
+
+        let small = layer(tmp.path(), FailureMode::Open).max_body_bytes(8);
+        let (status, _) = send(&small, "application/json", json).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    // A closure extractor reading a header instead of the body.
+    #[tokio::test]
+    async fn closure_extractor() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_dataset(tmp.path(), &["password123"], &["hAwT?}cuC:r#kW5"]);
+        let layer = layer(tmp.path(), FailureMode::Closed).extractor(|parts: &Parts, _: &[u8]| {
+            parts.headers.get(CONTENT_TYPE)?.to_str().ok().map(str::to_owned)
+        });
+
+        let (status, _) = send(&layer, "password123", "").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
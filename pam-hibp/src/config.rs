@@ -0,0 +1,47 @@
+//! Module arguments from the PAM configuration line.
+
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+use hibp_verifier::policy::FailureMode;
+
+/// Options parsed from `password requisite pam_hibp.so <args>`.
+#[derive(Debug)]
+pub struct Config {
+    /// `dataset=<dir>`: the dataset directory. Required.
+    pub dataset: Option<PathBuf>,
+    /// `fail_open` or `fail_closed` (the default): whether a password is accepted when the
+    /// dataset cannot be read.
+    pub failure_mode: FailureMode,
+    /// `retry=<n>`: how many passwords the user may try before the change fails. Default 1.
+    pub retry: u32,
+    /// `debug`: log every decision, not only rejections and failures.
+    pub debug: bool,
+}
+
+impl Config {
+    /// Parses the module arguments. Unknown or malformed arguments are returned alongside
+    /// the config so they can be logged; they do not stop the module.
+    pub fn parse<'a>(args: impl IntoIterator<Item = &'a CStr>) -> (Self, Vec<String>) {
+        let mut config =
+            Config { dataset: None, failure_mode: FailureMode::Closed, retry: 1, debug: false };
+        let mut invalid = Vec::new();
+        for arg in args {
+            let arg = arg.to_string_lossy();
+            match arg.split_once('=') {
+                Some(("dataset", path)) if !path.is_empty() => {
+                    config.dataset = Some(PathBuf::from(path));
+                }
+                Some(("retry", n)) => match n.parse() {
+                    Ok(n) if n > 0 => config.retry = n,
+                    _ => invalid.push(arg.into_owned()),
+                },
+                None if arg == "fail_open" => config.failure_mode = FailureMode::Open,
+                None if arg == "fail_closed" => config.failure_mode = FailureMode::Closed,
+                None if arg == "debug" => config.debug = true,
+                _ => invalid.push(arg.into_owned()),
+            }
+        }
+        (config, invalid)
+    }
+}
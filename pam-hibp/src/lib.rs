@@ -0,0 +1,129 @@
+//! A PAM module that rejects new passwords found in a local HIBP dataset.
+//!
+//! Stack it in the `password` phase ahead of the module that stores the password, which then
+//! reuses the password this module prompted for:
+//!
+//! ```text
+//! password requisite pam_hibp.so dataset=/var/lib/hibp/current retry=3
+//! password required  pam_unix.so use_authtok
+//! ```
+//!
+//! Arguments are described on [`Config`](config::Config). Decisions are logged to the
+//! `authpriv` syslog facility; passwords and hashes never are.
+
+use std::ffi::{CStr, c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use hibp_verifier::OwnedBreachChecker;
+use hibp_verifier::policy::{Decision, DegradedReason, FailureMode};
+
+use crate::config::Config;
+use crate::pam::{
+    PAM_AUTHTOK_ERR, PAM_PRELIM_CHECK, PAM_SERVICE_ERR, PAM_SUCCESS, PAM_SYSTEM_ERR,
+    PAM_UPDATE_AUTHTOK, Pam, PamHandle, syslog,
+};
+
+pub mod config;
+mod pam;
+
+const BREACHED_MESSAGE: &CStr =
+    c"This password has appeared in a data breach. Please choose a different one.";
+const UNAVAILABLE_MESSAGE: &CStr =
+    c"The password could not be checked against the breach database. Please try again later.";
+
+/// Checks a password hash against the dataset, turning failures into a degraded decision.
+fn decide(dataset: &Path, failure_mode: FailureMode, hash: &[u8; 20]) -> Decision {
+    match OwnedBreachChecker::open(dataset).and_then(|checker| checker.contains_hash(hash)) {
+        Ok(false) => Decision::Allow,
+        Ok(true) => Decision::Reject,
+        Err(e) => Decision::Degraded { reason: DegradedReason::Lookup(e), failure_mode },
+    }
+}
+
+fn chauthtok(pam: &Pam, flags: c_int, config: &Config) -> c_int {
+    if flags & PAM_PRELIM_CHECK != 0 {
+        return PAM_SUCCESS;
+    }
+    if flags & PAM_UPDATE_AUTHTOK == 0 {
+        return PAM_SERVICE_ERR;
+    }
+    let Some(dataset) = &config.dataset else {
+        syslog(libc::LOG_ERR, "missing required dataset= argument");
+        return PAM_SERVICE_ERR;
+    };
+
+    for _ in 0..config.retry {
+        let hash = match pam.new_password_hash() {
+            Ok(hash) => hash,
+            Err(status) => return status,
+        };
+        let decision = decide(dataset, config.failure_mode, &hash);
+        match &decision {
+            Decision::Allow => {
+                if config.debug {
+                    syslog(libc::LOG_DEBUG, "password not found in breach database");
+                }
+                return PAM_SUCCESS;
+            }
+            Decision::Reject => {
+                syslog(
+                    libc::LOG_NOTICE,
+                    "rejected a password found in the breach database",
+                );
+                pam.error(BREACHED_MESSAGE);
+                pam.clear_new_password();
+            }
+            Decision::Degraded { reason, failure_mode } => {
+                let allowed = decision.is_allowed();
+                let action = if allowed { "accepting" } else { "refusing" };
+                let message = format!(
+                    "breach check failed ({reason}); {action} the password (fail mode {failure_mode:?})"
+                );
+                syslog(libc::LOG_WARNING, &message);
+                if allowed {
+                    return PAM_SUCCESS;
+                }
+                pam.error(UNAVAILABLE_MESSAGE);
+                return PAM_AUTHTOK_ERR;
+            }
+        }
+    }
+    PAM_AUTHTOK_ERR
+}
+
+/// Entry point for the `password` phase.
+///
+/// # Safety
+///
+/// Must only be called by PAM, with `argv` holding `argc` NUL-terminated module arguments.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pam_sm_chauthtok(
+    pamh: *mut PamHandle,
+    flags: c_int,
+    argc: c_int,
+    argv: *const *const c_char,
+) -> c_int {
+    let args: &[*const c_char] = match usize::try_from(argc) {
+        Ok(len) if len > 0 && !argv.is_null() => {
+            // SAFETY: PAM passes `argc` argument pointers.
+            unsafe { std::slice::from_raw_parts(argv, len) }
+        }
+        _ => &[],
+    };
+    // SAFETY: PAM passes NUL-terminated arguments.
+    let (config, invalid) = Config::parse(args.iter().map(|&arg| unsafe { CStr::from_ptr(arg) }));
+    for arg in invalid {
+        syslog(
+            libc::LOG_ERR,
+            &format!("ignoring unknown or malformed argument {arg:?}"),
+        );
+    }
+    // SAFETY: `pamh` is the handle for this call.
+    let pam = unsafe { Pam::new(pamh, flags) };
+
+    panic::catch_unwind(AssertUnwindSafe(|| chauthtok(&pam, flags, &config))).unwrap_or_else(|_| {
+        syslog(libc::LOG_CRIT, "internal error");
+        PAM_SYSTEM_ERR
+    })
+}
@@ -0,0 +1,97 @@
+//! The parts of the Linux-PAM module interface this module uses.
+
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::ptr;
+
+use sha1::{Digest, Sha1};
+
+pub const PAM_SUCCESS: c_int = 0;
+pub const PAM_SERVICE_ERR: c_int = 3;
+pub const PAM_SYSTEM_ERR: c_int = 4;
+pub const PAM_AUTHTOK_ERR: c_int = 20;
+
+const PAM_AUTHTOK: c_int = 6;
+
+pub const PAM_SILENT: c_int = 0x8000;
+pub const PAM_PRELIM_CHECK: c_int = 0x4000;
+pub const PAM_UPDATE_AUTHTOK: c_int = 0x2000;
+
+/// Opaque `pam_handle_t`.
+#[repr(C)]
+pub struct PamHandle {
+    _private: [u8; 0],
+}
+
+// Resolved from the libpam already loaded by the process that loads this module.
+unsafe extern "C" {
+    fn pam_get_authtok(
+        pamh: *mut PamHandle,
+        item: c_int,
+        authtok: *mut *const c_char,
+        prompt: *const c_char,
+    ) -> c_int;
+    fn pam_set_item(pamh: *mut PamHandle, item_type: c_int, item: *const c_void) -> c_int;
+    fn pam_error(pamh: *mut PamHandle, fmt: *const c_char, ...) -> c_int;
+}
+
+/// A PAM handle for the duration of one module call.
+pub struct Pam {
+    handle: *mut PamHandle,
+    silent: bool,
+}
+
+impl Pam {
+    /// # Safety
+    ///
+    /// `handle` must be the handle PAM passed to the current module call.
+    pub unsafe fn new(handle: *mut PamHandle, flags: c_int) -> Self {
+        Self { handle, silent: flags & PAM_SILENT != 0 }
+    }
+
+    /// SHA-1 of the new password, prompting for it (and its confirmation) if no earlier module
+    /// in the stack has.
+    pub fn new_password_hash(&self) -> Result<[u8; 20], c_int> {
+        let mut authtok = ptr::null();
+        // SAFETY: the handle is live for this call; PAM owns the returned string.
+        let status =
+            unsafe { pam_get_authtok(self.handle, PAM_AUTHTOK, &mut authtok, ptr::null()) };
+        if status != PAM_SUCCESS {
+            return Err(status);
+        }
+        if authtok.is_null() {
+            return Err(PAM_AUTHTOK_ERR);
+        }
+        // SAFETY: PAM returns a NUL-terminated string that stays valid until the item changes.
+        let password = unsafe { CStr::from_ptr(authtok) };
+        Ok(Sha1::digest(password.to_bytes()).into())
+    }
+
+    /// Forgets the new password so the next [`new_password_hash`](Self::new_password_hash)
+    /// prompts again.
+    pub fn clear_new_password(&self) {
+        // SAFETY: the handle is live for this call; PAM copies or frees items itself.
+        unsafe { pam_set_item(self.handle, PAM_AUTHTOK, ptr::null()) };
+    }
+
+    /// Shows `message` to the user unless the application asked for silence.
+    pub fn error(&self, message: &CStr) {
+        if !self.silent {
+            // SAFETY: the handle is live for this call and both strings are NUL-terminated.
+            unsafe { pam_error(self.handle, c"%s".as_ptr(), message.as_ptr()) };
+        }
+    }
+}
+
+/// Logs `message` to the authpriv syslog facility.
+pub fn syslog(priority: c_int, message: &str) {
+    let message = message.replace('\0', "");
+    let message = std::ffi::CString::new(message).expect("NUL bytes were removed");
+    // SAFETY: both strings are NUL-terminated and the format takes exactly one string.
+    unsafe {
+        libc::syslog(
+            libc::LOG_AUTHPRIV | priority,
+            c"pam_hibp: %s".as_ptr(),
+            message.as_ptr(),
+        )
+    };
+}
@@ -0,0 +1,126 @@
+/* A stand-in for libpam: provides the functions pam_hibp calls, loads the module with
+ * dlopen and drives pam_sm_chauthtok the way passwd would. Run by tests/pam_harness.rs with
+ * a dataset holding "password123", with an empty prefix file for "correct horse". */
+
+#include <dlfcn.h>
+#include <stdarg.h>
+#include <stdio.h>
+#include <string.h>
+
+#define PAM_SUCCESS 0
+#define PAM_SERVICE_ERR 3
+#define PAM_AUTHTOK_ERR 20
+#define PAM_CONV_ERR 19
+#define PAM_AUTHTOK 6
+#define PAM_SILENT 0x8000
+#define PAM_PRELIM_CHECK 0x4000
+#define PAM_UPDATE_AUTHTOK 0x2000
+
+typedef struct pam_handle {
+    const char *const *answers; /* passwords the user types, in order */
+    int prompts;
+    const char *authtok;
+    int errors;
+} pam_handle_t;
+
+int pam_get_authtok(pam_handle_t *pamh, int item, const char **authtok, const char *prompt) {
+    (void)prompt;
+    if (item != PAM_AUTHTOK) {
+        return PAM_SERVICE_ERR;
+    }
+    if (pamh->authtok == NULL) {
+        if (pamh->answers[pamh->prompts] == NULL) {
+            return PAM_CONV_ERR;
+        }
+        pamh->authtok = pamh->answers[pamh->prompts++];
+    }
+    *authtok = pamh->authtok;
+    return PAM_SUCCESS;
+}
+
+int pam_set_item(pam_handle_t *pamh, int item_type, const void *item) {
+    if (item_type == PAM_AUTHTOK) {
+        pamh->authtok = item;
+    }
+    return PAM_SUCCESS;
+}
+
+int pam_error(pam_handle_t *pamh, const char *fmt, ...) {
+    (void)fmt;
+    pamh->errors++;
+    return PAM_SUCCESS;
+}
+
+typedef int (*chauthtok_fn)(pam_handle_t *, int, int, const char **);
+
+static chauthtok_fn chauthtok;
+static int failures = 0;
+
+/* Runs one password change and checks its result, prompts and messages shown. */
+static void expect(int line, int flags, const char *const *answers, int argc, const char **argv,
+                   int status, int prompts, int errors) {
+    pam_handle_t pamh = {answers, 0, NULL, 0};
+    int got = chauthtok(&pamh, flags, argc, argv);
+    if (got != status || pamh.prompts != prompts || pamh.errors != errors) {
+        fprintf(stderr, "line %d: got status %d, %d prompt(s), %d error(s); expected %d, %d, %d\n",
+                line, got, pamh.prompts, pamh.errors, status, prompts, errors);
+        failures++;
+    }
+}
+
+#define EXPECT(...) expect(__LINE__, __VA_ARGS__)
+
+int main(int argc, char **argv) {
+    if (argc != 4) {
+        fprintf(stderr, "usage: %s <pam_hibp.so> <dataset-arg> <missing-dataset-arg>\n", argv[0]);
+        return 2;
+    }
+    void *module = dlopen(argv[1], RTLD_NOW);
+    if (module == NULL) {
+        fprintf(stderr, "dlopen: %s\n", dlerror());
+        return 1;
+    }
+    chauthtok = (chauthtok_fn)dlsym(module, "pam_sm_chauthtok");
+    if (chauthtok == NULL) {
+        fprintf(stderr, "dlsym: %s\n", dlerror());
+        return 1;
+    }
+
+    const char *breached[] = {"password123", NULL};
+    const char *clean[] = {"correct horse", NULL};
+    const char *retried[] = {"password123", "correct horse", NULL};
+    const char *breached_twice[] = {"password123", "password123", NULL};
+    const char *unchecked[] = {"letmein", NULL};
+
+    const char *dataset[] = {argv[2]};
+    const char *retry[] = {argv[2], "retry=2"};
+    const char *missing[] = {argv[3]};
+    const char *missing_open[] = {argv[3], "fail_open"};
+    const char *noise[] = {"bogus", "retry=0", argv[2]};
+
+    int update = PAM_UPDATE_AUTHTOK;
+
+    /* The preliminary pass does not prompt. */
+    EXPECT(PAM_PRELIM_CHECK, breached, 1, dataset, PAM_SUCCESS, 0, 0);
+    /* Breached → refused with a message; clean → accepted. */
+    EXPECT(update, breached, 1, dataset, PAM_AUTHTOK_ERR, 1, 1);
+    EXPECT(update | PAM_SILENT, breached, 1, dataset, PAM_AUTHTOK_ERR, 1, 0);
+    EXPECT(update, clean, 1, dataset, PAM_SUCCESS, 1, 0);
+    /* With retry=2 the user is prompted again after a breached password. */
+    EXPECT(update, retried, 2, retry, PAM_SUCCESS, 2, 1);
+    EXPECT(update, breached_twice, 2, retry, PAM_AUTHTOK_ERR, 2, 2);
+    /* Dataset unreadable → refused by default, accepted with fail_open. */
+    EXPECT(update, clean, 1, missing, PAM_AUTHTOK_ERR, 1, 1);
+    EXPECT(update, clean, 2, missing_open, PAM_SUCCESS, 1, 0);
+    /* A missing prefix file is a lookup failure too. */
+    EXPECT(update, unchecked, 1, dataset, PAM_AUTHTOK_ERR, 1, 1);
+    /* Bad arguments are ignored; a missing dataset= is a configuration error. */
+    EXPECT(update, clean, 3, noise, PAM_SUCCESS, 1, 0);
+    EXPECT(update, clean, 0, NULL, PAM_SERVICE_ERR, 0, 0);
+
+    if (failures > 0) {
+        fprintf(stderr, "%d scenario(s) failed\n", failures);
+        return 1;
+    }
+    return 0;
+}
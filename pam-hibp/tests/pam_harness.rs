@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use hibp_verifier::test_util::write_dataset;
+
+/// Directory holding `libpam_hibp.so`: cargo leaves the library built for tests in `deps`,
+/// next to this test binary.
+fn lib_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+// The built module loaded into tests/pam_harness.c, a stand-in for libpam → every scenario
+// in it passes.
+#[test]
+fn pam_harness_passes() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp = tempfile::tempdir().unwrap();
+    let dataset = tmp.path().join("dataset");
+    write_dataset(&dataset, &["password123"], &["correct horse"]);
+
+    let module = lib_dir().join("libpam_hibp.so");
+    assert!(module.exists(), "{} not found", module.display());
+    let harness = tmp.path().join("harness");
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(&cc)
+        .args(["-std=c11", "-Wall", "-Wextra", "-Werror", "-rdynamic"])
+        .arg(crate_dir.join("tests/pam_harness.c"))
+        .args(["-ldl", "-o"])
+        .arg(&harness)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {cc}: {e}"));
+    assert!(status.success(), "compiling pam_harness.c failed");
+
+    let output = Command::new(&harness)
+        .arg(&module)
+        .arg(format!("dataset={}", dataset.display()))
+        .arg(format!("dataset={}", tmp.path().join("missing").display()))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}